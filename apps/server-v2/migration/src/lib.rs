@@ -1,12 +1,29 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20250809_185819_create_users;
+mod m20261018_090000_add_deleted_at_to_users;
+mod m20261018_100000_manage_users_timestamps;
+mod m20261018_110000_add_version_to_users;
+mod m20261018_120000_add_password_hash_to_users;
+mod m20261018_130000_create_api_keys;
+mod m20261018_140000_add_role_to_users;
+mod m20261018_150000_ignore_api_key_usage_in_updated_at;
+mod timestamps;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20250809_185819_create_users::Migration)]
+        vec![
+            Box::new(m20250809_185819_create_users::Migration),
+            Box::new(m20261018_090000_add_deleted_at_to_users::Migration),
+            Box::new(m20261018_100000_manage_users_timestamps::Migration),
+            Box::new(m20261018_110000_add_version_to_users::Migration),
+            Box::new(m20261018_120000_add_password_hash_to_users::Migration),
+            Box::new(m20261018_130000_create_api_keys::Migration),
+            Box::new(m20261018_140000_add_role_to_users::Migration),
+            Box::new(m20261018_150000_ignore_api_key_usage_in_updated_at::Migration),
+        ]
     }
 }
@@ -0,0 +1,19 @@
+use sea_orm_migration::prelude::*;
+
+use crate::timestamps;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        timestamps::create_function(manager).await?;
+        timestamps::manage(manager, "users").await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        timestamps::unmanage(manager, "users").await?;
+        timestamps::drop_function(manager).await
+    }
+}
@@ -0,0 +1,20 @@
+use sea_orm_migration::prelude::*;
+
+use crate::timestamps;
+
+/// Recording the use of a key is not an edit of it
+const USAGE_COLUMNS: [&str; 2] = ["usage_count", "last_used_at"];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        timestamps::ignore(manager, "api_keys", &USAGE_COLUMNS).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        timestamps::ignore(manager, "api_keys", &[]).await
+    }
+}
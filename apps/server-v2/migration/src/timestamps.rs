@@ -0,0 +1,102 @@
+//! Shared by every migration that creates a table with `created_at` and
+//! `updated_at`, so new entities get the same database-side behaviour.
+
+use sea_orm_migration::prelude::*;
+
+const FUNCTION: &str = "set_updated_at";
+
+fn trigger_name(table: &str) -> String {
+    format!("{table}_{FUNCTION}")
+}
+
+/// (Re)creates the `updated_at` trigger of `table`. Updates that change
+/// nothing but `ignored` columns leave the stamp alone.
+fn trigger_sql(table: &str, ignored: &[&str]) -> String {
+    let trigger = trigger_name(table);
+    let when = if ignored.is_empty() {
+        String::new()
+    } else {
+        let columns = format!("'{{{}}}'::text[]", ignored.join(","));
+        format!("WHEN (to_jsonb(OLD) - {columns} IS DISTINCT FROM to_jsonb(NEW) - {columns})")
+    };
+    format!(
+        "DROP TRIGGER IF EXISTS {trigger} ON {table};
+        CREATE TRIGGER {trigger} BEFORE UPDATE ON {table}
+            FOR EACH ROW {when} EXECUTE FUNCTION {FUNCTION}();"
+    )
+}
+
+/// Trigger function that stamps `updated_at` on every update. Safe to run
+/// more than once.
+pub async fn create_function(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let sql = format!(
+        "CREATE OR REPLACE FUNCTION {FUNCTION}() RETURNS trigger AS $$
+        BEGIN
+            NEW.updated_at = now();
+            RETURN NEW;
+        END;
+        $$ LANGUAGE plpgsql"
+    );
+    manager.get_connection().execute_unprepared(&sql).await?;
+    Ok(())
+}
+
+pub async fn drop_function(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let sql = format!("DROP FUNCTION IF EXISTS {FUNCTION}()");
+    manager.get_connection().execute_unprepared(&sql).await?;
+    Ok(())
+}
+
+/// Attaches the `updated_at` trigger to `table`, created with both columns
+/// already NOT NULL and defaulting to `now()`.
+pub async fn attach(manager: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    let sql = trigger_sql(table, &[]);
+    manager.get_connection().execute_unprepared(&sql).await?;
+    Ok(())
+}
+
+/// Backfills null timestamps, gives both columns a `now()` default and a
+/// NOT NULL constraint, and attaches the `updated_at` trigger to `table`.
+pub async fn manage(manager: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    let trigger = trigger_sql(table, &[]);
+    let sql = format!(
+        "UPDATE {table} SET created_at = COALESCE(created_at, updated_at, now())
+            WHERE created_at IS NULL;
+        UPDATE {table} SET updated_at = created_at WHERE updated_at IS NULL;
+        ALTER TABLE {table}
+            ALTER COLUMN created_at SET DEFAULT now(),
+            ALTER COLUMN created_at SET NOT NULL,
+            ALTER COLUMN updated_at SET DEFAULT now(),
+            ALTER COLUMN updated_at SET NOT NULL;
+        {trigger}"
+    );
+    manager.get_connection().execute_unprepared(&sql).await?;
+    Ok(())
+}
+
+/// Stops updates to `columns` alone from moving `updated_at`, for bookkeeping
+/// that isn't an edit of the row. An empty list stamps every update again.
+pub async fn ignore(
+    manager: &SchemaManager<'_>,
+    table: &str,
+    columns: &[&str],
+) -> Result<(), DbErr> {
+    let sql = trigger_sql(table, columns);
+    manager.get_connection().execute_unprepared(&sql).await?;
+    Ok(())
+}
+
+/// Reverts [`manage`]; the backfilled values are kept.
+pub async fn unmanage(manager: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    let trigger = trigger_name(table);
+    let sql = format!(
+        "DROP TRIGGER IF EXISTS {trigger} ON {table};
+        ALTER TABLE {table}
+            ALTER COLUMN created_at DROP DEFAULT,
+            ALTER COLUMN created_at DROP NOT NULL,
+            ALTER COLUMN updated_at DROP DEFAULT,
+            ALTER COLUMN updated_at DROP NOT NULL;"
+    );
+    manager.get_connection().execute_unprepared(&sql).await?;
+    Ok(())
+}
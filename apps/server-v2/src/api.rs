@@ -1,3 +1,7 @@
+pub mod docs;
+pub mod guards;
 pub mod main;
 pub mod middlewares;
+pub mod pagination;
+pub mod preconditions;
 pub mod routes;
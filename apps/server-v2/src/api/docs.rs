@@ -0,0 +1,343 @@
+use actix_web::{HttpResponse, Responder, get, web};
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::{JsonSchema, Schema};
+use serde_json::{Map, Value, json};
+
+use super::guards::API_KEY_HEADER;
+use crate::core::permissions::Permission;
+use crate::schemas::api::ErrorResponse;
+
+/// OpenAPI 3.1 document of the API. Schemas come from the `JsonSchema`
+/// derives of the request and response types, whose JSON Schema 2020-12
+/// dialect is the one OpenAPI 3.1 uses; operations are described by each
+/// routes module next to its handlers. An operation's `id` is the name of the
+/// actix resource it documents, i.e. its handler, which lets the tests check
+/// every registered route against the document.
+pub struct ApiDoc {
+    generator: SchemaGenerator,
+    paths: Map<String, Value>,
+}
+
+impl Default for ApiDoc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApiDoc {
+    pub fn new() -> Self {
+        let settings = SchemaSettings::draft2020_12().with(|settings| {
+            settings.definitions_path = "/components/schemas".into();
+            settings.meta_schema = None;
+        });
+        ApiDoc {
+            generator: settings.into_generator(),
+            paths: Map::new(),
+        }
+    }
+
+    /// Adds the operation `method path`, `path` being relative to the prefix.
+    pub fn route(
+        &mut self,
+        method: &str,
+        path: &str,
+        operation: impl FnOnce(Operation) -> Operation,
+    ) -> &mut Self {
+        let operation = operation(Operation {
+            generator: &mut self.generator,
+            operation: Map::new(),
+        });
+        let item = self
+            .paths
+            .entry(path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        item[method] = Value::Object(operation.operation);
+        self
+    }
+
+    pub fn into_json(mut self, prefix: &str) -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "info": {
+                "title": "User Management API",
+                "version": "2.0.0",
+            },
+            "servers": [{"url": prefix}],
+            "paths": self.paths,
+            "components": {
+                "schemas": self.generator.take_definitions(true),
+                "securitySchemes": {
+                    BEARER_AUTH: {"type": "http", "scheme": "bearer", "bearerFormat": "JWT"},
+                    API_KEY_AUTH: {
+                        "type": "apiKey",
+                        "in": "header",
+                        "name": API_KEY_HEADER,
+                        "description": "Also accepted as `Authorization: ApiKey <key>`",
+                    },
+                },
+            },
+        })
+    }
+}
+
+/// Security scheme of the endpoints behind the `authenticate` middleware.
+pub const BEARER_AUTH: &str = "bearerAuth";
+
+/// Security scheme of the API keys, whose requirements list the scopes needed.
+pub const API_KEY_AUTH: &str = "apiKeyAuth";
+
+/// Builder for one operation of an [`ApiDoc`].
+pub struct Operation<'a> {
+    generator: &'a mut SchemaGenerator,
+    operation: Map<String, Value>,
+}
+
+impl Operation<'_> {
+    fn schema<T: JsonSchema>(&mut self) -> Schema {
+        self.generator.subschema_for::<T>()
+    }
+
+    fn push(&mut self, key: &str, value: Value) {
+        let list = self
+            .operation
+            .entry(key)
+            .or_insert_with(|| Value::Array(vec![]));
+        if let Value::Array(list) = list {
+            list.push(value);
+        }
+    }
+
+    fn respond(&mut self, status: u16, response: Value) {
+        let responses = self
+            .operation
+            .entry("responses")
+            .or_insert_with(|| Value::Object(Map::new()));
+        responses[status.to_string()] = response;
+    }
+
+    pub fn id(mut self, operation_id: &str) -> Self {
+        self.operation
+            .insert("operationId".into(), operation_id.into());
+        self
+    }
+
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.operation.insert("summary".into(), summary.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.push("tags", tag.into());
+        self
+    }
+
+    /// The operation needs a bearer token, and answers 401 without a valid one.
+    pub fn authenticated(mut self) -> Self {
+        self.push("security", json!({BEARER_AUTH: []}));
+        self.error(401, "Missing, invalid or expired bearer token")
+    }
+
+    /// Callers need `permission`: a role granting it, or an API key with
+    /// the matching scope.
+    pub fn permission(mut self, permission: Permission) -> Self {
+        let scope = permission.scope();
+        self.push("security", json!({API_KEY_AUTH: [scope.as_str()]}));
+        self.error(
+            403,
+            &format!(
+                "Needs role {}, or an API key with the `{scope}` scope",
+                permission.roles()
+            ),
+        )
+    }
+
+    pub fn path_param<T: JsonSchema>(mut self, name: &str) -> Self {
+        let schema = self.schema::<T>();
+        self.push(
+            "parameters",
+            json!({"name": name, "in": "path", "required": true, "schema": schema}),
+        );
+        self
+    }
+
+    /// One query parameter per field of `T`, the struct a handler extracts
+    /// with `web::Query<T>`.
+    pub fn query<T: JsonSchema>(mut self) -> Self {
+        let schema = T::json_schema(self.generator);
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| required.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let properties = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+
+        for (name, mut property) in properties {
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": required.contains(&name.as_str()),
+            });
+            if let Some(description) = property
+                .as_object_mut()
+                .and_then(|p| p.remove("description"))
+            {
+                parameter["description"] = description;
+            }
+            parameter["schema"] = property;
+            self.push("parameters", parameter);
+        }
+        self
+    }
+
+    pub fn header(mut self, name: &str, description: &str, required: bool) -> Self {
+        self.push(
+            "parameters",
+            json!({
+                "name": name,
+                "in": "header",
+                "required": required,
+                "description": description,
+                "schema": {"type": "string"},
+            }),
+        );
+        self
+    }
+
+    fn add_body(&mut self, content_type: &str, schema: Value) {
+        let body = self
+            .operation
+            .entry("requestBody")
+            .or_insert_with(|| json!({"required": true, "content": {}}));
+        body["content"][content_type] = json!({"schema": schema});
+    }
+
+    /// May be called once per accepted content type.
+    pub fn body<T: JsonSchema>(mut self, content_type: &str) -> Self {
+        let schema = self.schema::<T>().to_value();
+        self.add_body(content_type, schema);
+        self
+    }
+
+    /// A body that is not JSON, such as an upload.
+    pub fn raw_body(mut self, content_types: &[&str]) -> Self {
+        for content_type in content_types {
+            self.add_body(content_type, json!({"type": "string"}));
+        }
+        self
+    }
+
+    pub fn response<T: JsonSchema>(mut self, status: u16, description: &str) -> Self {
+        let schema = self.schema::<T>();
+        self.respond(
+            status,
+            json!({
+                "description": description,
+                "content": {"application/json": {"schema": schema}},
+            }),
+        );
+        self
+    }
+
+    /// A JSON response whose shape depends on the request.
+    pub fn response_one_of<A: JsonSchema, B: JsonSchema>(
+        mut self,
+        status: u16,
+        description: &str,
+    ) -> Self {
+        let schema = json!({"oneOf": [self.schema::<A>(), self.schema::<B>()]});
+        self.respond(
+            status,
+            json!({
+                "description": description,
+                "content": {"application/json": {"schema": schema}},
+            }),
+        );
+        self
+    }
+
+    /// A response that is not JSON, such as an export.
+    pub fn raw_response(mut self, status: u16, description: &str, content_types: &[&str]) -> Self {
+        let content: Map<String, Value> = content_types
+            .iter()
+            .map(|content_type| {
+                (
+                    content_type.to_string(),
+                    json!({"schema": {"type": "string"}}),
+                )
+            })
+            .collect();
+        self.respond(
+            status,
+            json!({"description": description, "content": content}),
+        );
+        self
+    }
+
+    pub fn empty_response(mut self, status: u16, description: &str) -> Self {
+        self.respond(status, json!({"description": description}));
+        self
+    }
+
+    /// An error answered with the usual [`ErrorResponse`] body.
+    pub fn error(self, status: u16, description: &str) -> Self {
+        self.response::<ErrorResponse>(status, description)
+    }
+}
+
+/// The document, built once by [`super::main::handler`] for its prefix.
+pub struct OpenApi(pub Value);
+
+#[get("/openapi.json")]
+pub async fn openapi(document: web::Data<OpenApi>) -> impl Responder {
+    HttpResponse::Ok().json(&document.0)
+}
+
+// The UIs load the document relative to their own URL, so they work under any
+// prefix. Their bundles are pinned so a release can't change the pages unnoticed
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>User Management API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5.17.14/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+const REDOC: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>User Management API</title>
+</head>
+<body>
+  <redoc spec-url="openapi.json"></redoc>
+  <script src="https://cdn.redoc.ly/redoc/v2.5.0/bundles/redoc.standalone.js"></script>
+</body>
+</html>
+"#;
+
+#[get("/docs")]
+pub async fn swagger_ui() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(SWAGGER_UI)
+}
+
+#[get("/redoc")]
+pub async fn redoc() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(REDOC)
+}
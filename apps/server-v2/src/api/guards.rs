@@ -0,0 +1,300 @@
+use std::future::{Ready, ready};
+
+use std::marker::PhantomData;
+
+use actix_web::dev::Payload;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, web};
+use futures::future::LocalBoxFuture;
+
+use crate::core::config::SETTINGS;
+use crate::core::database::DatabaseService;
+use crate::core::errors::AppError;
+use crate::core::jwt::{Claims, JWT};
+use crate::core::permissions::Permission;
+use crate::crud::UserService;
+use crate::crud::api_keys::{ApiKeyService, invalid_api_key};
+use crate::models::api_keys::Model as ApiKeyModel;
+use crate::models::users::Model as UserModel;
+
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// Compares in time independent of where the inputs differ, so the key
+/// cannot be guessed byte by byte from response times.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Extractor for admin-only handlers: the request must carry `ADMIN_API_KEY`
+/// in the `X-Admin-Key` header.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(expected) = SETTINGS.admin_api_key.as_ref() else {
+            return ready(Err(AppError::Forbidden(
+                "Admin endpoints are disabled, ADMIN_API_KEY is not set".to_string(),
+            )));
+        };
+        let result = match req.headers().get(ADMIN_KEY_HEADER) {
+            None => Err(AppError::Unauthorized(format!(
+                "Missing {ADMIN_KEY_HEADER} header"
+            ))),
+            Some(key) if constant_time_eq(key.as_bytes(), expected.expose().as_bytes()) => {
+                Ok(Admin)
+            }
+            Some(_) => Err(AppError::Forbidden("Invalid admin key".to_string())),
+        };
+        ready(result)
+    }
+}
+
+/// Header an API key may come in, besides `Authorization: ApiKey <key>`.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Scheme and credentials of the `Authorization` header. The scheme is
+/// case-insensitive (RFC 9110, section 11.1).
+fn authorization(req: &HttpRequest) -> Result<Option<(&str, &str)>, AppError> {
+    let Some(header) = req.headers().get(AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let header = header
+        .to_str()
+        .map_err(|_| AppError::Unauthorized("Malformed Authorization header".to_string()))?;
+    Ok(header
+        .split_once(' ')
+        .map(|(scheme, credentials)| (scheme, credentials.trim())))
+}
+
+/// The token of an `Authorization: Bearer <token>` header.
+pub fn bearer_token(req: &HttpRequest) -> Result<&str, AppError> {
+    match authorization(req)? {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => Ok(token),
+        _ => Err(AppError::Unauthorized("Missing bearer token".to_string())),
+    }
+}
+
+/// The key of an `X-API-Key` or `Authorization: ApiKey <key>` header.
+pub fn api_key(req: &HttpRequest) -> Result<Option<&str>, AppError> {
+    if let Some(key) = req.headers().get(API_KEY_HEADER) {
+        let key = key
+            .to_str()
+            .map_err(|_| AppError::Unauthorized("Invalid API key".to_string()))?;
+        return Ok(Some(key));
+    }
+    Ok(match authorization(req)? {
+        Some((scheme, key)) if scheme.eq_ignore_ascii_case("apikey") => Some(key),
+        _ => None,
+    })
+}
+
+/// Verifies the request's bearer token against [`JWT`].
+pub fn verify_bearer(req: &HttpRequest) -> Result<Claims, AppError> {
+    JWT.decode(bearer_token(req)?)
+}
+
+/// The outcome of verifying the request's API key, left in the request
+/// extensions by the rate limiter so [`Caller::identify`] doesn't verify the
+/// key again. `None` when the key is invalid.
+#[derive(Debug, Clone)]
+pub struct VerifiedApiKey(pub Option<ApiKeyModel>);
+
+/// Who a request comes from: a user or service holding a token, or another
+/// service holding an API key.
+#[derive(Debug, Clone)]
+pub enum Caller {
+    Token(Claims),
+    ApiKey(ApiKeyModel),
+}
+
+impl Caller {
+    /// An API key when one is given, the bearer token otherwise.
+    pub async fn identify(req: &HttpRequest) -> Result<Caller, AppError> {
+        let Some(key) = api_key(req)? else {
+            return verify_bearer(req).map(Caller::Token);
+        };
+        let db = req
+            .app_data::<web::Data<DatabaseService>>()
+            .ok_or_else(|| AppError::Internal("Database is not configured".to_string()))?;
+        let api_key_service = ApiKeyService {};
+        let verified = req.extensions().get::<VerifiedApiKey>().cloned();
+        let api_key = match verified {
+            Some(VerifiedApiKey(Some(api_key))) => api_key,
+            Some(VerifiedApiKey(None)) => return Err(invalid_api_key()),
+            None => api_key_service.verify(key, &db.connection).await?,
+        };
+        api_key_service.record_usage(&api_key, &db.connection).await;
+        Ok(Caller::ApiKey(api_key))
+    }
+}
+
+/// Behind the [`authenticate`](super::middlewares::auth::authenticate)
+/// middleware the caller is already in the request extensions, elsewhere the
+/// credentials are checked here.
+impl FromRequest for Caller {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let caller = req.extensions().get::<Caller>().cloned();
+        let req = req.clone();
+        Box::pin(async move {
+            match caller {
+                Some(caller) => Ok(caller),
+                None => Caller::identify(&req).await,
+            }
+        })
+    }
+}
+
+/// Extractor for the verified claims of a caller holding a token.
+pub struct Authenticated(pub Claims);
+
+impl FromRequest for Authenticated {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let not_a_key = || {
+            AppError::Forbidden("This endpoint takes a bearer token, not an API key".to_string())
+        };
+        let result = match req.extensions().get::<Caller>() {
+            Some(Caller::Token(claims)) => Ok(Authenticated(claims.clone())),
+            Some(Caller::ApiKey(_)) => Err(not_a_key()),
+            None => match api_key(req) {
+                Ok(Some(_)) => Err(not_a_key()),
+                Ok(None) => verify_bearer(req).map(Authenticated),
+                Err(e) => Err(e),
+            },
+        };
+        ready(result)
+    }
+}
+
+/// What a caller may do with users: API keys are held to their scopes, users
+/// to their role. Tokens issued with the admin key to a subject that is not a
+/// user act for the admin.
+#[derive(Debug, Clone)]
+pub enum Access {
+    ApiKey(ApiKeyModel),
+    User(UserModel),
+    Service,
+}
+
+impl Access {
+    /// Looks up the role of a user on every request, so changing it or
+    /// deactivating the user takes effect before their tokens expire.
+    pub async fn resolve(caller: Caller, req: &HttpRequest) -> Result<Access, AppError> {
+        let claims = match caller {
+            Caller::ApiKey(api_key) => return Ok(Access::ApiKey(api_key)),
+            Caller::Token(claims) => claims,
+        };
+        let Some(user_id) = claims.user_id() else {
+            return Ok(Access::Service);
+        };
+        let gone = || AppError::Forbidden("The user this token was issued to is gone".to_string());
+        let user_id = u16::try_from(user_id).map_err(|_| gone())?;
+        let db = req
+            .app_data::<web::Data<DatabaseService>>()
+            .ok_or_else(|| AppError::Internal("Database is not configured".to_string()))?;
+        let user_service = UserService {};
+        let user = match user_service.get_user_by_id(user_id, &db.connection).await {
+            Ok(user) => user,
+            Err(AppError::NotFound(_)) => return Err(gone()),
+            Err(e) => return Err(e),
+        };
+        if user.is_active == Some(false) {
+            return Err(AppError::Forbidden("User is deactivated".to_string()));
+        }
+        Ok(Access::User(user))
+    }
+
+    /// Whether the `id` or `email` in the route's path names the caller.
+    pub fn owns(&self, req: &HttpRequest) -> bool {
+        let Access::User(user) = self else {
+            return false;
+        };
+        let path = req.match_info();
+        path.get("id").and_then(|id| id.parse().ok()) == Some(user.id)
+            || path.get("email") == Some(user.email.as_str())
+    }
+
+    /// A 403 with the reason unless the caller has `permission`; `own` tells
+    /// whether the request targets the caller's own record.
+    pub fn check(&self, permission: Permission, own: bool) -> Result<(), AppError> {
+        let denial = match self {
+            Access::ApiKey(api_key) => {
+                let scope = permission.scope();
+                (!scope.granted_to(api_key))
+                    .then(|| format!("API key \"{}\" lacks the {scope} scope", api_key.name))
+            }
+            Access::User(user) => permission.denial(user.role, own),
+            Access::Service => None,
+        };
+        denial.map_or(Ok(()), |reason| Err(AppError::Forbidden(reason)))
+    }
+}
+
+/// A permission a route needs, declared with [`Permit`].
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+pub struct ListUsers;
+
+impl RequiredPermission for ListUsers {
+    const PERMISSION: Permission = Permission::ListUsers;
+}
+
+pub struct ReadUser;
+
+impl RequiredPermission for ReadUser {
+    const PERMISSION: Permission = Permission::ReadUser;
+}
+
+pub struct CreateUsers;
+
+impl RequiredPermission for CreateUsers {
+    const PERMISSION: Permission = Permission::CreateUsers;
+}
+
+pub struct UpdateUser;
+
+impl RequiredPermission for UpdateUser {
+    const PERMISSION: Permission = Permission::UpdateUser;
+}
+
+pub struct DeleteUsers;
+
+impl RequiredPermission for DeleteUsers {
+    const PERMISSION: Permission = Permission::DeleteUsers;
+}
+
+pub struct ManageAccess;
+
+impl RequiredPermission for ManageAccess {
+    const PERMISSION: Permission = Permission::ManageAccess;
+}
+
+/// Extractor that lets the request through only if the caller has the
+/// permission `P`, counting the record in the path as theirs when it is.
+/// Carries the caller's access for checks that depend on the request body.
+pub struct Permit<P>(pub Access, PhantomData<P>);
+
+impl<P: RequiredPermission> FromRequest for Permit<P> {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let caller = Caller::from_request(req, payload);
+        let req = req.clone();
+        Box::pin(async move {
+            let access = Access::resolve(caller.await?, &req).await?;
+            access.check(P::PERMISSION, access.owns(&req))?;
+            Ok(Permit(access, PhantomData))
+        })
+    }
+}
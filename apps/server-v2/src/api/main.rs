@@ -1,91 +1,138 @@
-use super::routes::handler_users;
-use actix_web::{Responder, Scope, get, http::Error, web};
+use super::docs::{self, ApiDoc, OpenApi};
+use super::routes::{api_keys, auth, handler_api_keys, handler_auth, handler_users, users};
+use actix_web::error::JsonPayloadError;
+use actix_web::{HttpRequest, HttpResponse, Responder, Scope, get, http::Error, web};
+use schemars::JsonSchema;
 use serde::Serialize;
 
+use crate::core::errors::AppError;
+
 // For healthchecks
-use crate::core::cache::REDIS_SERVICE;
-use crate::core::config::SETTINGS;
-use crate::core::database::{DatabaseParams, DatabaseService};
+use crate::core::health::{HealthReport, HealthService, HealthStatus};
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct Root {
     message: String,
     version: String,
+    openapi: String,
     docs: String,
     redoc: String,
 }
 
-#[derive(Serialize)]
-struct Health {
+#[derive(Serialize, JsonSchema)]
+struct Liveness {
     status: String,
-    database: String,
-    cache: String,
-    questdb: String,
 }
 
 #[get("/")]
-pub async fn root() -> Result<impl Responder, Error> {
+pub async fn root(req: HttpRequest) -> Result<impl Responder, Error> {
+    // This route is `{prefix}/`, so the path is also where the docs live
+    let base = req.path();
     Ok(web::Json(Root {
         message: "User Management API".to_owned(),
         version: "2.0.0".to_owned(),
-        docs: "/docs".to_owned(),
-        redoc: "/redoc".to_owned(),
+        openapi: format!("{base}openapi.json"),
+        docs: format!("{base}docs"),
+        redoc: format!("{base}redoc"),
     }))
 }
 
+/// Readiness: checks every dependency and answers 503 unless all of them are healthy.
 #[get("/health")]
-pub async fn health_check() -> Result<impl Responder, Error> {
-    let mut health_status = Health {
-        status: "healthy".to_owned(),
-        database: "unknown".to_owned(),
-        cache: "unknown".to_owned(),
-        questdb: "unknown".to_owned(),
-    };
-
-    // Check database connection
-    /* Another way to check the database connection
-    let exec = db
-    .connection
-    .execute(Statement::from_string(
-        DatabaseBackend::Postgres,
-        "SELECT 1",
-    ))
-    .await;
-    */
-    let db = DatabaseService::init(None).await;
-    match db.connection.ping().await {
-        Ok(_) => health_status.database = "healthy".to_owned(),
-        Err(_) => health_status.database = "unhealthy".to_owned(),
+pub async fn health_check(health: web::Data<HealthService>) -> Result<impl Responder, Error> {
+    let report = health.check().await;
+    if report.status != HealthStatus::Healthy {
+        log::warn!("Health check reported {:?}", report.status);
     }
 
-    // Check Redis cache connection
-    match REDIS_SERVICE.ping() {
-        Ok(_) => health_status.cache = "healthy".to_owned(),
-        Err(_) => health_status.cache = "unhealthy".to_owned(),
-    }
+    Ok(HttpResponse::build(report.get_status_code()).json(report))
+}
 
-    // Check QuestDB connection
-    let questdb_conn = DatabaseService::init(Some(DatabaseParams {
-        protocol: Some("postgres".to_string()),
-        host: SETTINGS.questdb_host.clone(),
-        port: SETTINGS.questdb_pg_port.clone(),
-        db: SETTINGS.questdb_db.clone(),
-        user: SETTINGS.questdb_user.clone().ok(),
-        password: SETTINGS.questdb_password.clone().ok(),
+/// Liveness: only tells that the process is able to serve requests.
+#[get("/health/live")]
+pub async fn liveness_check() -> Result<impl Responder, Error> {
+    Ok(web::Json(Liveness {
+        status: "alive".to_owned(),
     }))
-    .await;
+}
 
-    match questdb_conn.connection.ping().await {
-        Ok(_) => health_status.questdb = "healthy".to_owned(),
-        Err(_) => health_status.questdb = "unhealthy".to_owned(),
-    };
+/// Extractor failures answer with the same body as every other error. Routes
+/// with their own body limit start from this one.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|e, _| match e {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            AppError::PayloadTooLarge(format!("Request body too large: {e}")).into()
+        }
+        e => AppError::BadRequest(format!("Invalid JSON body: {e}")).into(),
+    })
+}
 
-    Ok(web::Json(health_status))
+/// The OpenAPI document of everything [`handler`] serves under `prefix`.
+pub fn openapi(prefix: &str) -> serde_json::Value {
+    let mut doc = ApiDoc::new();
+    doc.route("get", "/", |op| {
+        op.id("root")
+            .tag("service")
+            .summary("Name, version and documentation links")
+            .response::<Root>(200, "Service information")
+    })
+    .route("get", "/health", |op| {
+        op.id("health_check")
+            .tag("service")
+            .summary("Readiness, with the state of every dependency")
+            .response::<HealthReport>(200, "Every dependency is healthy")
+            .response::<HealthReport>(503, "A dependency is degraded or down")
+    })
+    .route("get", "/health/live", |op| {
+        op.id("liveness_check")
+            .tag("service")
+            .summary("Liveness")
+            .response::<Liveness>(200, "The process serves requests")
+    })
+    .route("get", "/openapi.json", |op| {
+        op.id("openapi")
+            .tag("service")
+            .summary("This document")
+            .empty_response(200, "OpenAPI 3.1 document")
+    })
+    .route("get", "/docs", |op| {
+        op.id("swagger_ui")
+            .tag("service")
+            .summary("Swagger UI for this document")
+            .raw_response(200, "HTML page", &["text/html"])
+    })
+    .route("get", "/redoc", |op| {
+        op.id("redoc")
+            .tag("service")
+            .summary("ReDoc for this document")
+            .raw_response(200, "HTML page", &["text/html"])
+    });
+    auth::docs(&mut doc);
+    api_keys::docs(&mut doc);
+    users::docs(&mut doc);
+    doc.into_json(prefix)
 }
 
 pub fn handler(prefix: &str) -> Scope {
+    let json_config = json_config();
+    // A path that doesn't parse (e.g. an out of range ID) names no resource, hence 404
+    let path_config = web::PathConfig::default()
+        .error_handler(|e, _| AppError::NotFound(format!("Invalid path: {e}")).into());
+    let query_config = web::QueryConfig::default()
+        .error_handler(|e, _| AppError::BadRequest(format!("Invalid query: {e}")).into());
+
     web::scope(prefix)
+        .app_data(json_config)
+        .app_data(path_config)
+        .app_data(query_config)
         .service(root)
         .service(health_check)
+        .service(liveness_check)
+        .app_data(web::Data::new(OpenApi(openapi(prefix))))
+        .service(docs::openapi)
+        .service(docs::swagger_ui)
+        .service(docs::redoc)
+        .service(handler_auth())
+        .service(handler_api_keys())
         .service(handler_users())
 }
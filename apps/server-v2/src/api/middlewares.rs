@@ -1,2 +1,7 @@
+pub mod auth;
 pub mod logs;
-mod utils;
+pub mod rate_limit;
+pub mod request_id;
+pub mod shipper;
+pub mod trace;
+pub(crate) mod utils;
@@ -0,0 +1,26 @@
+use actix_web::{
+    Error, HttpMessage,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+
+use crate::api::guards::Caller;
+
+/// Rejects requests without a valid API key or bearer token with a 401, and
+/// hands the [`Caller`] of the others to the handlers through the request
+/// extensions.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    match Caller::identify(req.request()).await {
+        Ok(caller) => {
+            req.extensions_mut().insert(caller);
+            Ok(next.call(req).await?.map_into_left_body())
+        }
+        // Answered here rather than returned as `Err`, so the response goes
+        // back through the outer middlewares like any other
+        Err(e) => Ok(req.error_response(e).map_into_right_body()),
+    }
+}
@@ -3,14 +3,18 @@ use std::time::Instant;
 
 use actix_web::http::Version;
 use actix_web::{
-    Error,
+    Error, HttpMessage,
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
     middleware::Next,
 };
 
+use super::request_id::RequestId;
 use super::utils::{Params, ReqParams, ResParams, send_logs_to_questdb};
 
+/// Headers carrying credentials, logged without their value
+const REDACTED_HEADERS: [&str; 4] = ["authorization", "cookie", "x-admin-key", "x-api-key"];
+
 pub async fn dispatch_logs(
     req: ServiceRequest,
     next: Next<impl MessageBody>,
@@ -26,12 +30,21 @@ pub async fn dispatch_logs(
         _ => "Unknown", // Handle any future or unhandled versions
     };
 
+    // Its own statement, connection_info() below borrows the extensions mutably
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(RequestId::to_string)
+        .unwrap_or_default();
+
     let req_params = ReqParams {
+        request_id,
         method: req.method().to_string(),
         headers: req
             .headers()
             .iter()
             .map(|(key, value)| match value.to_str() {
+                Ok(_) if REDACTED_HEADERS.contains(&key.as_str()) => format!("{}: ********", key),
                 Ok(v) => format!("{}: {}", key, v),
                 Err(_) => String::new(),
             })
@@ -82,7 +95,7 @@ pub async fn dispatch_logs(
     let result_questdb = send_logs_to_questdb(params);
     match result_questdb {
         Ok(_) => (),
-        Err(e) => log::error!("Error sending logs to QuestDB: {e}"),
+        Err(e) => log::debug!("Error sending logs to QuestDB: {e}"),
     };
 
     Ok(response)
@@ -0,0 +1,218 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::{
+    Error, HttpMessage,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web,
+};
+
+use crate::api::guards::{VerifiedApiKey, api_key, verify_bearer};
+use crate::core::cache::REDIS_SERVICE;
+use crate::core::config::Settings;
+use crate::core::database::DatabaseService;
+use crate::core::errors::AppError;
+use crate::core::rate_limit::{Decision, RateLimit};
+use crate::crud::api_keys::ApiKeyService;
+
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+pub const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+/// Whose requests a group's limit counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// The client address
+    Ip,
+    /// The verified API key or token subject, the client address for
+    /// requests without either
+    Caller,
+}
+
+/// Routes under `path` share one limit.
+#[derive(Debug, Clone)]
+pub struct RateLimitGroup {
+    pub name: String,
+    pub path: String,
+    pub limit: RateLimit,
+    pub key: RateLimitKey,
+}
+
+/// Limits per route group, counted in Redis so every replica sees the same
+/// counts. Routes outside the groups are not limited.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// Prefix of the Redis keys
+    namespace: String,
+    trust_forwarded: bool,
+    groups: Vec<RateLimitGroup>,
+}
+
+impl RateLimiter {
+    pub fn new(namespace: &str, trust_forwarded: bool) -> Self {
+        RateLimiter {
+            namespace: namespace.to_string(),
+            trust_forwarded,
+            groups: vec![],
+        }
+    }
+
+    /// Adds a group; the first group whose path matches a request wins.
+    pub fn group(mut self, name: &str, path: &str, limit: RateLimit, key: RateLimitKey) -> Self {
+        self.groups.push(RateLimitGroup {
+            name: name.to_string(),
+            path: path.to_string(),
+            limit,
+            key,
+        });
+        self
+    }
+
+    /// The groups of the API mounted at `prefix`.
+    pub fn from_settings(settings: &Settings, prefix: &str) -> Self {
+        RateLimiter::new("ratelimit", settings.rate_limit_trust_forwarded)
+            .group(
+                "auth",
+                &format!("{prefix}/auth"),
+                settings.rate_limit_auth,
+                RateLimitKey::Ip,
+            )
+            .group(
+                "users",
+                &format!("{prefix}/users"),
+                settings.rate_limit_users,
+                RateLimitKey::Caller,
+            )
+            .group(
+                "api_keys",
+                &format!("{prefix}/api-keys"),
+                settings.rate_limit_api_keys,
+                RateLimitKey::Caller,
+            )
+    }
+
+    fn group_for(&self, path: &str) -> Option<&RateLimitGroup> {
+        self.groups.iter().find(|group| {
+            path.strip_prefix(group.path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    fn client_address(&self, req: &ServiceRequest) -> String {
+        let info = req.connection_info();
+        let address = if self.trust_forwarded {
+            info.realip_remote_addr()
+        } else {
+            info.peer_addr()
+        };
+        format!("ip:{}", address.unwrap_or("unknown"))
+    }
+
+    /// Credentials only get a count of their own once verified, so made up
+    /// keys or tokens all share the limit of the address sending them. The
+    /// `authenticate` middleware reuses the verified key and records its use.
+    async fn client(&self, req: &ServiceRequest, key: RateLimitKey) -> String {
+        if key == RateLimitKey::Caller {
+            if let Ok(Some(presented)) = api_key(req.request()) {
+                let db = req.app_data::<web::Data<DatabaseService>>();
+                if let Some(db) = db {
+                    let api_key = ApiKeyService {}.verify(presented, &db.connection).await;
+                    let verified = match api_key {
+                        Ok(api_key) => Some(api_key),
+                        Err(AppError::Unauthorized(_)) => None,
+                        // Left to `authenticate` to report
+                        Err(_) => return self.client_address(req),
+                    };
+                    let client = match &verified {
+                        Some(api_key) => format!("key:{}", api_key.id),
+                        None => self.client_address(req),
+                    };
+                    req.extensions_mut().insert(VerifiedApiKey(verified));
+                    return client;
+                }
+            } else if let Ok(claims) = verify_bearer(req.request()) {
+                return format!("sub:{}", claims.sub);
+            }
+        }
+        self.client_address(req)
+    }
+
+    async fn check(&self, group: &RateLimitGroup, client: &str) -> Option<Decision> {
+        let window = group.limit.window.as_millis();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let index = now / window;
+        let key = |index: u128| format!("{}:{}:{client}:{index}", self.namespace, group.name);
+
+        // The previous window still counts while the current one runs
+        let counts = REDIS_SERVICE
+            .count_hit(&key(index), group.limit.window * 2, &key(index - 1))
+            .await;
+        match counts {
+            Ok((current, previous)) => {
+                let elapsed = Duration::from_millis((now % window) as u64);
+                Some(group.limit.decide(elapsed, current, previous))
+            }
+            Err(e) => {
+                log::warn!("Rate limiting is off, Redis failed -- Error: {e}");
+                None
+            }
+        }
+    }
+}
+
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision, limit: &RateLimit) {
+    let number = |value: u64| HeaderValue::from(value);
+    headers.insert(RATELIMIT_LIMIT, number(decision.limit.into()));
+    headers.insert(RATELIMIT_REMAINING, number(decision.remaining.into()));
+    headers.insert(RATELIMIT_RESET, number(decision.reset));
+    if let Ok(policy) =
+        HeaderValue::from_str(&format!("{};w={}", limit.limit, limit.window.as_secs()))
+    {
+        headers.insert(RATELIMIT_POLICY, policy);
+    }
+    if !decision.allowed {
+        headers.insert(RETRY_AFTER, number(decision.retry_after));
+    }
+}
+
+/// Answers 429 to clients over the limit of the route group, and tells every
+/// client where it stands with the `RateLimit-*` headers. Without a
+/// [`RateLimiter`] in the app data, or when Redis fails, requests go through.
+pub async fn rate_limit(
+    limiter: Option<web::Data<RateLimiter>>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let Some(limiter) = limiter else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let Some(group) = limiter.group_for(req.path()) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let client = limiter.client(&req, group.key).await;
+    let Some(decision) = limiter.check(group, &client).await else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    if decision.allowed {
+        let mut res = next.call(req).await?;
+        insert_headers(res.headers_mut(), &decision, &group.limit);
+        return Ok(res.map_into_left_body());
+    }
+
+    let error = AppError::TooManyRequests(format!(
+        "Rate limit of {} requests per {}s exceeded, retry in {}s",
+        group.limit.limit,
+        group.limit.window.as_secs(),
+        decision.retry_after
+    ));
+    let mut res = req.error_response(error);
+    insert_headers(res.headers_mut(), &decision, &group.limit);
+    Ok(res.map_into_right_body())
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use env_logger::fmt::Formatter;
+use uuid::Uuid;
+
+use super::utils::edit_error_response;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest id taken from a client, longer ones are replaced
+const MAX_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// Correlation id of a request, kept in its extensions by [`request_id`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        RequestId(Uuid::new_v4().to_string())
+    }
+
+    /// The id sent by the client, unless it is empty, too long or has
+    /// characters that could forge log lines or QuestDB columns.
+    pub fn parse(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LENGTH
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_.:/+=@".contains(&b));
+        valid.then(|| RequestId(value.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Id of the request this task is handling, if any.
+    pub fn current() -> Option<RequestId> {
+        CURRENT.try_with(RequestId::clone).ok()
+    }
+
+    /// Runs `future` as part of the request, so its records carry the id.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Accepts the `X-Request-Id` of the client or generates one, and echoes it in
+/// the response. Must wrap the other middlewares so they see it too.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(RequestId::parse)
+        .unwrap_or_else(RequestId::generate);
+    req.extensions_mut().insert(id.clone());
+
+    let echo = |headers: &mut HeaderMap| {
+        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+            headers.insert(REQUEST_ID_HEADER, value);
+        }
+    };
+    match id.clone().scope(next.call(req)).await {
+        Ok(mut response) => {
+            echo(response.headers_mut());
+            Ok(response)
+        }
+        Err(e) => Err(edit_error_response(e, |response| {
+            echo(response.headers_mut())
+        })),
+    }
+}
+
+/// The default `env_logger` format, with the id of the request being handled
+/// after the target.
+pub fn format_log(buf: &mut Formatter, record: &log::Record) -> std::io::Result<()> {
+    let level = buf.default_level_style(record.level());
+    write!(
+        buf,
+        "[{} {level}{:<5}{level:#} {}",
+        buf.timestamp(),
+        record.level(),
+        record.target()
+    )?;
+    if let Some(id) = RequestId::current() {
+        write!(buf, " request_id={id}")?;
+    }
+    writeln!(buf, "] {}", record.args())
+}
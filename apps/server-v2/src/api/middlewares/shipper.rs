@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use questdb::ingress::{Buffer, Sender};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::utils::{Params, questdb_conf, write_row};
+use crate::core::config::SETTINGS;
+
+pub struct LogEntry {
+    pub params: Params,
+    pub timestamp: DateTime<Utc>,
+}
+
+enum Message {
+    Entry(Box<LogEntry>),
+    Shutdown,
+}
+
+#[derive(Debug)]
+pub enum EnqueueError {
+    Full,
+    Closed,
+}
+
+impl fmt::Display for EnqueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnqueueError::Full => write!(f, "log queue is full, row dropped"),
+            EnqueueError::Closed => write!(f, "log shipper is shut down, row dropped"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ShipperStats {
+    pub shipped: AtomicU64,
+    pub dropped: AtomicU64,
+    pub failed: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShipperSnapshot {
+    /// Rows written to QuestDB
+    pub shipped: u64,
+    /// Rows rejected because the queue was full or closed
+    pub dropped: u64,
+    /// Rows lost because QuestDB rejected them or could not be reached
+    pub failed: u64,
+}
+
+/// Ships request logs to QuestDB from a background thread, so the request path
+/// only pays for pushing a row into a bounded channel.
+pub struct LogShipper {
+    sender: SyncSender<Message>,
+    stats: Arc<ShipperStats>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LogShipper {
+    pub fn new(conf: String, capacity: usize, batch_size: usize, interval: Duration) -> Self {
+        let (sender, receiver) = sync_channel(capacity);
+        let stats = Arc::new(ShipperStats::default());
+
+        let worker = Worker {
+            conf,
+            batch_size,
+            interval,
+            stats: stats.clone(),
+            sender: None,
+            buffer: None,
+            batch: Vec::with_capacity(batch_size),
+            reported_dropped: 0,
+        };
+        let handle = std::thread::Builder::new()
+            .name("questdb-logs".to_string())
+            .spawn(move || worker.run(receiver))
+            .expect("Failed to spawn the QuestDB log shipper");
+
+        LogShipper {
+            sender,
+            stats,
+            worker: Mutex::new(Some(handle)),
+        }
+    }
+
+    pub fn enqueue(&self, entry: LogEntry) -> Result<(), EnqueueError> {
+        let result = match self.sender.try_send(Message::Entry(Box::new(entry))) {
+            Ok(_) => return Ok(()),
+            Err(TrySendError::Full(_)) => EnqueueError::Full,
+            Err(TrySendError::Disconnected(_)) => EnqueueError::Closed,
+        };
+
+        self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+        Err(result)
+    }
+
+    pub fn stats(&self) -> ShipperSnapshot {
+        ShipperSnapshot {
+            shipped: self.stats.shipped.load(Ordering::Relaxed),
+            dropped: self.stats.dropped.load(Ordering::Relaxed),
+            failed: self.stats.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Flushes whatever is still queued and stops the worker. Blocks until done,
+    /// so call it once the HTTP server has stopped accepting requests.
+    pub fn shutdown(&self) {
+        let Some(handle) = self.worker.lock().unwrap().take() else {
+            return;
+        };
+
+        // Blocking send: the worker keeps draining, so there will be room
+        let _ = self.sender.send(Message::Shutdown);
+        if handle.join().is_err() {
+            log::error!("QuestDB log shipper panicked during shutdown");
+        }
+
+        let stats = self.stats();
+        log::info!(
+            "QuestDB log shipper stopped -- shipped: {}, dropped: {}, failed: {}",
+            stats.shipped,
+            stats.dropped,
+            stats.failed
+        );
+    }
+}
+
+struct Worker {
+    conf: String,
+    batch_size: usize,
+    interval: Duration,
+    stats: Arc<ShipperStats>,
+    // Long-lived sender, recreated only after a failed flush
+    sender: Option<Sender>,
+    buffer: Option<Buffer>,
+    batch: Vec<LogEntry>,
+    reported_dropped: u64,
+}
+
+impl Worker {
+    fn run(mut self, receiver: Receiver<Message>) {
+        let mut deadline = Instant::now() + self.interval;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(timeout) {
+                Ok(Message::Entry(entry)) => {
+                    if self.batch.is_empty() {
+                        deadline = Instant::now() + self.interval;
+                    }
+                    self.batch.push(*entry);
+                    if self.batch.len() >= self.batch_size {
+                        self.flush();
+                    }
+                }
+                Ok(Message::Shutdown) => {
+                    // Rows queued before the shutdown message still belong to this run
+                    while let Ok(Message::Entry(entry)) = receiver.try_recv() {
+                        self.batch.push(*entry);
+                    }
+                    self.flush();
+                    return;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.flush();
+                    deadline = Instant::now() + self.interval;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.flush();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        // Drops happen on the request path, report them from here to avoid a log line per request
+        let dropped = self.stats.dropped.load(Ordering::Relaxed);
+        if dropped > self.reported_dropped {
+            log::warn!(
+                "Dropped {} request logs, the QuestDB queue was full",
+                dropped - self.reported_dropped
+            );
+            self.reported_dropped = dropped;
+        }
+
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let rows = self.batch.len() as u64;
+        match self.try_flush() {
+            Ok(written) => {
+                self.stats.shipped.fetch_add(written, Ordering::Relaxed);
+                self.stats
+                    .failed
+                    .fetch_add(rows - written, Ordering::Relaxed);
+            }
+            Err(e) => {
+                log::error!("Error sending {rows} logs to QuestDB: {e}");
+                self.stats.failed.fetch_add(rows, Ordering::Relaxed);
+                // Start over with a fresh connection on the next batch
+                self.sender = None;
+                self.buffer = None;
+            }
+        }
+        self.batch.clear();
+    }
+
+    /// Returns the number of rows that made it into the flushed buffer.
+    fn try_flush(&mut self) -> questdb::Result<u64> {
+        if self.sender.is_none() {
+            let sender = Sender::from_conf(self.conf.as_str())?;
+            self.buffer = Some(sender.new_buffer());
+            self.sender = Some(sender);
+        }
+        let (Some(sender), Some(buffer)) = (self.sender.as_mut(), self.buffer.as_mut()) else {
+            unreachable!("sender and buffer are created together");
+        };
+
+        let mut written = 0;
+        for entry in self.batch.drain(..) {
+            buffer.set_marker()?;
+            match write_row(buffer, entry) {
+                Ok(_) => written += 1,
+                Err(e) => {
+                    log::warn!("Skipping malformed log row: {e}");
+                    buffer.rewind_to_marker()?;
+                }
+            }
+        }
+        buffer.clear_marker();
+
+        sender.flush(buffer)?;
+        Ok(written)
+    }
+}
+
+/// Rows are flushed once `QUESTDB_BATCH_SIZE` of them are buffered or the oldest one
+/// has waited `QUESTDB_FLUSH_INTERVAL`. Once `QUESTDB_QUEUE_CAPACITY` rows are waiting,
+/// new ones are dropped instead of slowing requests down.
+pub static LOG_SHIPPER: LazyLock<LogShipper> = LazyLock::new(|| {
+    LogShipper::new(
+        questdb_conf(),
+        SETTINGS.questdb_queue_capacity,
+        SETTINGS.questdb_batch_size,
+        SETTINGS.questdb_flush_interval,
+    )
+});
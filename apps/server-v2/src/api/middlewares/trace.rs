@@ -0,0 +1,101 @@
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use opentelemetry::global;
+use opentelemetry::propagation::{Extractor, Injector};
+use tracing::{Instrument, field};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::request_id::RequestId;
+use super::utils::edit_error_response;
+
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// Headers the propagator reads the caller's context from.
+struct RequestHeaders<'a>(&'a HeaderMap);
+
+impl Extractor for RequestHeaders<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+/// Headers the propagator writes the request's context to.
+struct ResponseHeaders<'a>(&'a mut HeaderMap);
+
+impl Injector for ResponseHeaders<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(key), HeaderValue::try_from(value)) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+/// Opens the server span of the request, as a child of the caller's span when
+/// it sent a `traceparent`, and answers with the `traceparent` of this span so
+/// the caller can link to it.
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let route = req.match_pattern();
+    let name = match &route {
+        Some(route) => format!("{} {route}", req.method()),
+        None => req.method().to_string(),
+    };
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(RequestId::to_string);
+    let span = tracing::info_span!(
+        "HTTP request",
+        otel.name = name,
+        otel.kind = "server",
+        otel.status_code = field::Empty,
+        http.request.method = req.method().as_str(),
+        http.route = route.as_deref(),
+        http.response.status_code = field::Empty,
+        url.path = req.path(),
+        client.address = req.peer_addr().map(|addr| addr.ip().to_string()),
+        request.id = request_id,
+    );
+    let caller = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&RequestHeaders(req.headers()))
+    });
+    // Only fails without a subscriber, when there is no span to link
+    let _ = span.set_parent(caller);
+
+    let result = next.call(req).instrument(span.clone()).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    // Unsigned values would be exported as strings
+    span.record("http.response.status_code", i64::from(status.as_u16()));
+    // Client errors are the caller's, only server errors fail the span
+    if status.is_server_error() {
+        span.record("otel.status_code", "error");
+    }
+    let inject = |headers: &mut HeaderMap| {
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&span.context(), &mut ResponseHeaders(headers))
+        })
+    };
+    match result {
+        Ok(mut response) => {
+            inject(response.headers_mut());
+            Ok(response)
+        }
+        Err(e) => Err(edit_error_response(e, |response| {
+            inject(response.headers_mut())
+        })),
+    }
+}
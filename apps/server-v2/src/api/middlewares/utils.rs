@@ -1,12 +1,26 @@
+use actix_web::error::InternalError;
+use actix_web::{Error, HttpResponse};
 use chrono::Utc;
 use questdb::{
     Result,
-    ingress::{Sender, TimestampNanos},
+    ingress::{Buffer, TimestampNanos},
 };
 
+use super::shipper::{EnqueueError, LOG_SHIPPER, LogEntry};
 use crate::core::config::SETTINGS;
 
+/// The error of an inner service, answered with its usual response as changed
+/// by `edit`. Lets middlewares add headers to error responses too, while the
+/// outer ones still see an error.
+pub fn edit_error_response(e: Error, edit: impl FnOnce(&mut HttpResponse)) -> Error {
+    let mut response = e.error_response();
+    edit(&mut response);
+    InternalError::from_response(e, response).into()
+}
+
 pub struct ReqParams {
+    /// Empty when the `request_id` middleware isn't installed
+    pub request_id: String,
     pub method: String,
     pub headers: Vec<String>,
     pub http_version: String,
@@ -32,30 +46,40 @@ pub struct Params {
     pub res_params: ResParams,
 }
 
-pub fn send_logs_to_questdb(params: Params) -> Result<()> {
+/// Queues the row for the background shipper; it never waits on QuestDB.
+pub fn send_logs_to_questdb(params: Params) -> std::result::Result<(), EnqueueError> {
+    LOG_SHIPPER.enqueue(LogEntry {
+        params,
+        timestamp: Utc::now(),
+    })
+}
+
+pub fn questdb_conf() -> String {
     let transport = "http";
     let host = SETTINGS.questdb_host.as_str();
-    let port = SETTINGS.questdb_port.as_str();
-    let current_datetime = Utc::now();
+    let port = SETTINGS.questdb_port;
 
-    let credentials = match (
-        SETTINGS.questdb_user.clone(),
-        SETTINGS.questdb_password.clone(),
-    ) {
-        (Ok(username), Ok(password)) => format!("username={username};password={password};"),
-        (Ok(username), Err(_)) => format!("username={username};"),
-        (Err(_), Ok(password)) => format!("password={password};"),
-        (Err(_), Err(_)) => String::new(),
+    let credentials = match (&SETTINGS.questdb_user, &SETTINGS.questdb_password) {
+        (Some(username), Some(password)) => {
+            format!("username={username};password={};", password.expose())
+        }
+        (Some(username), None) => format!("username={username};"),
+        (None, Some(password)) => format!("password={};", password.expose()),
+        (None, None) => String::new(),
     };
 
-    let mut sender = Sender::from_conf(format!("{transport}::addr={host}:{port};{credentials}",))?;
+    format!("{transport}::addr={host}:{port};{credentials}")
+}
+
+pub fn write_row(buffer: &mut Buffer, entry: LogEntry) -> Result<()> {
+    let params = entry.params;
 
     // Protocol version documentation: https://docs.rs/crate/questdb-rs/latest
     // let mut buffer = Buffer::new(ProtocolVersion::V1);
 
-    let mut buffer = sender.new_buffer();
     buffer
         .table(SETTINGS.questdb_db.as_str())?
+        .column_str("request_id", params.req_params.request_id)?
         .column_str("method", params.req_params.method)?
         .column_str(
             "req_headers",
@@ -78,9 +102,7 @@ pub fn send_logs_to_questdb(params: Params) -> Result<()> {
         )?
         .column_f64("process_time", params.res_params.process_time)?
         .column_str("created_at", params.res_params.created_at)?
-        .at(TimestampNanos::from_datetime(current_datetime)?)?;
-
-    sender.flush(&mut buffer)?;
+        .at(TimestampNanos::from_datetime(entry.timestamp)?)?;
 
     Ok(())
 }
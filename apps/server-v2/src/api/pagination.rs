@@ -0,0 +1,74 @@
+use actix_web::HttpRequest;
+use actix_web::http::header::{HeaderName, HeaderValue, LINK};
+
+use crate::schemas::api::{CursorPage, PageLinks, Paginated};
+
+/// URL of the current request with `params` set and `remove` dropped, keeping
+/// every other query parameter (search, limit, ...) so the links stay on the
+/// same listing.
+fn url_with(req: &HttpRequest, params: &[(&str, &str)], remove: &[&str]) -> String {
+    let mut url = req.full_url();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| {
+            !remove.contains(&key.as_ref()) && !params.iter().any(|(name, _)| name == key)
+        })
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .extend_pairs(params);
+    url.to_string()
+}
+
+fn page_url(req: &HttpRequest, page: u64) -> String {
+    url_with(req, &[("page", &page.to_string())], &[])
+}
+
+pub fn page_links<T>(req: &HttpRequest, paginated: &Paginated<T>) -> PageLinks {
+    let last = paginated.pages.max(1);
+    PageLinks {
+        first: Some(page_url(req, 1)),
+        prev: (paginated.page > 1).then(|| page_url(req, (paginated.page - 1).min(last))),
+        next: (paginated.page < paginated.pages).then(|| page_url(req, paginated.page + 1)),
+        last: Some(page_url(req, last)),
+    }
+}
+
+/// Keyset listings have no last page; `first` restarts from the beginning.
+pub fn cursor_links<T>(req: &HttpRequest, page: &CursorPage<T>) -> PageLinks {
+    let cursor_keys = ["after", "before", "page"];
+    PageLinks {
+        first: Some(url_with(req, &[("after", "")], &cursor_keys)),
+        prev: page
+            .prev_cursor
+            .as_ref()
+            .map(|cursor| url_with(req, &[("before", cursor)], &cursor_keys)),
+        next: page
+            .next_cursor
+            .as_ref()
+            .map(|cursor| url_with(req, &[("after", cursor)], &cursor_keys)),
+        last: None,
+    }
+}
+
+/// RFC 8288 `Link` header carrying the same links as the body.
+pub fn link_header(links: &PageLinks) -> Option<(HeaderName, HeaderValue)> {
+    let relations = [
+        ("first", &links.first),
+        ("prev", &links.prev),
+        ("next", &links.next),
+        ("last", &links.last),
+    ];
+    let value = relations
+        .iter()
+        .filter_map(|(rel, url)| url.as_ref().map(|url| format!("<{url}>; rel=\"{rel}\"")))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    HeaderValue::from_str(&value)
+        .ok()
+        .map(|value| (LINK, value))
+}
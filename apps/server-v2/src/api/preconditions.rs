@@ -0,0 +1,110 @@
+use std::future::{Ready, ready};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::dev::Payload;
+use actix_web::http::header::{
+    ETag, EntityTag, Header, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use sha2::{Digest, Sha256};
+
+use crate::core::config::SETTINGS;
+use crate::core::errors::AppError;
+use crate::schemas::api::Precondition;
+
+/// Strong entity tag of a versioned resource.
+pub fn version_etag(version: i32) -> ETag {
+    ETag(EntityTag::new_strong(format!("v{version}")))
+}
+
+/// Strong entity tag derived from the exact bytes of a response body, for
+/// representations without a version of their own such as listings.
+pub fn content_etag(body: &[u8]) -> ETag {
+    ETag(EntityTag::new_strong(
+        URL_SAFE_NO_PAD.encode(Sha256::digest(body)),
+    ))
+}
+
+/// HTTP dates have second precision, so compare at that precision too.
+pub fn last_modified(time: impl Into<SystemTime>) -> LastModified {
+    let time = time.into();
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let whole_seconds = UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs());
+    LastModified(HttpDate::from(whole_seconds))
+}
+
+/// 304 response when the client's copy is still current, per RFC 9110:
+/// `If-None-Match` wins over `If-Modified-Since` when both are sent.
+pub fn not_modified(
+    req: &HttpRequest,
+    etag: &ETag,
+    last_modified: Option<&LastModified>,
+) -> Option<HttpResponse> {
+    let fresh = if req.headers().contains_key(IfNoneMatch::name()) {
+        match IfNoneMatch::parse(req) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag.0)),
+            Err(_) => false,
+        }
+    } else {
+        match (IfModifiedSince::parse(req), last_modified) {
+            (Ok(IfModifiedSince(since)), Some(LastModified(modified))) => {
+                SystemTime::from(*modified) <= SystemTime::from(since)
+            }
+            _ => false,
+        }
+    };
+    if !fresh {
+        return None;
+    }
+
+    let mut response = HttpResponse::NotModified();
+    response.insert_header(etag.clone());
+    if let Some(last_modified) = last_modified {
+        response.insert_header(last_modified.clone());
+    }
+    Some(response.finish())
+}
+
+fn tag_version(tag: &EntityTag) -> Option<i32> {
+    tag.tag().strip_prefix('v')?.parse().ok()
+}
+
+/// Extractor for the `If-Match` header of writes. A missing header is a 428
+/// while `REQUIRE_IF_MATCH` is on, otherwise the write goes ahead unchecked.
+pub struct IfMatchVersion(pub Precondition);
+
+impl FromRequest for IfMatchVersion {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if !req.headers().contains_key(IfMatch::name()) {
+            let result = if SETTINGS.require_if_match {
+                Err(AppError::PreconditionRequired(
+                    "This request requires an If-Match header with the user's ETag".to_string(),
+                ))
+            } else {
+                Ok(IfMatchVersion(Precondition::Any))
+            };
+            return ready(result);
+        }
+
+        let result = match IfMatch::parse(req) {
+            Ok(IfMatch::Any) => Ok(Precondition::Any),
+            // `If-Match` uses the strong comparison, weak tags never match
+            Ok(IfMatch::Items(tags)) => Ok(Precondition::Versions(
+                tags.iter()
+                    .filter(|tag| !tag.weak)
+                    .filter_map(tag_version)
+                    .collect(),
+            )),
+            Err(_) => Err(AppError::BadRequest(
+                "Malformed If-Match header".to_string(),
+            )),
+        };
+        ready(result.map(IfMatchVersion))
+    }
+}
@@ -1,2 +1,6 @@
+pub mod api_keys;
+pub mod auth;
 pub mod users;
+pub use super::routes::api_keys::handler_api_keys;
+pub use super::routes::auth::handler_auth;
 pub use super::routes::users::handler_users;
@@ -0,0 +1,119 @@
+use crate::api::docs::{ApiDoc, Operation};
+use crate::api::guards::{ADMIN_KEY_HEADER, Admin};
+use crate::core::database::DatabaseService;
+use crate::core::errors::AppError;
+use crate::core::validation::Validate;
+use crate::crud::api_keys::ApiKeyService;
+use crate::models::api_keys::Model as ApiKeyModel;
+use crate::schemas::api_keys::{ApiKeyCreate, ApiKeyCreated};
+use actix_web::{HttpResponse, Responder, delete, get, post, web};
+
+#[post("/")]
+async fn create_api_key(
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+    request: web::Json<ApiKeyCreate>,
+) -> Result<impl Responder, AppError> {
+    let request = request.into_inner();
+    request.validate()?;
+    let api_key_service = ApiKeyService {};
+    let (api_key, key) = api_key_service
+        .create_api_key(request, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::Created()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(ApiKeyCreated {
+            api_key,
+            key: key.expose(),
+        }))
+}
+
+#[get("/")]
+async fn get_api_keys(
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+) -> Result<impl Responder, AppError> {
+    let api_key_service = ApiKeyService {};
+    let api_keys = api_key_service.get_api_keys(&db.connection).await?;
+    Ok(web::Json(api_keys))
+}
+
+#[post("/{id}/rotate")]
+async fn rotate_api_key(
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+    id: web::Path<i32>,
+) -> Result<impl Responder, AppError> {
+    let api_key_service = ApiKeyService {};
+    let (api_key, key) = api_key_service
+        .rotate_api_key(id.into_inner(), &db.connection)
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(ApiKeyCreated {
+            api_key,
+            key: key.expose(),
+        }))
+}
+
+#[delete("/{id}")]
+async fn revoke_api_key(
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+    id: web::Path<i32>,
+) -> Result<impl Responder, AppError> {
+    let api_key_service = ApiKeyService {};
+    api_key_service
+        .revoke_api_key(id.into_inner(), &db.connection)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn handler_api_keys() -> actix_web::Scope {
+    actix_web::web::scope("/api-keys")
+        .service(create_api_key)
+        .service(get_api_keys)
+        .service(rotate_api_key)
+        .service(revoke_api_key)
+}
+
+fn admin(op: Operation<'_>) -> Operation<'_> {
+    op.tag("api-keys")
+        .header(ADMIN_KEY_HEADER, "Admin API key", true)
+        .error(401, "Missing admin key")
+        .error(403, "Wrong admin key")
+}
+
+/// Describes the operations of [`handler_api_keys`].
+pub fn docs(doc: &mut ApiDoc) {
+    doc.route("post", "/api-keys/", |op| {
+        admin(op.id("create_api_key"))
+            .summary("Create an API key")
+            .body::<ApiKeyCreate>("application/json")
+            .response::<ApiKeyCreated>(201, "The key, with its secret shown this once")
+            .error(422, "Invalid name or scopes")
+    })
+    .route("get", "/api-keys/", |op| {
+        admin(op.id("get_api_keys"))
+            .summary("List API keys, revoked ones included")
+            .response::<Vec<ApiKeyModel>>(200, "Every key, without secrets")
+    })
+    .route("post", "/api-keys/{id}/rotate", |op| {
+        admin(op.id("rotate_api_key"))
+            .summary("Replace the secret of an API key")
+            .path_param::<i32>("id")
+            .response::<ApiKeyCreated>(200, "The new key; the previous one no longer works")
+            .error(404, "No such key")
+            .error(409, "The key is revoked")
+    })
+    .route("delete", "/api-keys/{id}", |op| {
+        admin(op.id("revoke_api_key"))
+            .summary("Revoke an API key")
+            .path_param::<i32>("id")
+            .empty_response(204, "Revoked")
+            .error(404, "No such key")
+    });
+}
@@ -0,0 +1,113 @@
+use crate::api::docs::ApiDoc;
+use crate::api::guards::{ADMIN_KEY_HEADER, Admin, Authenticated};
+use crate::core::database::DatabaseService;
+use crate::core::errors::AppError;
+use crate::core::jwt::JWT;
+use crate::core::validation::Validate;
+use crate::crud::UserService;
+use crate::schemas::auth::{LoginRequest, PasswordChange, TokenRequest, TokenResponse};
+use actix_web::{HttpResponse, post, web};
+
+/// Tokens are credentials, so no cache may keep them.
+fn token_response(subject: &str) -> Result<HttpResponse, AppError> {
+    let access_token = JWT.encode(&JWT.claims(subject))?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: JWT.expiry().as_secs(),
+        }))
+}
+
+/// Issues an access token for any `subject`, e.g. a service account.
+#[post("/token")]
+async fn issue_token(
+    _admin: Admin,
+    request: web::Json<TokenRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request = request.into_inner();
+    request.validate()?;
+    token_response(request.subject.trim())
+}
+
+/// Exchanges a user's email and password for a token whose subject is their ID.
+#[post("/login")]
+async fn login(
+    db: web::Data<DatabaseService>,
+    request: web::Json<LoginRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request = request.into_inner();
+    let user_service = UserService {};
+    let user = user_service
+        .authenticate(&request.email, request.password, &db.connection)
+        .await?;
+    token_response(&user.id.to_string())
+}
+
+/// Changes the password of the user the token was issued to.
+#[post("/password")]
+async fn change_password(
+    caller: Authenticated,
+    db: web::Data<DatabaseService>,
+    request: web::Json<PasswordChange>,
+) -> Result<HttpResponse, AppError> {
+    let Some(user_id) = caller.0.user_id() else {
+        return Err(AppError::Forbidden(
+            "Only users can change their password".to_string(),
+        ));
+    };
+    let request = request.into_inner();
+    request.validate()?;
+    let user_service = UserService {};
+    user_service
+        .change_password(
+            user_id,
+            request.current_password,
+            request.new_password,
+            &db.connection,
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn handler_auth() -> actix_web::Scope {
+    actix_web::web::scope("/auth")
+        .service(issue_token)
+        .service(login)
+        .service(change_password)
+}
+
+/// Describes the operations of [`handler_auth`].
+pub fn docs(doc: &mut ApiDoc) {
+    doc.route("post", "/auth/token", |op| {
+        op.id("issue_token")
+            .tag("auth")
+            .summary("Issue an access token")
+            .header(ADMIN_KEY_HEADER, "Admin API key", true)
+            .body::<TokenRequest>("application/json")
+            .response::<TokenResponse>(200, "A signed JWT")
+            .error(401, "Missing admin key")
+            .error(403, "Wrong admin key")
+            .error(422, "Invalid subject")
+    })
+    .route("post", "/auth/login", |op| {
+        op.id("login")
+            .tag("auth")
+            .summary("Log in with email and password")
+            .body::<LoginRequest>("application/json")
+            .response::<TokenResponse>(200, "A signed JWT for the user")
+            .error(401, "Invalid email or password")
+    })
+    .route("post", "/auth/password", |op| {
+        op.id("change_password")
+            .tag("auth")
+            .authenticated()
+            .summary("Change the caller's password")
+            .body::<PasswordChange>("application/json")
+            .empty_response(204, "Changed")
+            .error(403, "Wrong current password, or the token is not a user's")
+            .error(422, "The new password breaks the policy")
+    });
+}
@@ -1,134 +1,769 @@
+use crate::api::docs::ApiDoc;
+use crate::api::guards::{
+    ADMIN_KEY_HEADER, Admin, CreateUsers, DeleteUsers, ListUsers, ManageAccess, Permit, ReadUser,
+    UpdateUser,
+};
+use crate::api::main::json_config;
+use crate::api::middlewares::auth::authenticate;
+use crate::api::pagination::{cursor_links, link_header, page_links};
+use crate::api::preconditions::{
+    IfMatchVersion, content_etag, last_modified, not_modified, version_etag,
+};
+use crate::core::config::SETTINGS;
+use crate::core::csv::{self, CSV_CONTENT_TYPE, NDJSON_CONTENT_TYPE};
 use crate::core::database::DatabaseService;
+use crate::core::errors::AppError;
+use crate::core::patch::{JSON_PATCH_CONTENT_TYPE, MERGE_PATCH_CONTENT_TYPE, PatchOperation};
+use crate::core::permissions::Permission;
+use crate::core::validation::Validate;
 use crate::crud::UserService;
-use crate::schemas::users::{UserCreate, UserUpdate};
-use actix_web::{HttpResponse, Responder, delete, get, post, put, web};
-use serde::Deserialize;
+use crate::models::users::Model as UserModel;
+use crate::schemas::api::{Cursor, CursorDirection, CursorPage, DataFormat, Paginated};
+use crate::schemas::auth::{PasswordSet, RoleSet};
+use crate::schemas::users::{
+    BulkOperation, BulkRequest, BulkResponse, EXPORT_COLUMNS, ImportReport, UserCreate,
+    UserFilters, UserPatch, UserSort, UserUpdate, export_record, read_import,
+};
+use actix_web::HttpMessage;
+use actix_web::dev::HttpServiceFactory;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{
+    CONTENT_TYPE, ContentDisposition, ContentType, HeaderName, HeaderValue,
+};
+use actix_web::middleware::from_fn;
+use actix_web::web::{Bytes, BytesMut};
+use actix_web::{HttpRequest, HttpResponse, Responder, delete, get, patch, post, put, web};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::ready;
 
-#[derive(Deserialize, Clone)]
+/// Users per page when the request has no `limit`
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize, JsonSchema, Clone)]
 struct QueryParamsUsers {
     page: Option<usize>,
+    /// 1 to `MAX_PAGE_SIZE` (1000 unless configured), 100 by default
     limit: Option<usize>,
     search: Option<String>,
+    /// Comma separated, `-` for descending: `sort=-age,name`
+    sort: Option<String>,
+    is_active: Option<bool>,
+    age_min: Option<i32>,
+    age_max: Option<i32>,
+    /// RFC 3339 timestamps; `*_after` is inclusive, `*_before` exclusive
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    updated_after: Option<DateTime<Utc>>,
+    updated_before: Option<DateTime<Utc>>,
+    /// Keyset mode: `after=` (empty) starts from the beginning
+    after: Option<String>,
+    before: Option<String>,
+}
+
+/// Listings have no version of their own, so they are tagged by content and
+/// dated by their most recently updated user.
+fn listing_response<T: Serialize>(
+    req: &HttpRequest,
+    listing: &T,
+    users: &[UserModel],
+    links: Option<(HeaderName, HeaderValue)>,
+) -> Result<HttpResponse, AppError> {
+    let body = serde_json::to_vec(listing)?;
+    let etag = content_etag(&body);
+    let modified = users.iter().map(|user| user.updated_at).max();
+    let modified = modified.map(last_modified);
+    if let Some(response) = not_modified(req, &etag, modified.as_ref()) {
+        return Ok(response);
+    }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(ContentType::json())
+        .insert_header(etag);
+    if let Some(modified) = modified {
+        response.insert_header(modified);
+    }
+    if let Some(header) = links {
+        response.insert_header(header);
+    }
+    Ok(response.body(body))
+}
+
+/// Single users are tagged by version, the same tag `If-Match` checks.
+fn user_response(req: &HttpRequest, user: UserModel) -> HttpResponse {
+    let etag = version_etag(user.version);
+    let modified = last_modified(user.updated_at);
+    if let Some(response) = not_modified(req, &etag, Some(&modified)) {
+        return response;
+    }
+
+    HttpResponse::Ok()
+        .insert_header(etag)
+        .insert_header(modified)
+        .json(user)
+}
+
+impl QueryParamsUsers {
+    fn filters(&self) -> Result<UserFilters, AppError> {
+        let filters = UserFilters {
+            search: self.search.clone(),
+            is_active: self.is_active,
+            age_min: self.age_min,
+            age_max: self.age_max,
+            created_after: self.created_after,
+            created_before: self.created_before,
+            updated_after: self.updated_after,
+            updated_before: self.updated_before,
+            sort: UserSort::parse_list(self.sort.as_deref().unwrap_or_default())?,
+        };
+        filters.check()?;
+        Ok(filters)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct QueryParamsExport {
+    format: DataFormat,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct QueryParamsImport {
+    /// Taken from the `Content-Type` when absent
+    format: Option<DataFormat>,
+    #[serde(default)]
+    dry_run: bool,
 }
 
 #[get("/")]
 async fn get_users(
+    _permit: Permit<ListUsers>,
+    req: HttpRequest,
     params: web::Query<QueryParamsUsers>,
     db: web::Data<DatabaseService>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
+    let params = params.into_inner();
+    let max_page_size = SETTINGS.max_page_size;
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE.min(max_page_size));
+    // Every distinct limit is a cache entry of its own, so it must be bounded
+    if !(1..=max_page_size).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "`limit` must be between 1 and {max_page_size}"
+        )));
+    }
     let user_service = UserService {};
-    match user_service
-        .get_users(
-            &db.connection,
-            params.clone().into_inner().page.unwrap_or(1),
-            params.clone().into_inner().limit.unwrap_or(100),
-            params.clone().into_inner().search,
-        )
-        .await
+    let filters = params.filters()?;
+
+    let direction = match (params.after, params.before) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::BadRequest(
+                "Use either `after` or `before`, not both".to_string(),
+            ));
+        }
+        (Some(after), None) if after.is_empty() => Some(CursorDirection::After(None)),
+        (Some(after), None) => Some(CursorDirection::After(Some(Cursor::decode(&after)?))),
+        (None, Some(before)) => Some(CursorDirection::Before(Cursor::decode(&before)?)),
+        (None, None) => None,
+    };
+    if let Some(direction) = direction {
+        if params.page.is_some() {
+            return Err(AppError::BadRequest(
+                "`page` cannot be combined with a cursor".to_string(),
+            ));
+        }
+        if !filters.sort.is_empty() {
+            return Err(AppError::BadRequest(
+                "`sort` cannot be combined with a cursor, cursor pages are ordered by id"
+                    .to_string(),
+            ));
+        }
+        let mut users = user_service
+            .get_users_by_cursor(&db.connection, direction, limit, &filters)
+            .await?;
+
+        users.links = cursor_links(&req, &users);
+        return listing_response(&req, &users, &users.items, link_header(&users.links));
+    }
+
+    let page = params.page.unwrap_or(1);
+    if page == 0 {
+        return Err(AppError::BadRequest("`page` starts at 1".to_string()));
+    }
+    let mut users = user_service
+        .get_users(&db.connection, page, limit, &filters)
+        .await?;
+
+    users.links = page_links(&req, &users);
+    listing_response(&req, &users, &users.items, link_header(&users.links))
+}
+
+/// Streams every user matching the list filters, in id order. Pagination and
+/// sorting parameters don't apply.
+#[get("/export")]
+async fn export_users(
+    _permit: Permit<ListUsers>,
+    db: web::Data<DatabaseService>,
+    params: web::Query<QueryParamsUsers>,
+    export: web::Query<QueryParamsExport>,
+) -> Result<impl Responder, AppError> {
+    let params = params.into_inner();
+    if params.page.is_some()
+        || params.limit.is_some()
+        || params.after.is_some()
+        || params.before.is_some()
     {
-        Ok(users) => Ok(HttpResponse::Ok().json(users)),
-        Err(e) => {
-            log::error!("Error fetching users: {}", e.message);
-            Ok(HttpResponse::InternalServerError().json(e))
+        return Err(AppError::BadRequest(
+            "Exports contain every matching user, `page`, `limit`, `after` and `before` don't apply"
+                .to_string(),
+        ));
+    }
+    let filters = params.filters()?;
+    if !filters.sort.is_empty() {
+        return Err(AppError::BadRequest(
+            "`sort` cannot be used with exports, they are ordered by id".to_string(),
+        ));
+    }
+
+    let format = export.format;
+    let user_service = UserService {};
+    let batches = user_service.export_users(db.connection.clone(), filters);
+    let rows = batches.map(move |batch| {
+        let batch = batch.inspect_err(|e| log::error!("User export failed: {e}"))?;
+        let mut chunk = String::new();
+        for user in &batch {
+            match format {
+                DataFormat::Csv => chunk.push_str(&csv::write_record(&export_record(user))),
+                DataFormat::Ndjson => {
+                    chunk.push_str(&serde_json::to_string(user)?);
+                    chunk.push('\n');
+                }
+            }
         }
+        Ok::<_, AppError>(Bytes::from(chunk))
+    });
+    let header = match format {
+        DataFormat::Csv => csv::write_record(&EXPORT_COLUMNS),
+        DataFormat::Ndjson => String::new(),
+    };
+    let body = stream::once(ready(Ok(Bytes::from(header)))).chain(rows);
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition::attachment(format!(
+            "users.{}",
+            format.extension()
+        )))
+        .streaming(body))
+}
+
+/// Creates users from a CSV or NDJSON upload, all of them or none. Invalid
+/// uploads answer 422 with the problems of every line.
+#[post("/import")]
+async fn import_users(
+    _permit: Permit<CreateUsers>,
+    req: HttpRequest,
+    db: web::Data<DatabaseService>,
+    params: web::Query<QueryParamsImport>,
+    mut payload: web::Payload,
+) -> Result<impl Responder, AppError> {
+    let content_type = req.content_type().to_ascii_lowercase();
+    let format = params
+        .format
+        .or_else(|| DataFormat::from_content_type(&content_type))
+        .ok_or_else(|| {
+            AppError::UnsupportedMediaType(format!(
+                "Expected {CSV_CONTENT_TYPE} or {NDJSON_CONTENT_TYPE}, or a `format` parameter"
+            ))
+        })?;
+
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| AppError::BadRequest(format!("Invalid body: {e}")))?;
+        if body.len() + chunk.len() > SETTINGS.bulk_max_body_size {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Uploads are limited to {} bytes",
+                SETTINGS.bulk_max_body_size
+            )));
+        }
+        body.extend_from_slice(&chunk);
     }
+    let body = std::str::from_utf8(&body)
+        .map_err(|e| AppError::BadRequest(format!("Uploads must be UTF-8: {e}")))?;
+
+    let (rows, errors) = read_import(format, body);
+    let user_service = UserService {};
+    let report = user_service
+        .import_users(rows, errors, params.dry_run, &db.connection)
+        .await?;
+
+    let status = if report.errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok(HttpResponse::build(status).json(report))
 }
 
 #[get("/id/{id}")]
 async fn get_user(
+    _permit: Permit<ReadUser>,
+    req: HttpRequest,
     db: web::Data<DatabaseService>,
     id: web::Path<u16>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
     let user_service = UserService {};
-    match user_service
+    let user = user_service
         .get_user_by_id(id.into_inner(), &db.connection)
-        .await
-    {
-        Ok(user) => Ok(HttpResponse::Ok().json(user)),
-        Err(e) => {
-            log::error!("Error fetching user: {}", e.message);
-            Ok(HttpResponse::build(e.get_status_code()).json(e))
-        }
-    }
+        .await?;
+
+    Ok(user_response(&req, user))
 }
 
 #[get("/email/{email}")]
 async fn get_user_by_email(
+    _permit: Permit<ReadUser>,
+    req: HttpRequest,
     db: web::Data<DatabaseService>,
     email: web::Path<String>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
     let user_service = UserService {};
-    match user_service
+    let user = user_service
         .get_user_by_email(email.into_inner().as_str(), &db.connection)
-        .await
-    {
-        Ok(user) => Ok(HttpResponse::Ok().json(user)),
-        Err(e) => {
-            log::error!("Error fetching user by email: {}", e.message);
-            Ok(HttpResponse::build(e.get_status_code()).json(e))
-        }
-    }
+        .await?;
+
+    Ok(user_response(&req, user))
 }
 
 #[post("/")]
 async fn create_user(
+    _permit: Permit<CreateUsers>,
     db: web::Data<DatabaseService>,
     user: web::Json<UserCreate>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
     let user = user.into_inner();
+    user.validate()?;
     let user_service = UserService {};
-    match user_service.create_user(user, &db.connection).await {
-        Ok(model) => Ok(HttpResponse::Created().json(model)),
-        Err(e) => {
-            log::error!("Error creating user: {}", e.message);
-            Ok(HttpResponse::InternalServerError().json(e))
-        }
-    }
+    let model = user_service.create_user(user, &db.connection).await?;
+
+    Ok(HttpResponse::Created()
+        .insert_header(version_etag(model.version))
+        .json(model))
 }
 
 #[put("/id/{id}")]
 async fn update_user(
+    _permit: Permit<UpdateUser>,
     db: web::Data<DatabaseService>,
     id: web::Path<u16>,
+    if_match: IfMatchVersion,
     user: web::Json<UserUpdate>,
-) -> Result<impl Responder, actix_web::Error> {
+) -> Result<impl Responder, AppError> {
     let user = user.into_inner();
+    user.validate()?;
     let user_id = id.into_inner();
     let user_service = UserService {};
-    match user_service
-        .update_user(user_id, user, &db.connection)
-        .await
-    {
-        Ok(updated_user) => Ok(HttpResponse::Ok().json(updated_user)),
-        Err(e) => {
-            log::error!("Error updating user: {}", e.message);
-            Ok(HttpResponse::build(e.get_status_code()).json(e))
+    let updated_user = user_service
+        .update_user(user_id, user, &if_match.0, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(updated_user.version))
+        .json(updated_user))
+}
+
+#[patch("/id/{id}")]
+async fn patch_user(
+    _permit: Permit<UpdateUser>,
+    req: HttpRequest,
+    db: web::Data<DatabaseService>,
+    id: web::Path<u16>,
+    if_match: IfMatchVersion,
+    body: web::Bytes,
+) -> Result<impl Responder, AppError> {
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let invalid_body = |e: serde_json::Error| AppError::BadRequest(format!("Invalid patch: {e}"));
+    // Plain JSON is read as a merge patch, the usual meaning of a partial body
+    let patch = match content_type.as_deref() {
+        Some(MERGE_PATCH_CONTENT_TYPE | "application/json") => {
+            UserPatch::Merge(serde_json::from_slice(&body).map_err(invalid_body)?)
         }
-    }
+        Some(JSON_PATCH_CONTENT_TYPE) => {
+            UserPatch::Json(serde_json::from_slice(&body).map_err(invalid_body)?)
+        }
+        _ => {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Expected {MERGE_PATCH_CONTENT_TYPE} or {JSON_PATCH_CONTENT_TYPE}"
+            )));
+        }
+    };
+
+    let user_service = UserService {};
+    let updated_user = user_service
+        .patch_user(id.into_inner(), patch, &if_match.0, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(updated_user.version))
+        .json(updated_user))
 }
 
 #[delete("/id/{id}")]
 async fn delete_user(
+    _permit: Permit<DeleteUsers>,
     db: web::Data<DatabaseService>,
     id: web::Path<u16>,
-) -> Result<impl Responder, actix_web::Error> {
+    if_match: IfMatchVersion,
+) -> Result<impl Responder, AppError> {
     let user_id = id.into_inner();
     let user_service = UserService {};
-    match user_service.delete_user(user_id, &db.connection).await {
-        Ok(_) => Ok(HttpResponse::NoContent().finish()),
-        Err(e) => {
-            log::error!("Error deleting user: {}", e.message);
-            Ok(HttpResponse::build(e.get_status_code()).json(e))
-        }
+    user_service
+        .delete_user(user_id, &if_match.0, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/id/{id}/restore")]
+async fn restore_user(
+    _permit: Permit<CreateUsers>,
+    db: web::Data<DatabaseService>,
+    id: web::Path<u16>,
+) -> Result<impl Responder, AppError> {
+    let user_id = id.into_inner();
+    let user_service = UserService {};
+    let user = user_service.restore_user(user_id, &db.connection).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(user.version))
+        .json(user))
+}
+
+/// Many creates, updates and deletes in one request. A transactional batch
+/// answers with the first failure and applies nothing; a best-effort batch
+/// with failures answers 207 Multi-Status, one result per operation.
+async fn bulk_users(
+    permit: Permit<CreateUsers>,
+    db: web::Data<DatabaseService>,
+    request: web::Json<BulkRequest>,
+) -> Result<impl Responder, AppError> {
+    let request = request.into_inner();
+    if request.operations.is_empty() {
+        return Err(AppError::BadRequest(
+            "`operations` must not be empty".to_string(),
+        ));
+    }
+    if request.operations.len() > SETTINGS.bulk_max_operations {
+        return Err(AppError::PayloadTooLarge(format!(
+            "At most {} operations per request, got {}",
+            SETTINGS.bulk_max_operations,
+            request.operations.len()
+        )));
     }
+
+    let deletes = request
+        .operations
+        .iter()
+        .any(|operation| matches!(operation, BulkOperation::Delete { .. }));
+    if deletes {
+        permit.0.check(Permission::DeleteUsers, false)?;
+    }
+
+    let user_service = UserService {};
+    let response = user_service.bulk_users(request, &db.connection).await?;
+
+    let status = if response.failed > 0 {
+        StatusCode::MULTI_STATUS
+    } else {
+        StatusCode::OK
+    };
+    Ok(HttpResponse::build(status).json(response))
+}
+
+#[delete("/id/{id}/purge")]
+async fn purge_user(
+    _permit: Permit<DeleteUsers>,
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+    id: web::Path<u16>,
+    if_match: IfMatchVersion,
+) -> Result<impl Responder, AppError> {
+    let user_id = id.into_inner();
+    let user_service = UserService {};
+    user_service
+        .purge_user(user_id, &if_match.0, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
-pub fn handler_users() -> actix_web::Scope {
+/// Sets a user's password, replacing any current one.
+#[put("/id/{id}/password")]
+async fn set_password(
+    _permit: Permit<ManageAccess>,
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+    id: web::Path<u16>,
+    request: web::Json<PasswordSet>,
+) -> Result<impl Responder, AppError> {
+    let request = request.into_inner();
+    request.validate()?;
+    let user_service = UserService {};
+    user_service
+        .set_password(id.into_inner(), request.password, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Gives a user another role.
+#[put("/id/{id}/role")]
+async fn set_role(
+    _permit: Permit<ManageAccess>,
+    _admin: Admin,
+    db: web::Data<DatabaseService>,
+    id: web::Path<u16>,
+    request: web::Json<RoleSet>,
+) -> Result<impl Responder, AppError> {
+    let user_service = UserService {};
+    let user = user_service
+        .set_role(id.into_inner(), request.into_inner().role, &db.connection)
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(user.version))
+        .json(user))
+}
+
+pub fn handler_users() -> impl HttpServiceFactory {
     actix_web::web::scope("/users")
+        .wrap(from_fn(authenticate))
         .service(get_users)
+        .service(export_users)
+        .service(import_users)
         .service(get_user)
         .service(get_user_by_email)
         .service(create_user)
+        .service(
+            // Named like the resources of the route macros, after the handler
+            web::resource("/bulk")
+                .name("bulk_users")
+                .app_data(json_config().limit(SETTINGS.bulk_max_body_size))
+                .route(web::post().to(bulk_users)),
+        )
         .service(update_user)
+        .service(patch_user)
         .service(delete_user)
+        .service(restore_user)
+        .service(purge_user)
+        .service(set_password)
+        .service(set_role)
+}
+
+/// Describes the operations of [`handler_users`].
+pub fn docs(doc: &mut ApiDoc) {
+    let if_match = "`ETag` of the version being changed, or `*`";
+    doc.route("get", "/users/", |op| {
+        op.id("get_users")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::ListUsers)
+            .summary("List users, by page or by cursor")
+            .query::<QueryParamsUsers>()
+            .response_one_of::<Paginated<UserModel>, CursorPage<UserModel>>(
+                200,
+                "A page of users, as a `CursorPage` when `after` or `before` is given",
+            )
+            .error(400, "Invalid filters or pagination")
+    })
+    .route("post", "/users/", |op| {
+        op.id("create_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::CreateUsers)
+            .summary("Create a user")
+            .body::<UserCreate>("application/json")
+            .response::<UserModel>(201, "The created user")
+            .error(409, "The email is taken")
+            .error(422, "Invalid fields")
+    })
+    .route("get", "/users/export", |op| {
+        op.id("export_users")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::ListUsers)
+            .summary("Stream every user matching the filters")
+            .query::<QueryParamsExport>()
+            .query::<QueryParamsUsers>()
+            .raw_response(
+                200,
+                "One user per record or line",
+                &[CSV_CONTENT_TYPE, NDJSON_CONTENT_TYPE],
+            )
+            .error(400, "Invalid filters, or pagination parameters given")
+    })
+    .route("post", "/users/import", |op| {
+        op.id("import_users")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::CreateUsers)
+            .summary("Create users from a CSV or NDJSON upload")
+            .query::<QueryParamsImport>()
+            .raw_body(&[CSV_CONTENT_TYPE, NDJSON_CONTENT_TYPE])
+            .response::<ImportReport>(200, "Every user was valid, and imported unless `dry_run`")
+            .response::<ImportReport>(422, "Nothing was imported, the report lists every problem")
+            .error(413, "Upload too large")
+            .error(415, "Unknown format")
+    })
+    .route("post", "/users/bulk", |op| {
+        op.id("bulk_users")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::CreateUsers)
+            .summary("Create, update and delete many users")
+            .body::<BulkRequest>("application/json")
+            .response::<BulkResponse>(200, "Every operation succeeded")
+            .response::<BulkResponse>(207, "Best-effort batch where some operations failed")
+            // A failed transactional batch answers with the status of the failed
+            // operation, located at `operations[i]`, and applies nothing
+            .error(404, "Transactional batch: a user to change doesn't exist")
+            .error(409, "Transactional batch: an email is taken")
+            .error(412, "Transactional batch: a `version` doesn't match")
+            .error(413, "Too many operations or body too large")
+            .error(422, "Transactional batch: invalid fields")
+            .error(428, "Transactional batch: a `version` is required")
+    })
+    .route("get", "/users/id/{id}", |op| {
+        op.id("get_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::ReadUser)
+            .summary("Get a user by ID")
+            .path_param::<u16>("id")
+            .header(
+                "If-None-Match",
+                "Answer 304 if the `ETag` still matches",
+                false,
+            )
+            .response::<UserModel>(200, "The user")
+            .empty_response(304, "Not modified")
+            .error(404, "No such user")
+    })
+    .route("put", "/users/id/{id}", |op| {
+        op.id("update_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::UpdateUser)
+            .summary("Replace the editable fields of a user")
+            .path_param::<u16>("id")
+            .header("If-Match", if_match, true)
+            .body::<UserUpdate>("application/json")
+            .response::<UserModel>(200, "The updated user")
+            .error(404, "No such user")
+            .error(412, "The user has changed")
+            .error(422, "Invalid fields")
+            .error(428, "`If-Match` is missing")
+    })
+    .route("patch", "/users/id/{id}", |op| {
+        op.id("patch_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::UpdateUser)
+            .summary("Change some fields of a user")
+            .path_param::<u16>("id")
+            .header("If-Match", if_match, true)
+            .body::<serde_json::Value>(MERGE_PATCH_CONTENT_TYPE)
+            .body::<Vec<PatchOperation>>(JSON_PATCH_CONTENT_TYPE)
+            .response::<UserModel>(200, "The patched user")
+            .error(409, "A `test` operation failed")
+            .error(412, "The user has changed")
+            .error(415, "Not a merge patch or JSON patch")
+            .error(422, "The patched user is invalid")
+    })
+    .route("delete", "/users/id/{id}", |op| {
+        op.id("delete_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::DeleteUsers)
+            .summary("Soft delete a user")
+            .path_param::<u16>("id")
+            .header("If-Match", if_match, true)
+            .empty_response(204, "Deleted")
+            .error(404, "No such user")
+            .error(412, "The user has changed")
+    })
+    .route("post", "/users/id/{id}/restore", |op| {
+        op.id("restore_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::CreateUsers)
+            .summary("Restore a soft deleted user")
+            .path_param::<u16>("id")
+            .response::<UserModel>(200, "The restored user")
+            .error(404, "No such deleted user")
+    })
+    .route("delete", "/users/id/{id}/purge", |op| {
+        op.id("purge_user")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::DeleteUsers)
+            .summary("Delete a user for good")
+            .path_param::<u16>("id")
+            .header(ADMIN_KEY_HEADER, "Admin API key", true)
+            .header("If-Match", if_match, true)
+            .empty_response(204, "Purged")
+            .error(401, "Missing bearer token or admin key")
+            .error(403, "Wrong admin key, or a caller without the permission")
+    })
+    .route("put", "/users/id/{id}/password", |op| {
+        op.id("set_password")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::ManageAccess)
+            .summary("Set the password of a user")
+            .path_param::<u16>("id")
+            .header(ADMIN_KEY_HEADER, "Admin API key", true)
+            .body::<PasswordSet>("application/json")
+            .empty_response(204, "Set")
+            .error(401, "Missing bearer token or admin key")
+            .error(403, "Wrong admin key, or a caller without the permission")
+            .error(404, "No such user")
+            .error(422, "The password breaks the policy")
+    })
+    .route("put", "/users/id/{id}/role", |op| {
+        op.id("set_role")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::ManageAccess)
+            .summary("Set the role of a user")
+            .path_param::<u16>("id")
+            .header(ADMIN_KEY_HEADER, "Admin API key", true)
+            .body::<RoleSet>("application/json")
+            .response::<UserModel>(200, "The user with their new role")
+            .error(401, "Missing bearer token or admin key")
+            .error(403, "Wrong admin key, or a caller without the permission")
+            .error(404, "No such user")
+    })
+    .route("get", "/users/email/{email}", |op| {
+        op.id("get_user_by_email")
+            .tag("users")
+            .authenticated()
+            .permission(Permission::ReadUser)
+            .summary("Get a user by email")
+            .path_param::<String>("email")
+            .header(
+                "If-None-Match",
+                "Answer 304 if the `ETag` still matches",
+                false,
+            )
+            .response::<UserModel>(200, "The user")
+            .empty_response(304, "Not modified")
+            .error(404, "No such user")
+    });
 }
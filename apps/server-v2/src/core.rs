@@ -1,3 +1,14 @@
+pub mod api_keys;
 pub mod cache;
 pub mod config;
+pub mod csv;
 pub mod database;
+pub mod errors;
+pub mod health;
+pub mod jwt;
+pub mod password;
+pub mod patch;
+pub mod permissions;
+pub mod rate_limit;
+pub mod telemetry;
+pub mod validation;
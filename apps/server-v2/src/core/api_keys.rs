@@ -0,0 +1,88 @@
+use std::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+/// Marks our keys, so they are recognizable in configs and by secret scanners.
+pub const KEY_MARKER: &str = "umk";
+const PREFIX_BYTES: usize = 6;
+const SECRET_BYTES: usize = 32;
+const SALT_BYTES: usize = 16;
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// A plaintext API key, `umk_<prefix>_<secret>`. The prefix finds the stored
+/// key, the secret proves the caller holds it. Only shown once, on creation or
+/// rotation.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    pub prefix: String,
+    secret: String,
+}
+
+impl ApiKey {
+    pub fn generate() -> Self {
+        let prefix: String = random_bytes::<PREFIX_BYTES>()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        ApiKey {
+            prefix,
+            secret: URL_SAFE_NO_PAD.encode(random_bytes::<SECRET_BYTES>()),
+        }
+    }
+
+    /// `None` for anything that is not shaped like one of our keys.
+    pub fn parse(key: &str) -> Option<Self> {
+        let (marker, rest) = key.trim().split_once('_')?;
+        let (prefix, secret) = rest.split_once('_')?;
+        let valid_prefix =
+            prefix.len() == PREFIX_BYTES * 2 && prefix.bytes().all(|byte| byte.is_ascii_hexdigit());
+        (marker == KEY_MARKER && valid_prefix && !secret.is_empty()).then(|| ApiKey {
+            prefix: prefix.to_string(),
+            secret: secret.to_string(),
+        })
+    }
+
+    pub fn expose(&self) -> String {
+        format!("{KEY_MARKER}_{}_{}", self.prefix, self.secret)
+    }
+
+    /// A fresh salt for [`ApiKey::hash`].
+    pub fn new_salt() -> String {
+        URL_SAFE_NO_PAD.encode(random_bytes::<SALT_BYTES>())
+    }
+
+    fn mac(&self, salt: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(salt.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(self.secret.as_bytes());
+        mac
+    }
+
+    /// Keys are random and long, so a salted SHA-256 HMAC is enough; unlike
+    /// passwords they don't need a slow hash.
+    pub fn hash(&self, salt: &str) -> String {
+        URL_SAFE_NO_PAD.encode(self.mac(salt).finalize().into_bytes())
+    }
+
+    /// Compares in constant time.
+    pub fn verify(&self, salt: &str, hash: &str) -> bool {
+        URL_SAFE_NO_PAD
+            .decode(hash)
+            .is_ok_and(|hash| self.mac(salt).verify_slice(&hash).is_ok())
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ApiKey({KEY_MARKER}_{}_********)", self.prefix)
+    }
+}
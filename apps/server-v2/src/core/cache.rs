@@ -1,10 +1,21 @@
 use std::sync::LazyLock;
+use std::time::Duration;
 
 use super::config::SETTINGS;
-use redis::{self, Commands};
+use futures::StreamExt;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{self, AsyncCommands};
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::OnceCell;
+use tracing::instrument;
 
 pub struct RedisService {
     client: redis::Client,
+    // The connection manager spawns its driver and reconnection tasks on the runtime
+    // that creates it. Actix runs one runtime per worker (and tests one per test), so it
+    // lives on a dedicated runtime that outlives all of them.
+    runtime: Runtime,
+    manager: OnceCell<ConnectionManager>,
 }
 
 impl Default for RedisService {
@@ -16,51 +27,156 @@ impl Default for RedisService {
 impl RedisService {
     pub fn new() -> RedisService {
         let params = redis::ConnectionInfo {
-            addr: redis::ConnectionAddr::Tcp(
-                SETTINGS.redis_host.clone(),
-                SETTINGS.redis_port.parse().expect("Invalid Redis port"),
-            ),
+            addr: redis::ConnectionAddr::Tcp(SETTINGS.redis_host.clone(), SETTINGS.redis_port),
             redis: redis::RedisConnectionInfo {
                 db: 0,
                 username: None,
-                password: SETTINGS.redis_password.clone().ok(),
+                password: SETTINGS
+                    .redis_password
+                    .as_ref()
+                    .map(|password| password.expose().to_string()),
                 protocol: redis::ProtocolVersion::default(),
             },
         };
 
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("redis-connection")
+            .enable_all()
+            .build()
+            .expect("Failed to create Redis runtime");
+
         RedisService {
             client: redis::Client::open(params).expect("Failed to create Redis client"),
+            runtime,
+            manager: OnceCell::new(),
         }
     }
 
-    pub fn get(&self, key: &str) -> Option<String> {
-        Commands::get(&mut self.client.get_connection().unwrap(), key).ok()
+    /// Returns a handle to the shared multiplexed connection, establishing it on first use.
+    /// If Redis is unreachable the error is returned and the next call tries again.
+    async fn connection(&self) -> redis::RedisResult<ConnectionManager> {
+        let manager = self
+            .manager
+            .get_or_try_init(|| async {
+                let client = self.client.clone();
+                let config = ConnectionManagerConfig::new()
+                    .set_connection_timeout(SETTINGS.redis_connection_timeout) // Connection timeout
+                    .set_response_timeout(SETTINGS.redis_response_timeout) // Command timeout
+                    .set_number_of_retries(1) // Reconnection attempts before giving up
+                    .set_max_delay(1000); // Max backoff between attempts (ms)
+
+                self.runtime
+                    .spawn(ConnectionManager::new_with_config(client, config))
+                    .await
+                    .map_err(|e| {
+                        redis::RedisError::from((
+                            redis::ErrorKind::ClientError,
+                            "Redis connection task failed",
+                            e.to_string(),
+                        ))
+                    })?
+            })
+            .await?;
+
+        Ok(manager.clone())
+    }
+
+    #[instrument(
+        name = "redis GET",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "GET")
+    )]
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let mut con = self.connection().await.ok()?;
+        con.get(key).await.ok()
     }
 
-    pub fn set(&self, key: &str, value: &str) -> redis::RedisResult<()> {
-        Commands::set(&mut self.client.get_connection().unwrap(), key, value)
+    #[instrument(
+        name = "redis SET",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "SET")
+    )]
+    pub async fn set(&self, key: &str, value: &str) -> redis::RedisResult<()> {
+        self.connection().await?.set(key, value).await
     }
 
-    pub fn delete(&self, key: &str) -> redis::RedisResult<()> {
-        Commands::del(&mut self.client.get_connection().unwrap(), key)
+    #[instrument(
+        name = "redis DEL",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "DEL")
+    )]
+    pub async fn delete(&self, key: &str) -> redis::RedisResult<()> {
+        self.connection().await?.del(key).await
     }
 
-    pub fn delete_pattern(&self, pattern: &str) -> usize {
-        let mut con = self.client.get_connection().unwrap();
-        let keys: Vec<String> = Commands::keys(&mut con, pattern).unwrap_or(vec![]);
+    /// One round trip for all the keys, however many there are.
+    #[instrument(
+        name = "redis DEL",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "DEL")
+    )]
+    pub async fn delete_many(&self, keys: &[String]) -> redis::RedisResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.connection().await?.del(keys).await
+    }
+
+    #[instrument(
+        name = "redis SCAN",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "SCAN")
+    )]
+    pub async fn delete_pattern(&self, pattern: &str) -> usize {
+        let Ok(mut con) = self.connection().await else {
+            return 0;
+        };
+
+        // SCAN instead of KEYS so large keyspaces don't block the server
+        let keys: Vec<String> = match con.scan_match::<_, String>(pattern).await {
+            Ok(iter) => iter.filter_map(|key| async { key.ok() }).collect().await,
+            Err(_) => vec![],
+        };
         if keys.is_empty() {
             return 0;
         }
 
-        let results: Vec<redis::RedisResult<()>> = keys
-            .iter()
-            .map(|key| Commands::del(&mut con, key))
-            .collect();
-        results.iter().filter(|res| res.is_ok()).count()
+        con.del(keys).await.unwrap_or(0)
+    }
+
+    /// Counts a hit on `key`, kept for `ttl`, and reads the counter `previous`
+    /// in the same transaction. Returns both counts.
+    #[instrument(
+        name = "redis MULTI",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "MULTI")
+    )]
+    pub async fn count_hit(
+        &self,
+        key: &str,
+        ttl: Duration,
+        previous: &str,
+    ) -> redis::RedisResult<(u64, u64)> {
+        let mut con = self.connection().await?;
+        let (current, previous): (u64, Option<u64>) = redis::pipe()
+            .atomic()
+            .incr(key, 1)
+            .pexpire(key, ttl.as_millis() as i64)
+            .ignore()
+            .get(previous)
+            .query_async(&mut con)
+            .await?;
+        Ok((current, previous.unwrap_or(0)))
     }
 
-    pub fn ping(&self) -> redis::RedisResult<String> {
-        Commands::ping(&mut self.client.get_connection().unwrap())
+    #[instrument(
+        name = "redis PING",
+        skip_all,
+        fields(otel.kind = "client", db.system.name = "redis", db.operation.name = "PING")
+    )]
+    pub async fn ping(&self) -> redis::RedisResult<String> {
+        self.connection().await?.ping().await
     }
 }
 
@@ -1,35 +1,427 @@
 use rand::RngCore;
 use secp256k1::SecretKey;
-use std::env::VarError;
-use std::sync::LazyLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use url::Url;
 
 use dotenv::dotenv;
 
+use super::jwt::{Jwt, JwtAlgorithm};
+use super::rate_limit::RateLimit;
+
+/// A value that must never end up in logs. `Debug` and `Display` print a placeholder,
+/// the real value is only reachable through [`Secret::expose`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(********)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "********")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Development,
+    Test,
+    Production,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Profile::Development => "development",
+            Profile::Test => "test",
+            Profile::Production => "production",
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "development" | "dev" => Ok(Profile::Development),
+            "test" => Ok(Profile::Test),
+            "production" | "prod" => Ok(Profile::Production),
+            other => Err(format!(
+                "unknown profile \"{other}\", expected development, test or production"
+            )),
+        }
+    }
+}
+
+/// Every problem found while loading the configuration, reported at once.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration ({} problems):", self.errors.len())?;
+        for error in &self.errors {
+            write!(f, "\n  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parsing from the raw string found in the environment or in a config file.
+pub trait ConfigValue: Sized {
+    fn parse_value(raw: &str) -> Result<Self, String>;
+}
+
+impl ConfigValue for String {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        match raw.trim() {
+            "" => Err("must not be empty".to_string()),
+            value => Ok(value.to_string()),
+        }
+    }
+}
+
+impl ConfigValue for Secret {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        match raw {
+            "" => Err("must not be empty".to_string()),
+            value => Ok(Secret::new(value)),
+        }
+    }
+}
+
+impl ConfigValue for bool {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(true),
+            "false" | "0" | "no" | "off" => Ok(false),
+            _ => Err("expected a boolean (true/false)".to_string()),
+        }
+    }
+}
+
+impl ConfigValue for u16 {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        match raw.trim().parse::<u16>() {
+            Ok(0) | Err(_) => Err("expected a port number between 1 and 65535".to_string()),
+            Ok(port) => Ok(port),
+        }
+    }
+}
+
+impl ConfigValue for u32 {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        raw.trim()
+            .parse()
+            // Settings that can't be 0 are checked in `from_sources`
+            .map_err(|_| "expected a non-negative integer".to_string())
+    }
+}
+
+impl ConfigValue for usize {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        raw.trim()
+            .parse()
+            // Settings that can't be 0 are checked in `from_sources`
+            .map_err(|_| "expected a non-negative integer".to_string())
+    }
+}
+
+/// Accepts `500ms`, `8s`, `10m`, `1h` or a bare number of seconds.
+impl ConfigValue for Duration {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        let split = raw.find(|c: char| !c.is_ascii_digit()).unwrap_or(raw.len());
+        let (amount, unit) = raw.split_at(split);
+        let error = || format!("expected a duration like 500ms, 8s, 10m or 1h, got \"{raw}\"");
+
+        let amount: u64 = amount.parse().map_err(|_| error())?;
+        let seconds_per_unit = match unit.trim() {
+            "ms" => return Ok(Duration::from_millis(amount)),
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            _ => return Err(error()),
+        };
+        amount
+            .checked_mul(seconds_per_unit)
+            .map(Duration::from_secs)
+            .ok_or_else(|| format!("duration \"{raw}\" is too long"))
+    }
+}
+
+impl ConfigValue for JwtAlgorithm {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        raw.parse()
+    }
+}
+
+impl ConfigValue for RateLimit {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        raw.parse()
+    }
+}
+
+impl ConfigValue for Url {
+    fn parse_value(raw: &str) -> Result<Self, String> {
+        Url::parse(raw.trim()).map_err(|e| format!("expected a valid URL: {e}"))
+    }
+}
+
+/// The raw configuration layers. Environment variables win over the profile file,
+/// which wins over the default file, which wins over the defaults in the code.
+#[derive(Debug, Default, Clone)]
+pub struct ConfigSources {
+    pub env: HashMap<String, String>,
+    /// Flattened config file entries, e.g. `postgres.port`
+    pub file: HashMap<String, String>,
+}
+
+impl ConfigSources {
+    /// Reads the process environment and `config/default.*` + `config/<profile>.*`
+    /// (TOML or YAML) from `CONFIG_DIR`, which defaults to `./config`.
+    pub fn load() -> Result<Self, ConfigError> {
+        let env: HashMap<String, String> = std::env::vars().collect();
+        let mut errors = vec![];
+
+        let profile = match env.get("APP_ENV") {
+            Some(value) => Profile::from_str(value).unwrap_or_else(|e| {
+                errors.push(format!("APP_ENV: {e}"));
+                Profile::Development
+            }),
+            None => Profile::Development,
+        };
+        let dir = PathBuf::from(env.get("CONFIG_DIR").map_or("config", |dir| dir.as_str()));
+
+        let mut file = HashMap::new();
+        for name in ["default", profile.as_str()] {
+            match ConfigSources::read_file(&dir, name) {
+                Ok(entries) => file.extend(entries),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ConfigError { errors });
+        }
+        Ok(ConfigSources { env, file })
+    }
+
+    fn read_file(dir: &Path, name: &str) -> Result<HashMap<String, String>, String> {
+        for extension in ["toml", "yaml", "yml"] {
+            let path = dir.join(format!("{name}.{extension}"));
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            let value: Result<serde_json::Value, String> = match extension {
+                "toml" => toml::from_str(content.as_str()).map_err(|e| e.to_string()),
+                _ => serde_yaml::from_str(content.as_str()).map_err(|e| e.to_string()),
+            };
+            let value = value.map_err(|e| format!("{}: {e}", path.display()))?;
+
+            let mut entries = HashMap::new();
+            ConfigSources::flatten("", &value, &mut entries);
+            return Ok(entries);
+        }
+
+        // Config files are optional, every setting has a default or an env variable
+        Ok(HashMap::new())
+    }
+
+    fn flatten(prefix: &str, value: &serde_json::Value, out: &mut HashMap<String, String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (name, value) in map {
+                    let key = match prefix {
+                        "" => name.clone(),
+                        prefix => format!("{prefix}.{name}"),
+                    };
+                    ConfigSources::flatten(key.as_str(), value, out);
+                }
+            }
+            serde_json::Value::Null => {}
+            serde_json::Value::String(value) => {
+                out.insert(prefix.to_string(), value.clone());
+            }
+            other => {
+                out.insert(prefix.to_string(), other.to_string());
+            }
+        }
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.env
+            .get("APP_ENV")
+            .and_then(|value| Profile::from_str(value).ok())
+            .unwrap_or(Profile::Development)
+    }
+}
+
+/// Resolves settings one by one and keeps going after a bad value, so the final
+/// report contains every problem instead of only the first one.
+struct Loader<'a> {
+    sources: &'a ConfigSources,
+    errors: Vec<String>,
+}
+
+impl Loader<'_> {
+    fn raw(&self, key: &str, env: &str) -> Option<(String, String)> {
+        if let Some(value) = self.sources.env.get(env) {
+            return Some((value.clone(), env.to_string()));
+        }
+        self.sources
+            .file
+            .get(key)
+            .map(|value| (value.clone(), format!("{key} (config file)")))
+    }
+
+    fn optional<T: ConfigValue>(&mut self, key: &str, env: &str) -> Option<T> {
+        let (raw, origin) = self.raw(key, env)?;
+        match T::parse_value(raw.as_str()) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors.push(format!("{origin}: {e}"));
+                None
+            }
+        }
+    }
+
+    fn value<T: ConfigValue>(&mut self, key: &str, env: &str, default: T) -> T {
+        self.optional(key, env).unwrap_or(default)
+    }
+
+    fn required<T: ConfigValue>(&mut self, key: &str, env: &str) -> Option<T> {
+        if self.raw(key, env).is_none() {
+            self.errors
+                .push(format!("{env} ({key}): missing, this setting is required"));
+            return None;
+        }
+        self.optional(key, env)
+    }
+
+    fn check(&mut self, valid: bool, message: &str) {
+        if !valid {
+            self.errors.push(message.to_string());
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Settings {
+    pub profile: Profile,
+
     // Postgres Configuration
-    pub postgres_user: Result<String, VarError>,
-    pub postgres_password: Result<String, VarError>,
+    pub postgres_user: Option<String>,
+    pub postgres_password: Option<Secret>,
     pub postgres_host: String,
-    pub postgres_port: String,
+    pub postgres_port: u16,
     pub postgres_db: String,
+    /// Takes precedence over the individual Postgres settings when set
+    pub database_url: Option<Url>,
+    pub database_connect_timeout: Duration,
+    pub database_acquire_timeout: Duration,
+    pub database_max_connections: u32,
+    pub database_min_connections: u32,
 
     // Redis Configuration
     pub redis_host: String,
-    pub redis_port: String,
-    pub redis_password: Result<String, VarError>,
+    pub redis_port: u16,
+    pub redis_password: Option<Secret>,
+    pub redis_connection_timeout: Duration,
+    pub redis_response_timeout: Duration,
 
     // QuestDB Configuration
     pub questdb_host: String,
-    pub questdb_port: String,
-    pub questdb_user: Result<String, VarError>,
-    pub questdb_password: Result<String, VarError>,
-    pub questdb_pg_port: String,
+    pub questdb_port: u16,
+    pub questdb_user: Option<String>,
+    pub questdb_password: Option<Secret>,
+    pub questdb_pg_port: u16,
     pub questdb_db: String,
+    pub questdb_queue_capacity: usize,
+    pub questdb_batch_size: usize,
+    pub questdb_flush_interval: Duration,
 
     // Server configuration
-    pub secret_key: String,
+    pub server_host: String,
+    pub server_port: u16,
+    pub secret_key: Secret,
+    /// Required by admin-only endpoints (`X-Admin-Key`); they are disabled when unset
+    pub admin_api_key: Option<Secret>,
+    /// Reject writes without `If-Match` (428) instead of applying them unconditionally
+    pub require_if_match: bool,
+    /// Largest `limit` a listing accepts, which bounds responses and cache entries
+    pub max_page_size: usize,
+    /// Most operations a single `POST /users/bulk` may carry
+    pub bulk_max_operations: usize,
+    /// Largest accepted bulk request body or import upload, in bytes
+    pub bulk_max_body_size: usize,
     pub debug: bool,
+    pub health_check_timeout: Duration,
+
+    // Authentication
+    /// HS256 signs with `SECRET_KEY` as is, ES256K needs it to be a hex secp256k1 key
+    pub jwt_algorithm: JwtAlgorithm,
+    /// Lifetime of the access tokens issued by `POST /auth/token`
+    pub jwt_expiry: Duration,
+    /// `iss` put in issued tokens and required from presented ones when set
+    pub jwt_issuer: Option<String>,
+    /// `aud` put in issued tokens and required from presented ones when set
+    pub jwt_audience: Option<String>,
+    /// Clock skew tolerated on `exp` and `nbf`
+    pub jwt_leeway: Duration,
+    // Password policy
+    pub password_min_length: usize,
+    /// Also bounds what a login attempt may make us hash
+    pub password_max_length: usize,
+    pub password_require_mixed_case: bool,
+    pub password_require_digit: bool,
+    pub password_require_symbol: bool,
+    /// argon2id memory cost in KiB
+    pub argon2_memory_cost: u32,
+    /// argon2id number of passes
+    pub argon2_time_cost: u32,
+    /// argon2id lanes
+    pub argon2_parallelism: u32,
+
+    // Rate limiting, shared by every replica through Redis
+    pub rate_limit_enabled: bool,
+    /// Take the client address from `Forwarded` / `X-Forwarded-For`, only
+    /// safe behind a proxy that sets them
+    pub rate_limit_trust_forwarded: bool,
+    /// Per client address, as these are where credentials get guessed
+    pub rate_limit_auth: RateLimit,
+    /// Per API key or token subject
+    pub rate_limit_users: RateLimit,
+    pub rate_limit_api_keys: RateLimit,
+
+    // Tracing, exported over OTLP/HTTP when an endpoint is set
+    /// Base URL of the collector, such as `http://localhost:4318`
+    pub tracing_otlp_endpoint: Option<Url>,
+    pub tracing_service_name: String,
 }
 
 impl Settings {
@@ -37,42 +429,297 @@ impl Settings {
         dotenv().ok();
     }
 
-    fn generate_secret_key() -> String {
+    fn generate_secret_key() -> Secret {
         let mut data = [0u8; 32]; // Generate a 32-byte array
         rand::rng().fill_bytes(&mut data);
         let secret_key = SecretKey::from_byte_array(data);
-        secret_key.unwrap().display_secret().to_string()
+        Secret::new(secret_key.unwrap().display_secret().to_string())
     }
 
-    pub fn load_settings() -> Self {
+    pub fn load_settings() -> Result<Self, ConfigError> {
         Settings::load_env();
+        Settings::from_sources(&ConfigSources::load()?)
+    }
 
-        Settings {
-            postgres_user: std::env::var("POSTGRES_USER"),
-            postgres_password: std::env::var("POSTGRES_PASSWORD"),
-            postgres_db: std::env::var("POSTGRES_DB").unwrap_or(String::from("api_test")),
-            postgres_host: std::env::var("POSTGRES_HOST").unwrap_or(String::from("localhost")),
-            postgres_port: std::env::var("POSTGRES_PORT").unwrap_or(String::from("5432")),
+    /// Makes these the settings [`SETTINGS`] serves, so the configuration
+    /// `main` validated isn't loaded a second time. Must run before first use.
+    pub fn install(self) {
+        *VALIDATED.lock().unwrap() = Some(self);
+    }
+
+    pub fn from_sources(sources: &ConfigSources) -> Result<Self, ConfigError> {
+        let profile = sources.profile();
+        let production = profile == Profile::Production;
+        let mut loader = Loader {
+            sources,
+            errors: vec![],
+        };
+
+        // A generated key would change on every restart, so production needs a real one
+        let secret_key = if production {
+            loader.required("server.secret_key", "SECRET_KEY")
+        } else {
+            loader.optional("server.secret_key", "SECRET_KEY")
+        };
+        let postgres_password = if production {
+            loader.required("postgres.password", "POSTGRES_PASSWORD")
+        } else {
+            loader.optional("postgres.password", "POSTGRES_PASSWORD")
+        };
+
+        let settings = Settings {
+            profile,
+
+            postgres_user: loader.optional("postgres.user", "POSTGRES_USER"),
+            postgres_password,
+            postgres_host: loader.value("postgres.host", "POSTGRES_HOST", "localhost".into()),
+            postgres_port: loader.value("postgres.port", "POSTGRES_PORT", 5432),
+            postgres_db: loader.value("postgres.db", "POSTGRES_DB", "api_test".into()),
+            database_url: loader.optional("postgres.url", "DATABASE_URL"),
+            database_connect_timeout: loader.value(
+                "postgres.connect_timeout",
+                "DATABASE_CONNECT_TIMEOUT",
+                Duration::from_secs(8),
+            ),
+            database_acquire_timeout: loader.value(
+                "postgres.acquire_timeout",
+                "DATABASE_ACQUIRE_TIMEOUT",
+                Duration::from_secs(8),
+            ),
+            database_max_connections: loader.value(
+                "postgres.max_connections",
+                "DATABASE_MAX_CONNECTIONS",
+                10,
+            ),
+            database_min_connections: loader.value(
+                "postgres.min_connections",
+                "DATABASE_MIN_CONNECTIONS",
+                1,
+            ),
+
+            redis_host: loader.value("redis.host", "REDIS_HOST", "localhost".into()),
+            redis_port: loader.value("redis.port", "REDIS_PORT", 6379),
+            redis_password: loader.optional("redis.password", "REDIS_PASSWORD"),
+            redis_connection_timeout: loader.value(
+                "redis.connection_timeout",
+                "REDIS_CONNECTION_TIMEOUT",
+                Duration::from_secs(2),
+            ),
+            redis_response_timeout: loader.value(
+                "redis.response_timeout",
+                "REDIS_RESPONSE_TIMEOUT",
+                Duration::from_secs(2),
+            ),
+
+            questdb_host: loader.value("questdb.host", "QUESTDB_HOST", "localhost".into()),
+            questdb_port: loader.value("questdb.port", "QUESTDB_PORT", 9000),
+            questdb_user: loader.optional("questdb.user", "QUESTDB_USER"),
+            questdb_password: loader.optional("questdb.password", "QUESTDB_PASSWORD"),
+            questdb_pg_port: loader.value("questdb.pg_port", "QUESTDB_PG_PORT", 8812),
+            questdb_db: loader.value("questdb.db", "QUESTDB_DB", "logs".into()),
+            questdb_queue_capacity: loader.value(
+                "questdb.queue_capacity",
+                "QUESTDB_QUEUE_CAPACITY",
+                10_000,
+            ),
+            questdb_batch_size: loader.value("questdb.batch_size", "QUESTDB_BATCH_SIZE", 500),
+            questdb_flush_interval: loader.value(
+                "questdb.flush_interval",
+                "QUESTDB_FLUSH_INTERVAL",
+                Duration::from_secs(1),
+            ),
+
+            server_host: loader.value("server.host", "SERVER_HOST", "0.0.0.0".into()),
+            server_port: loader.value("server.port", "SERVER_PORT", 8000),
+            secret_key: secret_key.unwrap_or_else(Settings::generate_secret_key),
+            admin_api_key: loader.optional("server.admin_api_key", "ADMIN_API_KEY"),
+            require_if_match: loader.value("server.require_if_match", "REQUIRE_IF_MATCH", true),
+            max_page_size: loader.value("server.max_page_size", "MAX_PAGE_SIZE", 1000),
+            bulk_max_operations: loader.value(
+                "server.bulk_max_operations",
+                "BULK_MAX_OPERATIONS",
+                1000,
+            ),
+            bulk_max_body_size: loader.value(
+                "server.bulk_max_body_size",
+                "BULK_MAX_BODY_SIZE",
+                4 * 1024 * 1024,
+            ),
+            debug: loader.value("server.debug", "DEBUG", false),
+            health_check_timeout: loader.value(
+                "server.health_check_timeout",
+                "HEALTH_CHECK_TIMEOUT",
+                Duration::from_secs(2),
+            ),
 
-            redis_host: std::env::var("REDIS_HOST").unwrap_or(String::from("localhost")),
-            redis_port: std::env::var("REDIS_PORT").unwrap_or(String::from("6379")),
-            redis_password: std::env::var("REDIS_PASSWORD"),
+            jwt_algorithm: loader.value("auth.jwt_algorithm", "JWT_ALGORITHM", JwtAlgorithm::Hs256),
+            jwt_expiry: loader.value("auth.jwt_expiry", "JWT_EXPIRY", Duration::from_secs(3600)),
+            jwt_issuer: loader.optional("auth.jwt_issuer", "JWT_ISSUER"),
+            jwt_audience: loader.optional("auth.jwt_audience", "JWT_AUDIENCE"),
+            jwt_leeway: loader.value("auth.jwt_leeway", "JWT_LEEWAY", Duration::from_secs(30)),
+            password_min_length: loader.value(
+                "auth.password_min_length",
+                "PASSWORD_MIN_LENGTH",
+                12,
+            ),
+            password_max_length: loader.value(
+                "auth.password_max_length",
+                "PASSWORD_MAX_LENGTH",
+                128,
+            ),
+            password_require_mixed_case: loader.value(
+                "auth.password_require_mixed_case",
+                "PASSWORD_REQUIRE_MIXED_CASE",
+                false,
+            ),
+            password_require_digit: loader.value(
+                "auth.password_require_digit",
+                "PASSWORD_REQUIRE_DIGIT",
+                false,
+            ),
+            password_require_symbol: loader.value(
+                "auth.password_require_symbol",
+                "PASSWORD_REQUIRE_SYMBOL",
+                false,
+            ),
+            // OWASP's minimum for argon2id: 19 MiB, 2 passes, 1 lane
+            argon2_memory_cost: loader.value(
+                "auth.argon2_memory_cost",
+                "ARGON2_MEMORY_COST",
+                19 * 1024,
+            ),
+            argon2_time_cost: loader.value("auth.argon2_time_cost", "ARGON2_TIME_COST", 2),
+            argon2_parallelism: loader.value("auth.argon2_parallelism", "ARGON2_PARALLELISM", 1),
 
-            questdb_host: std::env::var("QUESTDB_HOST").unwrap_or(String::from("localhost")),
-            questdb_port: std::env::var("QUESTDB_PORT").unwrap_or(String::from("9000")),
-            questdb_user: std::env::var("QUESTDB_USER"),
-            questdb_password: std::env::var("QUESTDB_PASSWORD"),
-            questdb_pg_port: std::env::var("QUESTDB_PG_PORT").unwrap_or(String::from("8812")),
-            questdb_db: std::env::var("QUESTDB_DB").unwrap_or(String::from("logs")),
+            rate_limit_enabled: loader.value("rate_limit.enabled", "RATE_LIMIT_ENABLED", true),
+            rate_limit_trust_forwarded: loader.value(
+                "rate_limit.trust_forwarded",
+                "RATE_LIMIT_TRUST_FORWARDED",
+                false,
+            ),
+            rate_limit_auth: loader.value(
+                "rate_limit.auth",
+                "RATE_LIMIT_AUTH",
+                RateLimit {
+                    limit: 10,
+                    window: Duration::from_secs(60),
+                },
+            ),
+            rate_limit_users: loader.value(
+                "rate_limit.users",
+                "RATE_LIMIT_USERS",
+                RateLimit {
+                    limit: 600,
+                    window: Duration::from_secs(60),
+                },
+            ),
+            rate_limit_api_keys: loader.value(
+                "rate_limit.api_keys",
+                "RATE_LIMIT_API_KEYS",
+                RateLimit {
+                    limit: 60,
+                    window: Duration::from_secs(60),
+                },
+            ),
 
-            secret_key: std::env::var("SECRET_KEY").unwrap_or(Settings::generate_secret_key()),
-            debug: std::env::var("DEBUG")
-                .unwrap_or(String::from("false"))
-                .to_lowercase()
-                .trim()
-                == "true",
+            tracing_otlp_endpoint: loader
+                .optional("tracing.otlp_endpoint", "OTEL_EXPORTER_OTLP_ENDPOINT"),
+            tracing_service_name: loader.value(
+                "tracing.service_name",
+                "OTEL_SERVICE_NAME",
+                "user-management-api".into(),
+            ),
+        };
+
+        // Cross-field rules
+        if let Some(url) = &settings.tracing_otlp_endpoint {
+            loader.check(
+                matches!(url.scheme(), "http" | "https"),
+                "OTEL_EXPORTER_OTLP_ENDPOINT (tracing.otlp_endpoint): expected an http:// or https:// URL",
+            );
+        }
+        if let Some(url) = &settings.database_url {
+            loader.check(
+                matches!(url.scheme(), "postgres" | "postgresql"),
+                "DATABASE_URL (postgres.url): expected a postgres:// URL",
+            );
+        }
+        loader.check(
+            settings.database_min_connections <= settings.database_max_connections,
+            "DATABASE_MIN_CONNECTIONS (postgres.min_connections): must not exceed DATABASE_MAX_CONNECTIONS",
+        );
+        loader.check(
+            settings.database_max_connections > 0,
+            "DATABASE_MAX_CONNECTIONS (postgres.max_connections): must be greater than 0",
+        );
+        loader.check(
+            settings.questdb_queue_capacity > 0,
+            "QUESTDB_QUEUE_CAPACITY (questdb.queue_capacity): must be greater than 0",
+        );
+        loader.check(
+            settings.questdb_batch_size > 0,
+            "QUESTDB_BATCH_SIZE (questdb.batch_size): must be greater than 0",
+        );
+        loader.check(
+            settings.max_page_size > 0,
+            "MAX_PAGE_SIZE (server.max_page_size): must be greater than 0",
+        );
+        loader.check(
+            settings.bulk_max_operations > 0,
+            "BULK_MAX_OPERATIONS (server.bulk_max_operations): must be greater than 0",
+        );
+        loader.check(
+            settings.bulk_max_body_size > 0,
+            "BULK_MAX_BODY_SIZE (server.bulk_max_body_size): must be greater than 0",
+        );
+        loader.check(
+            !settings.health_check_timeout.is_zero(),
+            "HEALTH_CHECK_TIMEOUT (server.health_check_timeout): must be greater than 0",
+        );
+        loader.check(
+            settings.jwt_expiry.as_secs() > 0,
+            "JWT_EXPIRY (auth.jwt_expiry): must be at least 1s",
+        );
+        loader.check(
+            settings.password_min_length > 0
+                && settings.password_min_length <= settings.password_max_length,
+            "PASSWORD_MIN_LENGTH (auth.password_min_length): must be between 1 and PASSWORD_MAX_LENGTH",
+        );
+        if let Err(e) = argon2::Params::new(
+            settings.argon2_memory_cost,
+            settings.argon2_time_cost,
+            settings.argon2_parallelism,
+            None,
+        ) {
+            loader.check(
+                false,
+                &format!(
+                    "ARGON2_MEMORY_COST, ARGON2_TIME_COST, ARGON2_PARALLELISM (auth.argon2_*): {e}"
+                ),
+            );
+        }
+        if let Err(e) = Jwt::from_settings(&settings) {
+            loader.check(false, &format!("JWT_ALGORITHM (auth.jwt_algorithm): {e}"));
         }
+
+        if !loader.errors.is_empty() {
+            return Err(ConfigError {
+                errors: loader.errors,
+            });
+        }
+        Ok(settings)
     }
 }
 
-pub static SETTINGS: LazyLock<Settings> = LazyLock::new(Settings::load_settings);
+/// Settings validated by `main`, taken by [`SETTINGS`] on first use.
+static VALIDATED: Mutex<Option<Settings>> = Mutex::new(None);
+
+/// The installed settings, or loaded on first use where nothing installed them.
+/// Panics with the full report on invalid configuration, which only happens in
+/// tools and tests since `main` validates the configuration before anything else.
+pub static SETTINGS: LazyLock<Settings> =
+    LazyLock::new(|| {
+        VALIDATED.lock().unwrap().take().unwrap_or_else(|| {
+            Settings::load_settings().unwrap_or_else(|report| panic!("{report}"))
+        })
+    });
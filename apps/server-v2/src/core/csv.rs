@@ -0,0 +1,96 @@
+use std::fmt;
+
+use ::csv::{Position, ReaderBuilder, Terminator, WriterBuilder};
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv";
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// One RFC 4180 record, terminated by CRLF. Fields are quoted only when they
+/// contain a delimiter, a quote or a line break.
+pub fn write_record<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut writer = WriterBuilder::new()
+        .terminator(Terminator::CRLF)
+        .from_writer(vec![]);
+    writer
+        .write_record(fields.iter().map(AsRef::as_ref))
+        .and_then(|()| writer.flush().map_err(Into::into))
+        .expect("writing to memory can't fail");
+    let record = writer.into_inner().expect("the writer was flushed");
+    String::from_utf8(record).expect("the fields are UTF-8")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvError {
+    /// Line the broken record starts on, from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// A parsed record with the line it starts on, which is not its index once
+/// quoted fields span several lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvRecord {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Parses RFC 4180 CSV, accepting LF as well as CRLF line endings. Blank
+/// lines are skipped and records may have any number of fields, which the
+/// caller checks against the header. Stray quotes are kept as text and an
+/// unterminated quoted field runs to the end of the input.
+pub fn parse(input: &str) -> Result<Vec<CsvRecord>, CsvError> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(input.as_bytes());
+    let mut lines = LineCounter::new(input);
+    let mut records = vec![];
+    for result in reader.records() {
+        let record = result.map_err(|e| CsvError {
+            line: e.position().map_or(1, |position| lines.at(position)),
+            message: e.to_string(),
+        })?;
+        records.push(CsvRecord {
+            line: record.position().map_or(1, |position| lines.at(position)),
+            fields: record.iter().map(str::to_string).collect(),
+        });
+    }
+    Ok(records)
+}
+
+/// The reader's own line numbers count from before the blank lines it skips,
+/// so records are located by their byte offset instead.
+struct LineCounter<'a> {
+    input: &'a str,
+    offset: usize,
+    line: usize,
+}
+
+impl<'a> LineCounter<'a> {
+    fn new(input: &'a str) -> Self {
+        LineCounter {
+            input,
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    /// Positions only move forward, so each byte is scanned once.
+    fn at(&mut self, position: &Position) -> usize {
+        let start = position.byte() as usize;
+        let blank =
+            self.input[start..].len() - self.input[start..].trim_start_matches(['\r', '\n']).len();
+        let end = start + blank;
+        self.line += self.input[self.offset..end].matches('\n').count();
+        self.offset = end;
+        self.line
+    }
+}
@@ -1,4 +1,5 @@
 use super::config::SETTINGS;
+use super::telemetry::record_statement;
 use sea_orm::{ConnectOptions, DatabaseConnection};
 use std::time::Duration;
 
@@ -10,12 +11,43 @@ pub struct DatabaseService {
 pub struct DatabaseParams {
     pub protocol: Option<String>,
     pub host: String,
-    pub port: String,
+    pub port: u16,
     pub db: String,
     pub user: Option<String>,
     pub password: Option<String>,
 }
 
+impl DatabaseParams {
+    pub fn postgres() -> Self {
+        DatabaseParams {
+            protocol: Some("postgres".to_string()),
+            host: SETTINGS.postgres_host.clone(),
+            port: SETTINGS.postgres_port,
+            db: SETTINGS.postgres_db.clone(),
+            user: SETTINGS.postgres_user.clone(),
+            password: SETTINGS
+                .postgres_password
+                .as_ref()
+                .map(|password| password.expose().to_string()),
+        }
+    }
+
+    /// QuestDB speaks the Postgres wire protocol on its PG port.
+    pub fn questdb() -> Self {
+        DatabaseParams {
+            protocol: Some("postgres".to_string()),
+            host: SETTINGS.questdb_host.clone(),
+            port: SETTINGS.questdb_pg_port,
+            db: SETTINGS.questdb_db.clone(),
+            user: SETTINGS.questdb_user.clone(),
+            password: SETTINGS
+                .questdb_password
+                .as_ref()
+                .map(|password| password.expose().to_string()),
+        }
+    }
+}
+
 impl DatabaseService {
     pub fn create_database_uri(params: DatabaseParams) -> String {
         let credentials = match (params.user, params.password) {
@@ -35,32 +67,47 @@ impl DatabaseService {
         )
     }
 
-    pub async fn init(params: Option<DatabaseParams>) -> Self {
-        let params = params.unwrap_or(DatabaseParams {
-            protocol: Some("postgres".to_string()),
-            host: SETTINGS.postgres_host.clone(),
-            port: SETTINGS.postgres_port.clone(),
-            db: SETTINGS.postgres_db.clone(),
-            user: SETTINGS.postgres_user.clone().ok(),
-            password: SETTINGS.postgres_password.clone().ok(),
-        });
-        let uri = DatabaseService::create_database_uri(params);
+    fn connect_options(params: Option<DatabaseParams>) -> ConnectOptions {
+        let uri = match (params, &SETTINGS.database_url) {
+            (Some(params), _) => DatabaseService::create_database_uri(params),
+            (None, Some(url)) => url.to_string(),
+            (None, None) => DatabaseService::create_database_uri(DatabaseParams::postgres()),
+        };
         let mut options = ConnectOptions::new(uri);
 
         // Configure connection timeouts and pool settings
         options
-            .connect_timeout(Duration::from_secs(8)) // Connection timeout
-            .acquire_timeout(Duration::from_secs(8)) // Pool acquire timeout
+            .connect_timeout(SETTINGS.database_connect_timeout) // Connection timeout
+            .acquire_timeout(SETTINGS.database_acquire_timeout) // Pool acquire timeout
             .idle_timeout(Duration::from_secs(600)) // Connection idle timeout (10 minutes)
             .max_lifetime(Duration::from_secs(3600)) // Max connection lifetime (1 hour)
-            .max_connections(10) // Maximum pool connections
-            .min_connections(1) // Minimum pool connections
+            .max_connections(SETTINGS.database_max_connections) // Maximum pool connections
+            .min_connections(SETTINGS.database_min_connections) // Minimum pool connections
             .sqlx_logging(true) // Enable SQL logging for debugging
             .sqlx_logging_level(log::LevelFilter::Debug);
 
-        let connection = sea_orm::Database::connect(options)
+        options
+    }
+
+    pub async fn init(params: Option<DatabaseParams>) -> Self {
+        let options = DatabaseService::connect_options(params);
+        let mut connection = sea_orm::Database::connect(options)
             .await
             .expect("Failed to connect to the database");
+        connection.set_metric_callback(record_statement);
+
+        DatabaseService { connection }
+    }
+
+    /// Builds the pool without opening a connection, so an unreachable backend
+    /// doesn't prevent startup. Connections are established on first use.
+    pub async fn init_lazy(params: Option<DatabaseParams>) -> Self {
+        let mut options = DatabaseService::connect_options(params);
+        options.connect_lazy(true);
+        let mut connection = sea_orm::Database::connect(options)
+            .await
+            .expect("Failed to create the database pool");
+        connection.set_metric_callback(record_statement);
 
         DatabaseService { connection }
     }
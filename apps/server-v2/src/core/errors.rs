@@ -0,0 +1,284 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderValue, WWW_AUTHENTICATE};
+use actix_web::{HttpResponse, ResponseError};
+use sea_orm::error::SqlxPostgresError;
+use sea_orm::sqlx::Error as SqlxError;
+use sea_orm::{DbErr, RuntimeErr, SqlErr};
+use std::fmt;
+
+use super::config::SETTINGS;
+use super::patch::{PatchError, PatchErrorKind};
+use crate::schemas::api::{ErrorResponse, FieldError};
+
+const REALM: &str = "api";
+
+/// Every error a request can end with. Handlers return it with `?` and
+/// [`ResponseError`] turns it into a status code and an [`ErrorResponse`] body.
+#[derive(Debug)]
+pub enum AppError {
+    /// Malformed request: unparseable JSON, path or query parameters
+    BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    /// A unique constraint was violated, naming the column(s) that clash
+    Duplicate(FieldError),
+    /// Well-formed request whose content breaks a rule
+    Validation(String),
+    /// Request body failed validation, one entry per invalid field
+    InvalidFields(Vec<FieldError>),
+    /// No credentials, or not the ones this endpoint takes
+    Unauthorized(String),
+    /// A bearer token was given but is malformed, badly signed or expired
+    InvalidToken(String),
+    Forbidden(String),
+    UnsupportedMediaType(String),
+    /// Body or batch larger than the configured limits
+    PayloadTooLarge(String),
+    /// `If-Match` names a version the resource no longer has
+    PreconditionFailed(String),
+    /// A write came without the `If-Match` it must carry
+    PreconditionRequired(String),
+    /// The client went over its rate limit
+    TooManyRequests(String),
+    /// A dependency (database, cache, QuestDB) cannot be reached
+    Unavailable(String),
+    Internal(String),
+}
+
+impl AppError {
+    /// Machine readable name of the variant, sent as `error` in the body.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) | AppError::Duplicate(_) => "conflict",
+            AppError::Validation(_) | AppError::InvalidFields(_) => "validation_error",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::InvalidToken(_) => "invalid_token",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::PreconditionFailed(_) => "precondition_failed",
+            AppError::PreconditionRequired(_) => "precondition_required",
+            AppError::TooManyRequests(_) => "too_many_requests",
+            AppError::Unavailable(_) => "service_unavailable",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::BadRequest(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message)
+            | AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::InvalidToken(message)
+            | AppError::Forbidden(message)
+            | AppError::UnsupportedMediaType(message)
+            | AppError::PayloadTooLarge(message)
+            | AppError::PreconditionFailed(message)
+            | AppError::PreconditionRequired(message)
+            | AppError::TooManyRequests(message)
+            | AppError::Unavailable(message)
+            | AppError::Internal(message) => message,
+            AppError::Duplicate(field) => &field.message,
+            AppError::InvalidFields(_) => "Request validation failed",
+        }
+    }
+
+    pub fn fields(&self) -> &[FieldError] {
+        match self {
+            AppError::Duplicate(field) => std::slice::from_ref(field),
+            AppError::InvalidFields(fields) => fields,
+            _ => &[],
+        }
+    }
+
+    /// Same error, pointing at a part of the request: the message is prefixed
+    /// with `location` and field names become `location.field`.
+    pub fn at(self, location: &str) -> AppError {
+        let prefix = |message: String| format!("{location}: {message}");
+        let field = |error: FieldError| FieldError {
+            field: format!("{location}.{}", error.field),
+            message: error.message,
+        };
+        match self {
+            AppError::BadRequest(message) => AppError::BadRequest(prefix(message)),
+            AppError::NotFound(message) => AppError::NotFound(prefix(message)),
+            AppError::Conflict(message) => AppError::Conflict(prefix(message)),
+            AppError::Duplicate(error) => AppError::Duplicate(field(error)),
+            AppError::Validation(message) => AppError::Validation(prefix(message)),
+            AppError::InvalidFields(errors) => {
+                AppError::InvalidFields(errors.into_iter().map(field).collect())
+            }
+            AppError::Unauthorized(message) => AppError::Unauthorized(prefix(message)),
+            AppError::InvalidToken(message) => AppError::InvalidToken(prefix(message)),
+            AppError::Forbidden(message) => AppError::Forbidden(prefix(message)),
+            AppError::UnsupportedMediaType(message) => {
+                AppError::UnsupportedMediaType(prefix(message))
+            }
+            AppError::PayloadTooLarge(message) => AppError::PayloadTooLarge(prefix(message)),
+            AppError::PreconditionFailed(message) => AppError::PreconditionFailed(prefix(message)),
+            AppError::PreconditionRequired(message) => {
+                AppError::PreconditionRequired(prefix(message))
+            }
+            AppError::TooManyRequests(message) => AppError::TooManyRequests(prefix(message)),
+            AppError::Unavailable(message) => AppError::Unavailable(prefix(message)),
+            AppError::Internal(message) => AppError::Internal(prefix(message)),
+        }
+    }
+
+    pub fn to_response(&self) -> ErrorResponse {
+        let status_code = self.status_code();
+        // Don't leak internals (SQL errors, hosts) outside of debug mode
+        let message = match self {
+            AppError::Internal(_) if !SETTINGS.debug => "Internal server error".to_string(),
+            _ => self.message().to_string(),
+        };
+
+        ErrorResponse {
+            message,
+            status_code: status_code.as_u16(),
+            error: Some(self.kind().to_string()),
+            fields: self.fields().to_vec(),
+        }
+    }
+
+    /// `WWW-Authenticate` value every 401 must carry (RFC 9110, section 11.6.1),
+    /// with the RFC 6750 error code when a bearer token was rejected.
+    fn challenge(&self) -> Option<HeaderValue> {
+        let challenge = match self {
+            AppError::Unauthorized(_) => format!("Bearer realm=\"{REALM}\""),
+            AppError::InvalidToken(message) => format!(
+                "Bearer realm=\"{REALM}\", error=\"invalid_token\", error_description=\"{}\"",
+                message.replace(['"', '\\'], "")
+            ),
+            _ => return None,
+        };
+        HeaderValue::from_str(&challenge).ok()
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) | AppError::Duplicate(_) => StatusCode::CONFLICT,
+            AppError::Validation(_) | AppError::InvalidFields(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            AppError::Unauthorized(_) | AppError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status_code = self.status_code();
+        if status_code.is_server_error() {
+            log::error!("{}: {}", self.kind(), self.message());
+        } else {
+            log::info!("{}: {}", self.kind(), self.message());
+        }
+
+        let mut response = HttpResponse::build(status_code);
+        if let Some(challenge) = self.challenge() {
+            response.insert_header((WWW_AUTHENTICATE, challenge));
+        }
+        response.json(self.to_response())
+    }
+}
+
+/// Name of the column(s) behind a Postgres unique violation. The detail reads
+/// `Key (email)=(...) already exists.`; fall back to the constraint name.
+fn unique_violation_field(e: &DbErr) -> Option<String> {
+    let (DbErr::Exec(RuntimeErr::SqlxError(SqlxError::Database(error)))
+    | DbErr::Query(RuntimeErr::SqlxError(SqlxError::Database(error)))) = e
+    else {
+        return None;
+    };
+    let error = error.try_downcast_ref::<SqlxPostgresError>()?;
+
+    error
+        .detail()
+        .and_then(|detail| detail.strip_prefix("Key ("))
+        .and_then(|detail| detail.split_once(")=("))
+        .map(|(columns, _)| columns.to_string())
+        .or_else(|| error.column().map(str::to_string))
+        .or_else(|| error.constraint().map(str::to_string))
+}
+
+impl From<DbErr> for AppError {
+    fn from(e: DbErr) -> Self {
+        if let Some(SqlErr::UniqueConstraintViolation(message)) = e.sql_err() {
+            return match unique_violation_field(&e) {
+                Some(field) => AppError::Duplicate(FieldError {
+                    message: format!("A record with this {field} already exists"),
+                    field,
+                }),
+                None => AppError::Conflict(format!("Unique constraint violated: {message}")),
+            };
+        }
+
+        match e {
+            DbErr::RecordNotFound(message) => AppError::NotFound(message),
+            DbErr::ConnectionAcquire(_) | DbErr::Conn(_) => {
+                AppError::Unavailable(format!("Database unavailable: {e}"))
+            }
+            _ => AppError::Internal(format!("Database error: {e}")),
+        }
+    }
+}
+
+impl From<redis::RedisError> for AppError {
+    fn from(e: redis::RedisError) -> Self {
+        if e.is_io_error() || e.is_timeout() || e.is_connection_dropped() {
+            AppError::Unavailable(format!("Cache unavailable: {e}"))
+        } else {
+            AppError::Internal(format!("Cache error: {e}"))
+        }
+    }
+}
+
+impl From<questdb::Error> for AppError {
+    fn from(e: questdb::Error) -> Self {
+        match e.code() {
+            questdb::ErrorCode::CouldNotResolveAddr | questdb::ErrorCode::SocketError => {
+                AppError::Unavailable(format!("QuestDB unavailable: {e}"))
+            }
+            _ => AppError::Internal(format!("QuestDB error: {e}")),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Internal(format!("Serialization error: {e}"))
+    }
+}
+
+impl From<PatchError> for AppError {
+    fn from(e: PatchError) -> Self {
+        match e.kind {
+            // The document is not in the state the client expected
+            PatchErrorKind::TestFailed => AppError::Conflict(e.to_string()),
+            _ => AppError::Validation(e.to_string()),
+        }
+    }
+}
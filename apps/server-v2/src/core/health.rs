@@ -0,0 +1,124 @@
+use std::future::Future;
+use std::time::Instant;
+
+use actix_web::http::StatusCode;
+use schemars::JsonSchema;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+
+use super::cache::REDIS_SERVICE;
+use super::config::SETTINGS;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct DependencyHealth {
+    pub status: HealthStatus,
+    pub critical: bool,
+    pub latency_ms: f64,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub database: DependencyHealth,
+    pub cache: DependencyHealth,
+    pub questdb: DependencyHealth,
+}
+
+impl HealthReport {
+    /// The service is unhealthy when a critical dependency is down and degraded
+    /// when only optional ones (cache, log storage) are.
+    pub fn new(
+        database: DependencyHealth,
+        cache: DependencyHealth,
+        questdb: DependencyHealth,
+    ) -> Self {
+        let dependencies = [&database, &cache, &questdb];
+        let failing = dependencies
+            .iter()
+            .filter(|dependency| dependency.status != HealthStatus::Healthy);
+
+        let mut status = HealthStatus::Healthy;
+        for dependency in failing {
+            if dependency.critical {
+                status = HealthStatus::Unhealthy;
+                break;
+            }
+            status = HealthStatus::Degraded;
+        }
+
+        HealthReport {
+            status,
+            database,
+            cache,
+            questdb,
+        }
+    }
+
+    pub fn get_status_code(&self) -> StatusCode {
+        match self.status {
+            HealthStatus::Healthy => StatusCode::OK,
+            HealthStatus::Degraded | HealthStatus::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+/// Checks the dependencies through the pools the application already holds,
+/// instead of opening new connections on every probe.
+#[derive(Clone)]
+pub struct HealthService {
+    pub database: DatabaseConnection,
+    pub questdb: DatabaseConnection,
+}
+
+impl HealthService {
+    pub fn new(database: DatabaseConnection, questdb: DatabaseConnection) -> Self {
+        HealthService { database, questdb }
+    }
+
+    pub async fn check(&self) -> HealthReport {
+        let (database, cache, questdb) = tokio::join!(
+            HealthService::check_dependency(true, self.database.ping()),
+            HealthService::check_dependency(false, REDIS_SERVICE.ping()),
+            HealthService::check_dependency(false, self.questdb.ping()),
+        );
+
+        HealthReport::new(database, cache, questdb)
+    }
+
+    async fn check_dependency<T, E: std::fmt::Display>(
+        critical: bool,
+        check: impl Future<Output = Result<T, E>>,
+    ) -> DependencyHealth {
+        // Keep `HEALTH_CHECK_TIMEOUT` below the readinessProbe `timeoutSeconds`
+        // so the probe always gets an answer
+        let timeout = SETTINGS.health_check_timeout;
+        let start_time = Instant::now();
+        let result = tokio::time::timeout(timeout, check).await;
+        let latency_ms = start_time.elapsed().as_micros() as f64 / 1000.0;
+
+        let error = match result {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(_) => Some(format!("Timed out after {}ms", timeout.as_millis())),
+        };
+
+        DependencyHealth {
+            status: match error {
+                None => HealthStatus::Healthy,
+                Some(_) => HealthStatus::Unhealthy,
+            },
+            critical,
+            latency_ms,
+            error,
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey, ecdsa};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::config::{SETTINGS, Secret, Settings};
+use super::errors::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    /// HMAC-SHA256 keyed with `SECRET_KEY`
+    Hs256,
+    /// ECDSA on secp256k1 with SHA-256, `SECRET_KEY` being the hex secret key
+    Es256k,
+}
+
+impl JwtAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JwtAlgorithm::Hs256 => "HS256",
+            JwtAlgorithm::Es256k => "ES256K",
+        }
+    }
+}
+
+impl FromStr for JwtAlgorithm {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_uppercase().as_str() {
+            "HS256" => Ok(JwtAlgorithm::Hs256),
+            "ES256K" => Ok(JwtAlgorithm::Es256k),
+            other => Err(format!(
+                "unknown JWT algorithm \"{other}\", expected HS256 or ES256K"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for JwtAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `aud` is either one string or a list of them (RFC 7519, section 4.1.3).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    pub fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(value) => value == audience,
+            Audience::Many(values) => values.iter().any(|value| value == audience),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Claims {
+    /// Who the token was issued to
+    pub sub: String,
+    pub iat: i64,
+    pub exp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Audience>,
+}
+
+impl Claims {
+    /// The user a login token was issued to; other subjects are not users.
+    pub fn user_id(&self) -> Option<i32> {
+        self.sub.parse().ok().filter(|id| *id > 0)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+}
+
+enum Key {
+    Hmac(Secret),
+    Secp256k1 {
+        secret: SecretKey,
+        public: PublicKey,
+    },
+}
+
+/// Signs and verifies compact JWS tokens with one algorithm and one key. Only
+/// that algorithm is accepted, so a token can't pick `none` or switch to HMAC
+/// keyed with the public key.
+pub struct Jwt {
+    algorithm: JwtAlgorithm,
+    key: Key,
+    expiry: Duration,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+}
+
+fn invalid(message: &str) -> AppError {
+    AppError::InvalidToken(message.to_string())
+}
+
+fn decode_part(part: &str) -> Result<Vec<u8>, AppError> {
+    URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|_| invalid("Malformed token"))
+}
+
+impl Jwt {
+    pub fn new(
+        algorithm: JwtAlgorithm,
+        secret_key: &Secret,
+        expiry: Duration,
+        issuer: Option<String>,
+        audience: Option<String>,
+        leeway: Duration,
+    ) -> Result<Self, String> {
+        let key = match algorithm {
+            JwtAlgorithm::Hs256 => Key::Hmac(secret_key.clone()),
+            JwtAlgorithm::Es256k => {
+                let secret = SecretKey::from_str(secret_key.expose())
+                    .map_err(|_| "ES256K needs SECRET_KEY to be a hex secp256k1 secret key")?;
+                let public = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret);
+                Key::Secp256k1 { secret, public }
+            }
+        };
+        Ok(Jwt {
+            algorithm,
+            key,
+            expiry,
+            issuer,
+            audience,
+            leeway,
+        })
+    }
+
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        Jwt::new(
+            settings.jwt_algorithm,
+            &settings.secret_key,
+            settings.jwt_expiry,
+            settings.jwt_issuer.clone(),
+            settings.jwt_audience.clone(),
+            settings.jwt_leeway,
+        )
+    }
+
+    pub fn expiry(&self) -> Duration {
+        self.expiry
+    }
+
+    /// Claims for `subject`, valid from now for the configured expiry.
+    pub fn claims(&self, subject: &str) -> Claims {
+        let now = Utc::now().timestamp();
+        Claims {
+            sub: subject.to_string(),
+            iat: now,
+            exp: now + self.expiry.as_secs() as i64,
+            nbf: None,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone().map(Audience::One),
+        }
+    }
+
+    fn sign(&self, input: &[u8]) -> Vec<u8> {
+        match &self.key {
+            Key::Hmac(secret) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose().as_bytes())
+                    .expect("HMAC accepts keys of any length");
+                mac.update(input);
+                mac.finalize().into_bytes().to_vec()
+            }
+            Key::Secp256k1 { secret, .. } => {
+                let message = Message::from_digest(Sha256::digest(input).into());
+                let signature = Secp256k1::signing_only().sign_ecdsa(message, secret);
+                signature.serialize_compact().to_vec()
+            }
+        }
+    }
+
+    fn verify(&self, input: &[u8], signature: &[u8]) -> bool {
+        match &self.key {
+            Key::Hmac(secret) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose().as_bytes())
+                    .expect("HMAC accepts keys of any length");
+                mac.update(input);
+                // Constant time comparison
+                mac.verify_slice(signature).is_ok()
+            }
+            Key::Secp256k1 { public, .. } => {
+                let Ok(signature) = ecdsa::Signature::from_compact(signature) else {
+                    return false;
+                };
+                let message = Message::from_digest(Sha256::digest(input).into());
+                Secp256k1::verification_only()
+                    .verify_ecdsa(message, &signature, public)
+                    .is_ok()
+            }
+        }
+    }
+
+    pub fn encode(&self, claims: &Claims) -> Result<String, AppError> {
+        let header = Header {
+            alg: self.algorithm.as_str().to_string(),
+            typ: Some("JWT".to_string()),
+        };
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?)
+        );
+        let signature = URL_SAFE_NO_PAD.encode(self.sign(input.as_bytes()));
+        Ok(format!("{input}.{signature}"))
+    }
+
+    /// Checks the signature, then the time window, issuer and audience.
+    pub fn decode(&self, token: &str) -> Result<Claims, AppError> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("Malformed token"));
+        };
+
+        let header: Header = serde_json::from_slice(&decode_part(header)?)
+            .map_err(|_| invalid("Malformed token header"))?;
+        if header.alg != self.algorithm.as_str() {
+            return Err(invalid("Unexpected signing algorithm"));
+        }
+        let input = &token[..token.len() - signature.len() - 1];
+        if !self.verify(input.as_bytes(), &decode_part(signature)?) {
+            return Err(invalid("Invalid signature"));
+        }
+
+        let claims: Claims = serde_json::from_slice(&decode_part(payload)?)
+            .map_err(|_| invalid("Malformed token claims"))?;
+        let now = Utc::now().timestamp();
+        let leeway = self.leeway.as_secs() as i64;
+        if claims.exp + leeway <= now {
+            return Err(invalid("Token has expired"));
+        }
+        if claims.nbf.is_some_and(|nbf| nbf - leeway > now) {
+            return Err(invalid("Token is not valid yet"));
+        }
+        if let Some(issuer) = &self.issuer
+            && claims.iss.as_ref() != Some(issuer)
+        {
+            return Err(invalid("Unexpected issuer"));
+        }
+        if let Some(audience) = &self.audience
+            && !claims
+                .aud
+                .as_ref()
+                .is_some_and(|aud| aud.contains(audience))
+        {
+            return Err(invalid("Token is not meant for this audience"));
+        }
+        Ok(claims)
+    }
+}
+
+/// Built from [`SETTINGS`], which already checked the key fits the algorithm.
+pub static JWT: LazyLock<Jwt> =
+    LazyLock::new(|| Jwt::from_settings(&SETTINGS).unwrap_or_else(|e| panic!("{e}")));
@@ -0,0 +1,142 @@
+use std::sync::LazyLock;
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, Salt, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+
+use super::config::{SETTINGS, Settings};
+use super::errors::AppError;
+
+/// Rules a new password must follow. Length is counted in characters, the
+/// upper bound keeps hashing cost in check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_mixed_case: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+}
+
+impl PasswordPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        PasswordPolicy {
+            min_length: settings.password_min_length,
+            max_length: settings.password_max_length,
+            require_mixed_case: settings.password_require_mixed_case,
+            require_digit: settings.password_require_digit,
+            require_symbol: settings.password_require_symbol,
+        }
+    }
+
+    /// The reason `password` is rejected, or `None`. Usable as a validation rule.
+    pub fn check(&self, password: &str) -> Option<String> {
+        let length = password.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return Some(format!(
+                "must be between {} and {} characters long",
+                self.min_length, self.max_length
+            ));
+        }
+        if password.trim().is_empty() {
+            return Some("must not be blank".to_string());
+        }
+        if self.require_mixed_case
+            && !(password.chars().any(char::is_lowercase)
+                && password.chars().any(char::is_uppercase))
+        {
+            return Some("must mix lowercase and uppercase letters".to_string());
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            return Some("must contain a digit".to_string());
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            return Some("must contain a symbol".to_string());
+        }
+        None
+    }
+}
+
+/// argon2id with the configured cost. Hashes are PHC strings carrying their
+/// own parameters, so changing the cost keeps existing hashes verifiable.
+pub struct Passwords {
+    argon2: Argon2<'static>,
+    /// Verified against when there is no user, so a miss costs as much as a hit
+    dummy_hash: String,
+}
+
+impl Passwords {
+    pub fn new(memory_cost: u32, time_cost: u32, parallelism: u32) -> Result<Self, String> {
+        let params = Params::new(memory_cost, time_cost, parallelism, None)
+            .map_err(|e| format!("invalid argon2 parameters: {e}"))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let mut passwords = Passwords {
+            argon2,
+            dummy_hash: String::new(),
+        };
+        passwords.dummy_hash = passwords
+            .hash("dummy password")
+            .map_err(|e| e.to_string())?;
+        Ok(passwords)
+    }
+
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        Passwords::new(
+            settings.argon2_memory_cost,
+            settings.argon2_time_cost,
+            settings.argon2_parallelism,
+        )
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, AppError> {
+        let mut salt = [0u8; Salt::RECOMMENDED_LENGTH];
+        rand::rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt)
+            .map_err(|e| AppError::Internal(format!("Password salt error: {e}")))?;
+        let hash = self
+            .argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| AppError::Internal(format!("Password hashing error: {e}")))?;
+        Ok(hash.to_string())
+    }
+
+    /// `false` for a wrong password as well as for a hash that doesn't parse.
+    pub fn verify(&self, password: &str, hash: &str) -> bool {
+        match PasswordHash::new(hash) {
+            Ok(hash) => self
+                .argon2
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(e) => {
+                log::warn!("Stored password hash does not parse -- Error: {e}");
+                false
+            }
+        }
+    }
+
+    /// Burns the time of a verification when there is no hash to check.
+    pub fn verify_dummy(&self, password: &str) {
+        self.verify(password, &self.dummy_hash);
+    }
+
+    /// Whether `hash` was made with other parameters than the configured ones.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let configured = self.argon2.params();
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || Params::try_from(&hash).is_ok_and(|params| {
+                params.m_cost() != configured.m_cost()
+                    || params.t_cost() != configured.t_cost()
+                    || params.p_cost() != configured.p_cost()
+            })
+    }
+}
+
+pub static PASSWORD_POLICY: LazyLock<PasswordPolicy> =
+    LazyLock::new(|| PasswordPolicy::from_settings(&SETTINGS));
+
+/// Built from [`SETTINGS`], which already checked the argon2 parameters.
+pub static PASSWORDS: LazyLock<Passwords> =
+    LazyLock::new(|| Passwords::from_settings(&SETTINGS).unwrap_or_else(|e| panic!("{e}")));
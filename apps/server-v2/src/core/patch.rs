@@ -0,0 +1,8 @@
+// RFC 6902 and RFC 7396 are implemented by the `json-patch` crate: `json_patch`
+// applies all operations or none, and `merge_patch` never fails.
+pub use json_patch::{
+    PatchError, PatchErrorKind, PatchOperation, merge as merge_patch, patch as json_patch,
+};
+
+pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+pub const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
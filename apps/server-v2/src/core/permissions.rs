@@ -0,0 +1,84 @@
+use sea_orm::Iterable;
+
+use crate::models::users::Role;
+use crate::schemas::api_keys::Scope;
+
+/// What a route on users needs. Roles grant permissions to users holding a
+/// token, scopes to API keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// List, search and export users
+    ListUsers,
+    /// Get one user
+    ReadUser,
+    /// Create, import and restore users
+    CreateUsers,
+    /// Replace or patch one user
+    UpdateUser,
+    /// Soft delete and purge users
+    DeleteUsers,
+    /// Set passwords and roles
+    ManageAccess,
+}
+
+impl Permission {
+    fn verb(&self) -> &'static str {
+        match self {
+            Permission::ListUsers => "list",
+            Permission::ReadUser => "read",
+            Permission::CreateUsers => "create",
+            Permission::UpdateUser => "update",
+            Permission::DeleteUsers => "delete",
+            Permission::ManageAccess => "manage the access of",
+        }
+    }
+
+    /// The scope an API key needs instead.
+    pub fn scope(&self) -> Scope {
+        match self {
+            Permission::ListUsers | Permission::ReadUser => Scope::UsersRead,
+            _ => Scope::UsersWrite,
+        }
+    }
+
+    /// Whether `role` has the permission; `own` tells if the route targets
+    /// the caller's own record.
+    pub fn granted_to(&self, role: Role, own: bool) -> bool {
+        match role {
+            Role::Admin => true,
+            Role::Operator => !matches!(self, Permission::DeleteUsers | Permission::ManageAccess),
+            Role::Viewer => matches!(self, Permission::ListUsers | Permission::ReadUser),
+            Role::SelfOnly => own && matches!(self, Permission::ReadUser | Permission::UpdateUser),
+        }
+    }
+
+    /// Why `role` is refused, `None` when it isn't.
+    pub fn denial(&self, role: Role, own: bool) -> Option<String> {
+        if self.granted_to(role, own) {
+            None
+        } else if self.granted_to(role, true) {
+            Some(format!(
+                "Role \"{role}\" may only {} its own record",
+                self.verb()
+            ))
+        } else {
+            Some(format!("Role \"{role}\" may not {} users", self.verb()))
+        }
+    }
+
+    /// Roles with the permission, for the API documentation.
+    pub fn roles(&self) -> String {
+        Role::iter()
+            .filter_map(|role| {
+                if self.granted_to(role, false) {
+                    Some(role.to_string())
+                } else if self.granted_to(role, true) {
+                    Some(format!("{role} (own record)"))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
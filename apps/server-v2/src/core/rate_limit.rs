@@ -0,0 +1,89 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::config::ConfigValue;
+
+/// `limit` requests per `window`, written `600/1m` in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub window: Duration,
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || format!("expected requests per window like 600/1m, got \"{value}\"");
+        let (limit, window) = value.trim().split_once('/').ok_or_else(error)?;
+        let limit: u32 = limit.trim().parse().map_err(|_| error())?;
+        let window = Duration::parse_value(window)?;
+        if limit == 0 {
+            return Err("the limit must be greater than 0".to_string());
+        }
+        if window < Duration::from_secs(1) {
+            return Err("the window must be at least 1s".to_string());
+        }
+        Ok(RateLimit { limit, window })
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}s", self.limit, self.window.as_secs())
+    }
+}
+
+/// The verdict on one request, and what to tell the client about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the current window ends
+    pub reset: u64,
+    /// Seconds before a retry can succeed, 0 when allowed
+    pub retry_after: u64,
+}
+
+fn seconds(millis: f64) -> u64 {
+    (millis / 1000.0).ceil().max(1.0) as u64
+}
+
+impl RateLimit {
+    /// Sliding window estimate: the requests of the current fixed window, this
+    /// one included, plus those of the previous window weighted by how much of
+    /// it the last `window` still overlaps. `elapsed` is the time since the
+    /// current window began.
+    pub fn decide(&self, elapsed: Duration, current: u64, previous: u64) -> Decision {
+        let window = self.window.as_millis() as f64;
+        let elapsed = (elapsed.as_millis() as f64).min(window);
+        let left = window - elapsed;
+        let limit = f64::from(self.limit);
+        let (current, previous) = (current as f64, previous as f64);
+
+        let estimate = previous * left / window + current;
+        let allowed = estimate <= limit;
+        let retry_after = if allowed {
+            0
+        } else {
+            // Room for one more request once the estimate has decayed enough
+            let room = limit - 1.0;
+            let wait = if current <= room {
+                left - (room - current) * window / previous
+            } else {
+                left + window * (1.0 - room / current)
+            };
+            seconds(wait)
+        };
+
+        Decision {
+            allowed,
+            limit: self.limit,
+            remaining: (limit - estimate).max(0.0) as u32,
+            reset: seconds(left),
+            retry_after,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::SystemTime;
+
+use opentelemetry::KeyValue;
+use opentelemetry::global;
+use opentelemetry::trace::{Span as _, SpanKind, Status, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::Registry;
+
+use super::config::Settings;
+
+/// Instrumentation scope of every span the service opens.
+const SCOPE: &str = env!("CARGO_PKG_NAME");
+
+/// The spans of this crate as OpenTelemetry spans of `provider`. Spans of
+/// other crates are not even created, so they can't leak their fields (SeaORM
+/// records bound values) and their children attach to the nearest traced
+/// ancestor.
+pub fn subscriber(provider: &SdkTracerProvider) -> impl Subscriber + Send + Sync {
+    Registry::default()
+        .with(filter_fn(|metadata| {
+            metadata.target().split("::").next() == Some(env!("CARGO_CRATE_NAME"))
+        }))
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(SCOPE)))
+}
+
+/// SeaORM metric callback, reporting each statement as a client span of the
+/// current one. SeaORM only calls it once the statement is done, so the span
+/// is started in the past, from the global tracer provider.
+pub fn record_statement(info: &sea_orm::metric::Info<'_>) {
+    let parent = Span::current().context();
+    if !parent.span().span_context().is_valid() {
+        return;
+    }
+    let sql = info.statement.sql.as_str();
+    // Named after the operation, as the semantic conventions ask
+    let operation = sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    let end = SystemTime::now();
+    let tracer = global::tracer(SCOPE);
+    let mut span = tracer
+        .span_builder(operation.clone())
+        .with_kind(SpanKind::Client)
+        .with_start_time(end - info.elapsed)
+        .with_attributes([
+            KeyValue::new("db.system.name", "postgresql"),
+            KeyValue::new("db.operation.name", operation),
+            KeyValue::new("db.query.text", sql.to_string()),
+        ])
+        .start_with_context(&tracer, &parent);
+    if info.failed {
+        span.set_status(Status::error("Statement failed"));
+    }
+    span.end_with_timestamp(end);
+}
+
+/// Installs the global subscriber, tracer provider and W3C Trace Context
+/// propagator when an OTLP endpoint is configured, and returns the provider
+/// to be shut down with the server.
+pub fn init(settings: &Settings) -> Option<SdkTracerProvider> {
+    let endpoint = settings.tracing_otlp_endpoint.as_ref()?;
+    let exporter = endpoint
+        .join("v1/traces")
+        .map_err(|e| e.to_string())
+        .and_then(|endpoint| {
+            SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint.as_str())
+                .build()
+                .map_err(|e| e.to_string())
+        });
+    let exporter = match exporter {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            log::error!("Spans are not exported, the OTLP exporter failed -- Error: {e}");
+            return None;
+        }
+    };
+    let resource = Resource::builder()
+        .with_service_name(settings.tracing_service_name.clone())
+        .build();
+    // Exported in batches from a thread of its own, so closing a span only queues it
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    if tracing::subscriber::set_global_default(subscriber(&provider)).is_err() {
+        log::warn!("A tracing subscriber is already installed, spans are not exported");
+    }
+    Some(provider)
+}
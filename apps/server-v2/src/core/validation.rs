@@ -0,0 +1,125 @@
+use std::ops::RangeInclusive;
+
+use super::errors::AppError;
+use crate::schemas::api::FieldError;
+
+/// A rule returns the reason a value is invalid, or `None` when it passes.
+pub type Rule<'a, T> = &'a dyn Fn(&T) -> Option<String>;
+
+/// Request bodies implement this so handlers can reject them with a 422
+/// before anything reaches the CRUD layer.
+pub trait Validate {
+    fn validate(&self) -> Result<(), AppError>;
+}
+
+/// Runs every rule of every field and collects all failures, so the client
+/// gets the complete list of problems in one response.
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field<T: ?Sized>(mut self, name: &str, value: &T, rules: &[Rule<T>]) -> Self {
+        // Only the first failing rule is reported for a field
+        if let Some(message) = rules.iter().find_map(|rule| rule(value)) {
+            self.errors.push(FieldError {
+                field: name.to_string(),
+                message,
+            });
+        }
+        self
+    }
+
+    /// Absent optional fields are valid; present ones go through `rules`.
+    pub fn optional<T: ?Sized>(self, name: &str, value: Option<&T>, rules: &[Rule<T>]) -> Self {
+        match value {
+            Some(value) => self.field(name, value, rules),
+            None => self,
+        }
+    }
+
+    pub fn finish(self) -> Result<(), AppError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::InvalidFields(self.errors))
+        }
+    }
+}
+
+/// Syntactic check only (`local@domain.tld`), deliverability is not our concern.
+pub fn email(value: &str) -> Option<String> {
+    let invalid = Some("must be a valid email address".to_string());
+    if value.len() > 254 || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return invalid;
+    }
+
+    let Some((local, domain)) = value.rsplit_once('@') else {
+        return invalid;
+    };
+    if local.is_empty() || local.len() > 64 || local.contains('@') {
+        return invalid;
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return invalid;
+    }
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_label = |label: &&str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+    };
+    if labels.len() < 2 || !labels.iter().all(valid_label) {
+        return invalid;
+    }
+
+    None
+}
+
+/// Length in characters, not bytes, so accented names count as expected.
+pub fn length(range: RangeInclusive<usize>) -> impl Fn(&str) -> Option<String> {
+    move |value| {
+        let length = value.trim().chars().count();
+        if range.contains(&length) {
+            None
+        } else {
+            Some(format!(
+                "must be between {} and {} characters long",
+                range.start(),
+                range.end()
+            ))
+        }
+    }
+}
+
+/// Letters, digits, spaces and the punctuation found in names (`' - . _`).
+pub fn name_charset(value: &str) -> Option<String> {
+    let allowed = |c: char| c.is_alphanumeric() || c == ' ' || "'-._".contains(c);
+    if value.chars().all(allowed) {
+        None
+    } else {
+        Some("may only contain letters, digits, spaces and ' - . _".to_string())
+    }
+}
+
+pub fn range(range: RangeInclusive<i32>) -> impl Fn(&i32) -> Option<String> {
+    move |value| {
+        if range.contains(value) {
+            None
+        } else {
+            Some(format!(
+                "must be between {} and {}",
+                range.start(),
+                range.end()
+            ))
+        }
+    }
+}
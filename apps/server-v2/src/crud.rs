@@ -1,29 +1,303 @@
+pub mod api_keys;
+
+use super::core::errors::AppError;
 use super::models::prelude::Users as UserEntity;
-use super::models::users::{self, Model as UserModel};
-use super::schemas::{
-    api::ErrorResponse,
-    users::{UserCreate, UserUpdate},
+use super::models::users::{self, Model as UserModel, Role};
+use super::schemas::api::{Cursor, CursorDirection, CursorPage, Paginated, Precondition};
+use super::schemas::users::{
+    BulkMode, BulkOperation, BulkRequest, BulkResponse, BulkResult, ImportError, ImportReport,
+    ImportRow, UserCreate, UserFilters, UserPatch, UserSortField, UserUpdate,
 };
 
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
 use sea_orm::ActiveValue::{NotSet, Set};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait,
+    DatabaseConnection, DbErr, EntityTrait, Order, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, Select, TransactionTrait, sea_query::Expr,
 };
 
 use crate::core::cache::REDIS_SERVICE;
+use crate::core::config::SETTINGS;
+use crate::core::password::{PASSWORD_POLICY, PASSWORDS};
+use crate::core::patch::{json_patch, merge_patch};
+use crate::core::validation::Validate;
+use chrono::Utc;
+use futures::Stream;
+use futures::stream;
 use serde_json;
+use std::collections::HashSet;
+use tracing::{Instrument, instrument};
 
 pub struct UserService;
 
+/// Rows fetched per query while exporting
+const EXPORT_BATCH_SIZE: u64 = 500;
+/// Rows per `INSERT` while importing
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Every default query starts here so soft-deleted users stay hidden.
+fn active_users() -> Select<UserEntity> {
+    UserEntity::find().filter(users::Column::DeletedAt.is_null())
+}
+
+/// Drops every cached listing and both cached lookups of one user.
+async fn invalidate_user_cache(user_id: i32, email: &str) {
+    invalidate_users_cache(&[(user_id, email.to_string())]).await;
+}
+
+/// Same for any number of users, in two round trips to Redis.
+async fn invalidate_users_cache(users: &[(i32, String)]) {
+    REDIS_SERVICE.delete_pattern("users:*").await;
+    let keys: Vec<String> = users
+        .iter()
+        .flat_map(|(user_id, email)| [format!("user:id:{user_id}"), format!("user:email:{email}")])
+        .collect();
+    if let Err(e) = REDIS_SERVICE.delete_many(&keys).await {
+        log::warn!(
+            "Failed to delete {} cached users -- Error: {e}",
+            users.len()
+        );
+    }
+}
+
+async fn find_active<C: ConnectionTrait>(
+    user_id: i32,
+    connection: &C,
+) -> Result<UserModel, AppError> {
+    active_users()
+        .filter(users::Column::Id.eq(user_id))
+        .one(connection)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User with ID {user_id} not found")))
+}
+
+fn check_precondition(user: &UserModel, precondition: &Precondition) -> Result<(), AppError> {
+    if precondition.allows(user.version) {
+        Ok(())
+    } else {
+        Err(AppError::PreconditionFailed(format!(
+            "User with ID {} has changed, it is now at version {}",
+            user.id, user.version
+        )))
+    }
+}
+
+/// Writes `model` only if the row is still at `version`, and bumps it. A
+/// concurrent write between our read and this update makes it a 412.
+async fn update_versioned<C: ConnectionTrait>(
+    mut model: users::ActiveModel,
+    version: i32,
+    connection: &C,
+) -> Result<UserModel, AppError> {
+    model.version = Set(version + 1);
+    let model = model.before_save(connection, false).await?;
+    let result = UserEntity::update(model)
+        .filter(users::Column::Version.eq(version))
+        .exec(connection)
+        .await;
+
+    match result {
+        Err(DbErr::RecordNotUpdated) => Err(AppError::PreconditionFailed(
+            "User was modified concurrently".to_string(),
+        )),
+        result => Ok(result?),
+    }
+}
+
+async fn insert_user<C: ConnectionTrait>(
+    user: UserCreate,
+    connection: &C,
+) -> Result<UserModel, AppError> {
+    let active_model: users::ActiveModel = users::ActiveModel {
+        id: NotSet,
+        email: Set(user.email),
+        name: Set(user.name),
+        age: Set(user.age),
+        is_active: Set(Some(true)),
+        created_at: NotSet,
+        updated_at: NotSet,
+        deleted_at: NotSet,
+        version: NotSet,
+        password_hash: NotSet,
+        role: NotSet,
+    };
+
+    // 1.
+    let result: Result<UserModel, sea_orm::DbErr> = active_model.insert(connection).await;
+
+    // 2.
+    // let result: Result<sea_orm::InsertResult<users::ActiveModel>, sea_orm::DbErr> = UserEntity::insert(active_model).exec(connection).await;
+
+    Ok(result?)
+}
+
+async fn replace_fields<C: ConnectionTrait>(
+    user: UserModel,
+    update_user: UserUpdate,
+    connection: &C,
+) -> Result<UserModel, AppError> {
+    let version = user.version;
+    let mut active_model: users::ActiveModel = user.into();
+    active_model.name = Set(update_user.name);
+    active_model.email = Set(update_user.email);
+    active_model.age = Set(update_user.age);
+    active_model.is_active = Set(update_user.is_active);
+
+    update_versioned(active_model, version, connection).await
+}
+
+async fn soft_delete<C: ConnectionTrait>(
+    user: UserModel,
+    connection: &C,
+) -> Result<UserModel, AppError> {
+    let version = user.version;
+    let mut active_model: users::ActiveModel = user.into();
+    active_model.deleted_at = Set(Some(Utc::now().fixed_offset()));
+    update_versioned(active_model, version, connection).await
+}
+
+/// argon2 is slow on purpose, so it runs off the async workers.
+async fn hash_password(password: String) -> Result<String, AppError> {
+    tokio::task::spawn_blocking(move || PASSWORDS.hash(&password))
+        .await
+        .map_err(|e| AppError::Internal(format!("Password hashing failed: {e}")))?
+}
+
+/// Without a hash the check still costs one verification, and fails.
+async fn verify_password(password: String, hash: Option<String>) -> Result<bool, AppError> {
+    tokio::task::spawn_blocking(move || match hash {
+        Some(hash) => PASSWORDS.verify(&password, &hash),
+        None => {
+            PASSWORDS.verify_dummy(&password);
+            false
+        }
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Password verification failed: {e}")))
+}
+
+/// The hash is never served, but the row's trigger still moves `updated_at`,
+/// so the version is bumped and the cached user dropped like on any update.
+async fn store_password_hash<C: ConnectionTrait>(
+    user_id: i32,
+    hash: String,
+    connection: &C,
+) -> Result<(), AppError> {
+    let users = UserEntity::update_many()
+        .col_expr(users::Column::PasswordHash, Expr::value(hash))
+        .col_expr(
+            users::Column::Version,
+            Expr::col(users::Column::Version).add(1),
+        )
+        .filter(users::Column::Id.eq(user_id))
+        .filter(users::Column::DeletedAt.is_null())
+        .exec_with_returning(connection)
+        .await?;
+    let Some(user) = users.first() else {
+        return Err(AppError::NotFound(format!(
+            "User with ID {user_id} not found"
+        )));
+    };
+    invalidate_user_cache(user.id, &user.email).await;
+    Ok(())
+}
+
+/// Bulk operations carry their `If-Match` as `version`, under the same rule.
+fn bulk_precondition(version: Option<i32>) -> Result<Precondition, AppError> {
+    match version {
+        Some(version) => Ok(Precondition::Versions(vec![version])),
+        None if SETTINGS.require_if_match => Err(AppError::PreconditionRequired(
+            "`version` is required to update or delete a user".to_string(),
+        )),
+        None => Ok(Precondition::Any),
+    }
+}
+
+/// Runs one bulk operation without touching the cache. Returns its status and
+/// the user before and after, whose cache entries the caller drops.
+async fn apply_operation<C: ConnectionTrait>(
+    operation: BulkOperation,
+    connection: &C,
+) -> Result<(StatusCode, Option<UserModel>, UserModel), AppError> {
+    match operation {
+        BulkOperation::Create { user } => {
+            user.validate()?;
+            let created = insert_user(user, connection).await?;
+            Ok((StatusCode::CREATED, None, created))
+        }
+        BulkOperation::Update { id, version, user } => {
+            let precondition = bulk_precondition(version)?;
+            user.validate()?;
+            let current = find_active(id, connection).await?;
+            check_precondition(&current, &precondition)?;
+            let updated = replace_fields(current.clone(), user, connection).await?;
+            Ok((StatusCode::OK, Some(current), updated))
+        }
+        BulkOperation::Delete { id, version } => {
+            let precondition = bulk_precondition(version)?;
+            let current = find_active(id, connection).await?;
+            check_precondition(&current, &precondition)?;
+            let deleted = soft_delete(current, connection).await?;
+            Ok((StatusCode::NO_CONTENT, None, deleted))
+        }
+    }
+}
+
+fn filter_users(filters: &UserFilters) -> Select<UserEntity> {
+    let mut condition = Condition::all();
+    if let Some(search_term) = filters.search.as_deref() {
+        condition = condition.add(
+            users::Column::Name
+                .contains(search_term)
+                .or(users::Column::Email.contains(search_term)),
+        );
+    }
+    if let Some(is_active) = filters.is_active {
+        condition = condition.add(users::Column::IsActive.eq(is_active));
+    }
+    if let Some(age_min) = filters.age_min {
+        condition = condition.add(users::Column::Age.gte(age_min));
+    }
+    if let Some(age_max) = filters.age_max {
+        condition = condition.add(users::Column::Age.lte(age_max));
+    }
+    if let Some(created_after) = filters.created_after {
+        condition = condition.add(users::Column::CreatedAt.gte(created_after));
+    }
+    if let Some(created_before) = filters.created_before {
+        condition = condition.add(users::Column::CreatedAt.lt(created_before));
+    }
+    if let Some(updated_after) = filters.updated_after {
+        condition = condition.add(users::Column::UpdatedAt.gte(updated_after));
+    }
+    if let Some(updated_before) = filters.updated_before {
+        condition = condition.add(users::Column::UpdatedAt.lt(updated_before));
+    }
+    active_users().filter(condition)
+}
+
+fn sort_column(field: UserSortField) -> users::Column {
+    match field {
+        UserSortField::Name => users::Column::Name,
+        UserSortField::Email => users::Column::Email,
+        UserSortField::Age => users::Column::Age,
+        UserSortField::CreatedAt => users::Column::CreatedAt,
+        UserSortField::UpdatedAt => users::Column::UpdatedAt,
+    }
+}
+
 impl UserService {
+    #[instrument(name = "UserService::get_user_by_id", skip_all, fields(user.id = user_id))]
     pub async fn get_user_by_id(
         &self,
         user_id: u16,
         connection: &DatabaseConnection,
-    ) -> Result<UserModel, ErrorResponse> {
+    ) -> Result<UserModel, AppError> {
         // Return cached response
         let cache_key = format!("user:id:{user_id}");
-        let cached_user = REDIS_SERVICE.get(cache_key.as_str());
+        let cached_user = REDIS_SERVICE.get(cache_key.as_str()).await;
         if let Some(user) = cached_user {
             match serde_json::from_str::<UserModel>(user.as_str()) {
                 Ok(user_model) => return Ok(user_model),
@@ -33,44 +307,33 @@ impl UserService {
             }
         }
 
-        let result = UserEntity::find_by_id(user_id as i32).one(connection).await;
-        match result {
-            Ok(Some(user)) => {
-                // Try to cache the response
-                match serde_json::to_string(&user) {
-                    Ok(cached_response) => {
-                        let set_result =
-                            REDIS_SERVICE.set(cache_key.as_str(), cached_response.as_str());
-                        if let Err(e) = set_result {
-                            log::warn!("Failed to cache response with ID {user_id} -- Error: {e} ")
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!(
-                            "Failed to serialize cached response with ID {user_id} -- Error: {e}"
-                        );
-                    }
-                };
-                Ok(user)
+        let user = find_active(user_id as i32, connection).await?;
+
+        // Try to cache the response
+        match serde_json::to_string(&user) {
+            Ok(cached_response) => {
+                let set_result = REDIS_SERVICE
+                    .set(cache_key.as_str(), cached_response.as_str())
+                    .await;
+                if let Err(e) = set_result {
+                    log::warn!("Failed to cache response with ID {user_id} -- Error: {e} ")
+                }
             }
-            Ok(None) => Err(ErrorResponse {
-                message: format!("User with ID {user_id} not found"),
-                status_code: 404,
-            }),
-            Err(e) => Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            }),
-        }
+            Err(e) => {
+                log::warn!("Failed to serialize cached response with ID {user_id} -- Error: {e}");
+            }
+        };
+        Ok(user)
     }
 
+    #[instrument(name = "UserService::get_user_by_email", skip_all)]
     pub async fn get_user_by_email(
         &self,
         email: &str,
         connection: &DatabaseConnection,
-    ) -> Result<UserModel, ErrorResponse> {
+    ) -> Result<UserModel, AppError> {
         let cache_key = format!("user:email:{email}");
-        let cached_user = REDIS_SERVICE.get(cache_key.as_str());
+        let cached_user = REDIS_SERVICE.get(cache_key.as_str()).await;
         if let Some(user) = cached_user {
             match serde_json::from_str::<UserModel>(user.as_str()) {
                 Ok(user_model) => return Ok(user_model),
@@ -80,53 +343,40 @@ impl UserService {
             }
         }
 
-        let result = UserEntity::find()
+        let user = active_users()
             .filter(users::Column::Email.eq(email.to_owned()))
             .one(connection)
-            .await;
-        match result {
-            Ok(Some(user)) => {
-                match serde_json::to_string(&user) {
-                    Ok(cached_response) => {
-                        let set_result =
-                            REDIS_SERVICE.set(cache_key.as_str(), cached_response.as_str());
-                        if let Err(e) = set_result {
-                            log::warn!("Failed to cache response with email {email} -- Error: {e} ")
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!(
-                            "Failed to serialize cached response with email {email} -- Error: {e}"
-                        );
-                    }
-                };
-                Ok(user)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("User with email {email} not found")))?;
+
+        match serde_json::to_string(&user) {
+            Ok(cached_response) => {
+                let set_result = REDIS_SERVICE
+                    .set(cache_key.as_str(), cached_response.as_str())
+                    .await;
+                if let Err(e) = set_result {
+                    log::warn!("Failed to cache response with email {email} -- Error: {e} ")
+                }
             }
-            Ok(None) => Err(ErrorResponse {
-                message: format!("User with email {email} not found"),
-                status_code: 404,
-            }),
-            Err(e) => Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            }),
-        }
+            Err(e) => {
+                log::warn!("Failed to serialize cached response with email {email} -- Error: {e}");
+            }
+        };
+        Ok(user)
     }
 
+    #[instrument(name = "UserService::get_users", skip_all)]
     pub async fn get_users(
         &self,
         connection: &DatabaseConnection,
         page: usize,
         limit: usize,
-        search: Option<String>,
-    ) -> Result<Vec<UserModel>, ErrorResponse> {
-        let cache_key = format!(
-            "users:page:{page}:limit:{limit}:search:{}",
-            search.clone().unwrap_or("none".to_string())
-        );
-        let cached_response = REDIS_SERVICE.get(cache_key.as_str());
+        filters: &UserFilters,
+    ) -> Result<Paginated<UserModel>, AppError> {
+        let cache_key = format!("users:page:{page}:limit:{limit}:{}", filters.cache_key());
+        let cached_response = REDIS_SERVICE.get(cache_key.as_str()).await;
         if let Some(response) = cached_response {
-            match serde_json::from_str::<Vec<UserModel>>(response.as_str()) {
+            match serde_json::from_str::<Paginated<UserModel>>(response.as_str()) {
                 Ok(users) => return Ok(users),
                 _ => {
                     log::warn!("Failed to deserialize cached users")
@@ -138,185 +388,579 @@ impl UserService {
         // let result = UserEntity::find().all(connection).await;
 
         // After (with pagination)
-        let offset = (page - 1) * limit;
-        let mut query = UserEntity::find();
-        sea_orm::QueryTrait::query(&mut query)
+        // Far enough pages overflow, or exceed the largest OFFSET Postgres takes
+        let offset = page
+            .saturating_sub(1)
+            .checked_mul(limit)
+            .and_then(|offset| i64::try_from(offset).ok())
+            .ok_or_else(|| AppError::BadRequest("`page` is out of range".to_string()))?;
+        let mut query = filter_users(filters);
+        // Count before paging so `total` covers every match of the filters
+        let total = query.clone().count(connection).await?;
+        for sort in &filters.sort {
+            let order = if sort.descending {
+                Order::Desc
+            } else {
+                Order::Asc
+            };
+            query = query.order_by(sort_column(sort.field), order);
+        }
+        // `id` breaks ties so pages never overlap or skip rows
+        let items = query
+            .order_by_asc(users::Column::Id)
             .offset(offset as u64)
-            .limit(limit as u64);
-        if let Some(search_term) = search {
-            query = query.filter(
-                users::Column::Name
-                    .contains(search_term.as_str())
-                    .or(users::Column::Email.contains(search_term.as_str())),
-            );
+            .limit(limit as u64)
+            .all(connection)
+            .await?;
+        let users = Paginated::new(items, total, page as u64, limit as u64);
+
+        match serde_json::to_string(&users) {
+            Ok(cached_response) => {
+                let set_result = REDIS_SERVICE
+                    .set(cache_key.as_str(), cached_response.as_str())
+                    .await;
+                if let Err(e) = set_result {
+                    log::warn!("Failed to cache response -- Error: {e} ")
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to serialize cached response -- Error: {e}");
+            }
+        };
+        Ok(users)
+    }
+
+    /// Keyset pagination on `id`: pages stay stable while rows are inserted or
+    /// deleted around them, and deep pages cost the same as the first one.
+    /// The order is the key itself, so `filters.sort` does not apply here.
+    #[instrument(name = "UserService::get_users_by_cursor", skip_all)]
+    pub async fn get_users_by_cursor(
+        &self,
+        connection: &DatabaseConnection,
+        direction: CursorDirection,
+        limit: usize,
+        filters: &UserFilters,
+    ) -> Result<CursorPage<UserModel>, AppError> {
+        let position = match direction {
+            CursorDirection::After(Some(cursor)) => format!("after:{}", cursor.id),
+            CursorDirection::After(None) => "after:start".to_string(),
+            CursorDirection::Before(cursor) => format!("before:{}", cursor.id),
+        };
+        let cache_key = format!(
+            "users:cursor:{position}:limit:{limit}:{}",
+            filters.cache_key()
+        );
+        let cached_response = REDIS_SERVICE.get(cache_key.as_str()).await;
+        if let Some(response) = cached_response {
+            match serde_json::from_str::<CursorPage<UserModel>>(response.as_str()) {
+                Ok(users) => return Ok(users),
+                _ => {
+                    log::warn!("Failed to deserialize cached users")
+                }
+            }
         }
-        let result = query.all(connection).await;
-
-        match result {
-            Ok(users) => {
-                match serde_json::to_string(&users) {
-                    Ok(cached_response) => {
-                        let set_result =
-                            REDIS_SERVICE.set(cache_key.as_str(), cached_response.as_str());
-                        if let Err(e) = set_result {
-                            log::warn!("Failed to cache response -- Error: {e} ")
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to serialize cached response -- Error: {e}");
-                    }
-                };
-                Ok(users)
+
+        // One extra row tells whether another page follows
+        let query = filter_users(filters).limit((limit as u64).saturating_add(1));
+        let mut items = match direction {
+            CursorDirection::After(cursor) => {
+                let mut query = query.order_by_asc(users::Column::Id);
+                if let Some(cursor) = cursor {
+                    query = query.filter(users::Column::Id.gt(cursor.id));
+                }
+                query.all(connection).await?
+            }
+            CursorDirection::Before(cursor) => {
+                let mut items = query
+                    .filter(users::Column::Id.lt(cursor.id))
+                    .order_by_desc(users::Column::Id)
+                    .all(connection)
+                    .await?;
+                items.reverse();
+                items
+            }
+        };
+
+        let has_more = items.len() > limit;
+        if has_more {
+            match direction {
+                CursorDirection::After(_) => items.truncate(limit),
+                CursorDirection::Before(_) => {
+                    items.drain(..items.len() - limit);
+                }
             }
-            Err(e) => Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            }),
         }
+
+        let cursor_of = |user: Option<&UserModel>| user.map(|user| Cursor { id: user.id }.encode());
+        let (next_cursor, prev_cursor) = match direction {
+            CursorDirection::After(cursor) => (
+                cursor_of(items.last().filter(|_| has_more)),
+                cursor_of(items.first().filter(|_| cursor.is_some())),
+            ),
+            CursorDirection::Before(_) => (
+                cursor_of(items.last()),
+                cursor_of(items.first().filter(|_| has_more)),
+            ),
+        };
+        let users = CursorPage {
+            items,
+            limit: limit as u64,
+            next_cursor,
+            prev_cursor,
+            links: Default::default(),
+        };
+
+        match serde_json::to_string(&users) {
+            Ok(cached_response) => {
+                let set_result = REDIS_SERVICE
+                    .set(cache_key.as_str(), cached_response.as_str())
+                    .await;
+                if let Err(e) = set_result {
+                    log::warn!("Failed to cache response -- Error: {e} ")
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to serialize cached response -- Error: {e}");
+            }
+        };
+        Ok(users)
     }
 
+    #[instrument(name = "UserService::create_user", skip_all)]
     pub async fn create_user(
         &self,
         user: UserCreate,
         connection: &DatabaseConnection,
-    ) -> Result<UserModel, ErrorResponse> {
-        let active_model: users::ActiveModel = users::ActiveModel {
-            id: NotSet,
-            email: Set(user.email),
-            name: Set(user.name),
-            age: Set(user.age),
-            is_active: Set(Some(true)),
-            created_at: NotSet,
-            updated_at: NotSet,
-        };
-
-        // 1.
-        let result: Result<UserModel, sea_orm::DbErr> = active_model.insert(connection).await;
-
-        // 2.
-        // let result: Result<sea_orm::InsertResult<users::ActiveModel>, sea_orm::DbErr> = UserEntity::insert(active_model).exec(connection).await;
+    ) -> Result<UserModel, AppError> {
+        let result = insert_user(user, connection).await;
 
         // Invalidate cached response of all users
-        REDIS_SERVICE.delete_pattern("users:*");
-
-        match result {
-            Ok(user_result) => Ok(user_result),
-            Err(e) => Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            }),
-        }
+        REDIS_SERVICE.delete_pattern("users:*").await;
+
+        result
     }
 
+    #[instrument(name = "UserService::update_user", skip_all, fields(user.id = user_id))]
     pub async fn update_user(
         &self,
         user_id: u16,
         update_user: UserUpdate,
+        precondition: &Precondition,
         connection: &DatabaseConnection,
-    ) -> Result<UserModel, ErrorResponse> {
-        let active_model: Result<Option<UserModel>, sea_orm::DbErr> =
-            UserEntity::find_by_id(user_id).one(connection).await;
-
-        if active_model.is_err() {
-            return Err(ErrorResponse {
-                message: format!("Database error: {}", active_model.err().unwrap()),
-                status_code: 500,
-            });
+    ) -> Result<UserModel, AppError> {
+        let user = find_active(user_id as i32, connection).await?;
+
+        check_precondition(&user, precondition)?;
+
+        self.replace_user(user, update_user, connection).await
+    }
+
+    /// Applies a merge patch or JSON patch to the editable fields of the user.
+    /// Members the patch leaves out keep their value; explicit `null`s clear them.
+    #[instrument(name = "UserService::patch_user", skip_all, fields(user.id = user_id))]
+    pub async fn patch_user(
+        &self,
+        user_id: u16,
+        patch: UserPatch,
+        precondition: &Precondition,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        let user = find_active(user_id as i32, connection).await?;
+        check_precondition(&user, precondition)?;
+
+        let mut document = serde_json::to_value(UserUpdate::from(&user))?;
+        match &patch {
+            UserPatch::Merge(merge) => merge_patch(&mut document, merge),
+            UserPatch::Json(operations) => json_patch(&mut document, operations)?,
         }
+        let update_user = UserUpdate::from_patched(document)?;
+        update_user.validate()?;
 
-        let mut active_model: users::ActiveModel = match active_model.unwrap() {
-            Some(model) => model.into(),
-            None => {
-                return Err(ErrorResponse {
-                    message: format!("User with ID {user_id} not found"),
-                    status_code: 404,
-                });
-            }
-        };
+        self.replace_user(user, update_user, connection).await
+    }
+
+    async fn replace_user(
+        &self,
+        user: UserModel,
+        update_user: UserUpdate,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        // The email may change, so the old one's cached response goes too
+        let email = user.email.clone();
+        let user = replace_fields(user, update_user, connection).await?;
 
         // Invalidate cache responses
-        REDIS_SERVICE.delete_pattern("users:*");
-        let redis_result = REDIS_SERVICE.delete(&format!("user:id:{user_id}"));
-        if let Err(e) = redis_result {
-            log::warn!("Failed to delete cached user with ID {user_id} -- Error: {e}");
+        invalidate_user_cache(user.id, &email).await;
+
+        Ok(user)
+    }
+
+    /// Soft delete: the row stays, with `deleted_at` set, and can be restored.
+    #[instrument(name = "UserService::delete_user", skip_all, fields(user.id = user_id))]
+    pub async fn delete_user(
+        &self,
+        user_id: u16,
+        precondition: &Precondition,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        let user = find_active(user_id as i32, connection).await?;
+        check_precondition(&user, precondition)?;
+
+        let user = soft_delete(user, connection).await?;
+
+        // Invalidate cache responses
+        invalidate_user_cache(user.id, &user.email).await;
+
+        Ok(user)
+    }
+
+    #[instrument(name = "UserService::restore_user", skip_all, fields(user.id = user_id))]
+    pub async fn restore_user(
+        &self,
+        user_id: u16,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        let user = UserEntity::find_by_id(user_id as i32)
+            .filter(users::Column::DeletedAt.is_not_null())
+            .one(connection)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("Deleted user with ID {user_id} not found"))
+            })?;
+
+        let version = user.version;
+        let mut active_model: users::ActiveModel = user.into();
+        active_model.deleted_at = Set(None);
+        let user = update_versioned(active_model, version, connection).await?;
+
+        invalidate_user_cache(user.id, &user.email).await;
+
+        Ok(user)
+    }
+
+    /// Removes the row for good, whether or not it was soft deleted first.
+    #[instrument(name = "UserService::purge_user", skip_all, fields(user.id = user_id))]
+    pub async fn purge_user(
+        &self,
+        user_id: u16,
+        precondition: &Precondition,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        let user = UserEntity::find_by_id(user_id as i32)
+            .one(connection)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("User with ID {user_id} not found")))?;
+        check_precondition(&user, precondition)?;
+
+        let result = UserEntity::delete_by_id(user.id)
+            .filter(users::Column::Version.eq(user.version))
+            .exec(connection)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(AppError::PreconditionFailed(
+                "User was modified concurrently".to_string(),
+            ));
         }
-        let redis_result =
-            REDIS_SERVICE.delete(&format!("user:email:{}", active_model.email.as_ref()));
-        if let Err(e) = redis_result {
-            log::warn!(
-                "Failed to delete cached user with email {} -- Error: {e}",
-                active_model.email.as_ref()
-            );
+
+        // Invalidate cache responses
+        invalidate_user_cache(user.id, &user.email).await;
+
+        Ok(user)
+    }
+
+    /// Replaces the password of a user without asking for the current one.
+    #[instrument(name = "UserService::set_password", skip_all, fields(user.id = user_id))]
+    pub async fn set_password(
+        &self,
+        user_id: u16,
+        password: String,
+        connection: &DatabaseConnection,
+    ) -> Result<(), AppError> {
+        let hash = hash_password(password).await?;
+        store_password_hash(user_id as i32, hash, connection).await
+    }
+
+    /// Gives a user another role, effective on their next request.
+    #[instrument(name = "UserService::set_role", skip_all, fields(user.id = user_id))]
+    pub async fn set_role(
+        &self,
+        user_id: u16,
+        role: Role,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        let user = find_active(user_id as i32, connection).await?;
+        if user.role == role {
+            return Ok(user);
         }
 
-        if let Some(name) = update_user.name {
-            active_model.name = Set(name);
+        let version = user.version;
+        let mut active_model: users::ActiveModel = user.into();
+        active_model.role = Set(role);
+        let user = update_versioned(active_model, version, connection).await?;
+
+        invalidate_user_cache(user.id, &user.email).await;
+
+        Ok(user)
+    }
+
+    /// Replaces the password of a user who proves they know the current one.
+    #[instrument(name = "UserService::change_password", skip_all, fields(user.id = user_id))]
+    pub async fn change_password(
+        &self,
+        user_id: i32,
+        current_password: String,
+        new_password: String,
+        connection: &DatabaseConnection,
+    ) -> Result<(), AppError> {
+        let user = find_active(user_id, connection).await?;
+        if !verify_password(current_password, user.password_hash).await? {
+            return Err(AppError::Forbidden(
+                "Current password is incorrect".to_string(),
+            ));
         }
-        if let Some(email) = update_user.email {
-            active_model.email = Set(email);
+        let hash = hash_password(new_password).await?;
+        store_password_hash(user.id, hash, connection).await
+    }
+
+    /// The active user with this email and password. Every failure answers
+    /// the same, in about the same time, so logins don't reveal which emails
+    /// exist. Hashes made with outdated parameters are upgraded on the way.
+    #[instrument(name = "UserService::authenticate", skip_all)]
+    pub async fn authenticate(
+        &self,
+        email: &str,
+        password: String,
+        connection: &DatabaseConnection,
+    ) -> Result<UserModel, AppError> {
+        let invalid = || AppError::Unauthorized("Invalid email or password".to_string());
+        // Hashing cost grows with the input, so oversized attempts stop here
+        if password.chars().count() > PASSWORD_POLICY.max_length {
+            return Err(invalid());
         }
 
-        active_model.age = Set(update_user.age);
-        active_model.is_active = Set(update_user.is_active);
+        let user = active_users()
+            .filter(users::Column::Email.eq(email))
+            .one(connection)
+            .await?;
+        let Some((user, hash)) =
+            user.and_then(|user| user.password_hash.clone().map(|hash| (user, hash)))
+        else {
+            verify_password(password, None).await?;
+            return Err(invalid());
+        };
+        if !verify_password(password.clone(), Some(hash.clone())).await?
+            || user.is_active == Some(false)
+        {
+            return Err(invalid());
+        }
 
-        let active_model = active_model.update(connection).await;
-        match active_model {
-            Ok(updated_user) => Ok(updated_user),
-            Err(e) => Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            }),
+        if PASSWORDS.needs_rehash(&hash) {
+            let rehashed = match hash_password(password).await {
+                Ok(rehashed) => store_password_hash(user.id, rehashed, connection).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = rehashed {
+                log::warn!(
+                    "Failed to rehash the password of user {} -- Error: {e}",
+                    user.id
+                );
+            }
         }
+        Ok(user)
     }
 
-    pub async fn delete_user(
+    /// Applies many writes in one call. The cache is invalidated once for the
+    /// whole batch, after the writes, instead of once per user.
+    #[instrument(name = "UserService::bulk_users", skip_all)]
+    pub async fn bulk_users(
         &self,
-        user_id: u16,
+        request: BulkRequest,
         connection: &DatabaseConnection,
-    ) -> Result<UserModel, ErrorResponse> {
-        let user = UserEntity::find_by_id(user_id).one(connection).await;
+    ) -> Result<BulkResponse, AppError> {
+        let mut results = Vec::with_capacity(request.operations.len());
+        let mut touched = vec![];
 
-        if let Err(e) = user {
-            return Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            });
+        let transaction = match request.mode {
+            BulkMode::Transactional => Some(connection.begin().await?),
+            BulkMode::BestEffort => None,
+        };
+        for (index, operation) in request.operations.into_iter().enumerate() {
+            let (op, id) = (operation.name(), operation.id());
+            let applied = match &transaction {
+                Some(transaction) => apply_operation(operation, transaction).await,
+                None => apply_operation(operation, connection).await,
+            };
+
+            match applied {
+                Ok((status, before, after)) => {
+                    touched.extend(before.map(|user| (user.id, user.email)));
+                    touched.push((after.id, after.email.clone()));
+                    results.push(BulkResult {
+                        index,
+                        op: op.to_string(),
+                        status: status.as_u16(),
+                        id: Some(after.id),
+                        user: (status != StatusCode::NO_CONTENT).then_some(after),
+                        error: None,
+                    });
+                }
+                // Dropping the transaction rolls back what the batch did so far
+                Err(e) if transaction.is_some() => {
+                    return Err(e.at(&format!("operations[{index}]")));
+                }
+                Err(e) => results.push(BulkResult {
+                    index,
+                    op: op.to_string(),
+                    status: e.status_code().as_u16(),
+                    id,
+                    user: None,
+                    error: Some(e.to_response()),
+                }),
+            }
+        }
+        if let Some(transaction) = transaction {
+            transaction.commit().await?;
         }
 
-        let user = user.unwrap();
-        if user.is_none() {
-            return Err(ErrorResponse {
-                message: format!("User with ID {user_id} not found"),
-                status_code: 404,
-            });
+        if !touched.is_empty() {
+            invalidate_users_cache(&touched).await;
         }
 
-        match user.clone().unwrap().delete(connection).await {
-            Err(e) => Err(ErrorResponse {
-                message: format!("Database error: {e}"),
-                status_code: 500,
-            }),
-            Ok(_) => {
-                // Invalidate cache responses
-                REDIS_SERVICE.delete_pattern("users:*");
-                let redis_result = REDIS_SERVICE.delete(&format!("user:id:{user_id}"));
-                if let Err(e) = redis_result {
-                    log::warn!("Failed to delete cached user with ID {user_id} -- Error: {e}");
+        let failed = results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .count();
+        Ok(BulkResponse {
+            mode: request.mode,
+            succeeded: results.len() - failed,
+            failed,
+            results,
+        })
+    }
+
+    /// Every user matching `filters`, in id order, one batch at a time so the
+    /// export never holds more than [`EXPORT_BATCH_SIZE`] rows. Sorting is not
+    /// supported: batches are walked by id. The cache is bypassed on purpose.
+    pub fn export_users(
+        &self,
+        connection: DatabaseConnection,
+        filters: UserFilters,
+    ) -> impl Stream<Item = Result<Vec<UserModel>, AppError>> + 'static {
+        // The batches run while the response streams, after the handler returned
+        let span = tracing::info_span!("UserService::export_users");
+        // `None` once the last batch has been sent
+        let start = Some((connection, filters, None::<i32>));
+        stream::unfold(start, move |state| {
+            async move {
+                let (connection, filters, after) = state?;
+                let mut query = filter_users(&filters)
+                    .order_by_asc(users::Column::Id)
+                    .limit(EXPORT_BATCH_SIZE);
+                if let Some(after) = after {
+                    query = query.filter(users::Column::Id.gt(after));
                 }
-                let redis_result =
-                    REDIS_SERVICE.delete(&format!("user:email:{}", user.clone().unwrap().email));
-                if let Err(e) = redis_result {
-                    log::warn!(
-                        "Failed to delete cached user with email {} -- Error: {e}",
-                        user.clone().unwrap().email
-                    );
+
+                match query.all(&connection).await {
+                    Ok(batch) if batch.is_empty() => None,
+                    Ok(batch) => {
+                        let last = batch.last().map(|user| user.id);
+                        let next = (batch.len() as u64 == EXPORT_BATCH_SIZE)
+                            .then_some((connection, filters, last));
+                        Some((Ok(batch), next))
+                    }
+                    Err(e) => Some((Err(e.into()), None)),
                 }
+            }
+            .instrument(span.clone())
+        })
+    }
 
-                Ok(user.unwrap())
+    /// Validates every row, including uniqueness of the emails within the
+    /// upload and against existing users, then inserts them in batches inside
+    /// one transaction. Nothing is inserted when any row is invalid or on a
+    /// dry run.
+    #[instrument(name = "UserService::import_users", skip_all)]
+    pub async fn import_users(
+        &self,
+        rows: Vec<ImportRow>,
+        mut errors: Vec<ImportError>,
+        dry_run: bool,
+        connection: &DatabaseConnection,
+    ) -> Result<ImportReport, AppError> {
+        let total = rows.len() + errors.len();
+        let mut valid = Vec::with_capacity(rows.len());
+        let mut seen = HashSet::new();
+        for row in rows {
+            if let Err(e) = row.user.validate() {
+                errors.extend(e.fields().iter().map(|field| ImportError {
+                    line: row.line,
+                    field: Some(field.field.clone()),
+                    message: field.message.clone(),
+                }));
+            } else if !seen.insert(row.user.email.clone()) {
+                errors.push(ImportError {
+                    line: row.line,
+                    field: Some("email".to_string()),
+                    message: "appears more than once in the upload".to_string(),
+                });
+            } else {
+                valid.push(row);
             }
         }
 
-        // Shorthand
-        // UserEntity::delete_by_id(user_id).exec(connection).await?
+        // Soft-deleted users keep their email, so they count as taken too
+        let mut taken = HashSet::new();
+        let emails: Vec<&String> = valid.iter().map(|row| &row.user.email).collect();
+        for emails in emails.chunks(IMPORT_BATCH_SIZE) {
+            let existing = UserEntity::find()
+                .filter(users::Column::Email.is_in(emails.iter().copied()))
+                .all(connection)
+                .await?;
+            taken.extend(existing.into_iter().map(|user| user.email));
+        }
+        valid.retain(|row| {
+            let available = !taken.contains(&row.user.email);
+            if !available {
+                errors.push(ImportError {
+                    line: row.line,
+                    field: Some("email".to_string()),
+                    message: "A record with this email already exists".to_string(),
+                });
+            }
+            available
+        });
+        errors.sort_by_key(|error| error.line);
+
+        let mut report = ImportReport {
+            dry_run,
+            total,
+            valid: valid.len(),
+            imported: 0,
+            errors,
+        };
+        if dry_run || !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        let transaction = connection.begin().await?;
+        for batch in valid.chunks(IMPORT_BATCH_SIZE) {
+            let models = batch.iter().map(|row| users::ActiveModel {
+                email: Set(row.user.email.clone()),
+                name: Set(row.user.name.clone()),
+                age: Set(row.user.age),
+                is_active: Set(Some(true)),
+                ..Default::default()
+            });
+            UserEntity::insert_many(models)
+                .exec_without_returning(&transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        report.imported = valid.len();
+
+        // Invalidate cached response of all users
+        REDIS_SERVICE.delete_pattern("users:*").await;
+
+        Ok(report)
     }
 }
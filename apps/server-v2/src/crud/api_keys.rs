@@ -0,0 +1,167 @@
+use chrono::Utc;
+use sea_orm::ActiveValue::{NotSet, Set};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    sea_query::Expr,
+};
+
+use crate::core::api_keys::ApiKey;
+use crate::core::errors::AppError;
+use crate::models::api_keys::{self, Model as ApiKeyModel};
+use crate::models::prelude::ApiKeys as ApiKeyEntity;
+use crate::schemas::api_keys::ApiKeyCreate;
+
+pub struct ApiKeyService;
+
+fn not_found(key_id: i32) -> AppError {
+    AppError::NotFound(format!("API key with ID {key_id} not found"))
+}
+
+/// Unknown, malformed, revoked and wrong keys are refused alike.
+pub fn invalid_api_key() -> AppError {
+    AppError::Unauthorized("Invalid API key".to_string())
+}
+
+impl ApiKeyService {
+    /// Returns the stored key and its plaintext, which is not kept anywhere.
+    pub async fn create_api_key(
+        &self,
+        request: ApiKeyCreate,
+        connection: &DatabaseConnection,
+    ) -> Result<(ApiKeyModel, ApiKey), AppError> {
+        let key = ApiKey::generate();
+        let salt = ApiKey::new_salt();
+        let mut scopes: Vec<String> = request
+            .scopes
+            .iter()
+            .map(|scope| scope.as_str().to_string())
+            .collect();
+        scopes.sort();
+        scopes.dedup();
+
+        let model = api_keys::ActiveModel {
+            id: NotSet,
+            name: Set(request.name.trim().to_string()),
+            prefix: Set(key.prefix.clone()),
+            key_hash: Set(key.hash(&salt)),
+            key_salt: Set(salt),
+            scopes: Set(scopes),
+            usage_count: NotSet,
+            last_used_at: NotSet,
+            rotated_at: NotSet,
+            revoked_at: NotSet,
+            created_at: NotSet,
+            updated_at: NotSet,
+        }
+        .insert(connection)
+        .await?;
+        Ok((model, key))
+    }
+
+    /// Revoked keys included, most recent first.
+    pub async fn get_api_keys(
+        &self,
+        connection: &DatabaseConnection,
+    ) -> Result<Vec<ApiKeyModel>, AppError> {
+        Ok(ApiKeyEntity::find()
+            .order_by_desc(api_keys::Column::Id)
+            .all(connection)
+            .await?)
+    }
+
+    /// Replaces the secret and the prefix; the previous key stops working at once.
+    pub async fn rotate_api_key(
+        &self,
+        key_id: i32,
+        connection: &DatabaseConnection,
+    ) -> Result<(ApiKeyModel, ApiKey), AppError> {
+        let current = ApiKeyEntity::find_by_id(key_id)
+            .one(connection)
+            .await?
+            .ok_or_else(|| not_found(key_id))?;
+        if current.revoked_at.is_some() {
+            return Err(AppError::Conflict(format!(
+                "API key with ID {key_id} is revoked"
+            )));
+        }
+
+        let key = ApiKey::generate();
+        let salt = ApiKey::new_salt();
+        let mut model: api_keys::ActiveModel = current.into();
+        model.prefix = Set(key.prefix.clone());
+        model.key_hash = Set(key.hash(&salt));
+        model.key_salt = Set(salt);
+        model.rotated_at = Set(Some(Utc::now().fixed_offset()));
+        Ok((model.update(connection).await?, key))
+    }
+
+    /// Revoking a revoked key changes nothing.
+    pub async fn revoke_api_key(
+        &self,
+        key_id: i32,
+        connection: &DatabaseConnection,
+    ) -> Result<ApiKeyModel, AppError> {
+        let current = ApiKeyEntity::find_by_id(key_id)
+            .one(connection)
+            .await?
+            .ok_or_else(|| not_found(key_id))?;
+        if current.revoked_at.is_some() {
+            return Ok(current);
+        }
+
+        let mut model: api_keys::ActiveModel = current.into();
+        model.revoked_at = Set(Some(Utc::now().fixed_offset()));
+        Ok(model.update(connection).await?)
+    }
+
+    /// The live key matching `presented`. Unknown, malformed, revoked and
+    /// wrong keys are refused alike.
+    pub async fn verify(
+        &self,
+        presented: &str,
+        connection: &DatabaseConnection,
+    ) -> Result<ApiKeyModel, AppError> {
+        let key = ApiKey::parse(presented).ok_or_else(invalid_api_key)?;
+        ApiKeyEntity::find()
+            .filter(api_keys::Column::Prefix.eq(key.prefix.as_str()))
+            .filter(api_keys::Column::RevokedAt.is_null())
+            .one(connection)
+            .await?
+            .filter(|model| key.verify(&model.key_salt, &model.key_hash))
+            .ok_or_else(invalid_api_key)
+    }
+
+    /// Same as [`verify`](Self::verify), with the use of the key recorded.
+    pub async fn authenticate(
+        &self,
+        presented: &str,
+        connection: &DatabaseConnection,
+    ) -> Result<ApiKeyModel, AppError> {
+        let model = self.verify(presented, connection).await?;
+        self.record_usage(&model, connection).await;
+        Ok(model)
+    }
+
+    /// Counts a use of a verified key. Usage is bookkeeping: failing to record
+    /// it doesn't fail the request.
+    pub async fn record_usage(&self, model: &ApiKeyModel, connection: &DatabaseConnection) {
+        let recorded = ApiKeyEntity::update_many()
+            .col_expr(
+                api_keys::Column::UsageCount,
+                Expr::col(api_keys::Column::UsageCount).add(1),
+            )
+            .col_expr(
+                api_keys::Column::LastUsedAt,
+                Expr::current_timestamp().into(),
+            )
+            .filter(api_keys::Column::Id.eq(model.id))
+            .exec(connection)
+            .await;
+        if let Err(e) = recorded {
+            log::warn!(
+                "Failed to record the use of API key {} -- Error: {e}",
+                model.id
+            );
+        }
+    }
+}
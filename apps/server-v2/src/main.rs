@@ -1,28 +1,62 @@
 use actix_cors::Cors;
+use actix_web::http::header;
 use actix_web::{App, HttpServer, web};
 use v2::api::main::handler;
 
 use actix_web::middleware::{Logger, from_fn};
 use env_logger::Env;
 use v2::api::middlewares::logs::dispatch_logs;
+use v2::api::middlewares::rate_limit::{
+    RATELIMIT_LIMIT, RATELIMIT_POLICY, RATELIMIT_REMAINING, RATELIMIT_RESET, RateLimiter,
+    rate_limit,
+};
+use v2::api::middlewares::request_id::{REQUEST_ID_HEADER, format_log, request_id};
+use v2::api::middlewares::shipper::LOG_SHIPPER;
+use v2::api::middlewares::trace::{TRACEPARENT, trace_requests};
+use v2::core::config::{SETTINGS, Settings};
+use v2::core::database::{DatabaseParams, DatabaseService};
+use v2::core::health::HealthService;
+use v2::core::telemetry;
 
 use migration::{Migrator, MigratorTrait};
 
 #[actix_web::main]
 async fn main() -> Result<(), std::io::Error> {
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
+    env_logger::Builder::from_env(Env::default().default_filter_or("info"))
+        .format(format_log)
+        .init();
+
+    // Report every configuration problem at once instead of failing on first use
+    match Settings::load_settings() {
+        Ok(settings) => settings.install(),
+        Err(report) => {
+            log::error!("{report}");
+            return Err(std::io::Error::other("Invalid configuration"));
+        }
+    }
+
+    // Spans are only recorded when there is a collector to send them to
+    let tracer_provider = telemetry::init(&SETTINGS);
 
     let prefix = "/api/v2";
-    let db = v2::core::database::DatabaseService::init(None).await;
+    let db = DatabaseService::init(None).await;
     let app_data = web::Data::new(db.clone());
 
+    // QuestDB is only needed for logs, so its pool must not block startup
+    let questdb = DatabaseService::init_lazy(Some(DatabaseParams::questdb())).await;
+    let health_data = web::Data::new(HealthService::new(
+        db.connection.clone(),
+        questdb.connection,
+    ));
+
+    // Left out of the app data when disabled, which lets every request through
+    let rate_limiter = web::Data::new(RateLimiter::from_settings(&SETTINGS, prefix));
+
     match Migrator::up(&db.connection, None).await {
         Ok(_) => log::info!("Database migration completed successfully."),
         Err(e) => {
             log::error!("Database migration failed: {e}");
-            return Err(std::io::Error::other(
-                "Migration failed",
-            ));
+            return Err(std::io::Error::other("Migration failed"));
         }
     };
 
@@ -31,17 +65,52 @@ async fn main() -> Result<(), std::io::Error> {
             .allow_any_header()
             .allow_any_method()
             .allow_any_origin() // Just for development
+            .expose_headers([
+                header::ETAG,
+                header::LAST_MODIFIED,
+                header::LINK,
+                header::RETRY_AFTER,
+                REQUEST_ID_HEADER,
+                TRACEPARENT,
+                RATELIMIT_LIMIT,
+                RATELIMIT_REMAINING,
+                RATELIMIT_RESET,
+                RATELIMIT_POLICY,
+            ])
             .supports_credentials();
-        App::new()
+        let mut app = App::new()
             .app_data(app_data.clone())
-            .service(handler(prefix))
+            .app_data(health_data.clone());
+        if SETTINGS.rate_limit_enabled {
+            app = app.app_data(rate_limiter.clone());
+        }
+        app.service(handler(prefix))
+            // Inside CORS, so preflights aren't counted and 429s can be read
+            .wrap(from_fn(rate_limit))
             .wrap(cors)
-            // Documentation: https://actix.rs/docs/middleware
-            // Logger::new("  %a %t "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T")
-            .wrap(Logger::default())
             .wrap(from_fn(dispatch_logs))
+            // Outside dispatch_logs, so its time is part of the request's span
+            .wrap(from_fn(trace_requests))
+            // Outside the others, so every record they and the handlers log carries the id
+            .wrap(from_fn(request_id))
+            // Documentation: https://actix.rs/docs/middleware
+            // Outside request_id too: the access log is written once the body is
+            // sent, when the id is no longer in scope, so it reads the response header
+            .wrap(Logger::new(
+                r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{x-request-id}o"#,
+            ))
     })
-    .bind(("0.0.0.0", 8000))?
+    .bind((SETTINGS.server_host.as_str(), SETTINGS.server_port))?
     .run()
-    .await
+    .await?;
+
+    // Ship the request logs and spans still buffered before exiting
+    LOG_SHIPPER.shutdown();
+    if let Some(provider) = tracer_provider
+        && let Err(e) = provider.shutdown()
+    {
+        log::warn!("Failed to export the last spans -- Error: {e}");
+    }
+
+    Ok(())
 }
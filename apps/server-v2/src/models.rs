@@ -1,4 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+pub mod api_keys;
 pub mod prelude;
+pub mod timestamps;
 pub mod users;
@@ -0,0 +1,47 @@
+use schemars::JsonSchema;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::timestamps;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, JsonSchema)]
+#[sea_orm(table_name = "api_keys")]
+#[schemars(rename = "ApiKey")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub name: String,
+    /// Public part of the key (`umk_<prefix>_<secret>`), shown to tell keys apart
+    #[sea_orm(unique)]
+    pub prefix: String,
+    /// The secret itself is never stored, only this salt and the salted hash
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub key_salt: String,
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub key_hash: String,
+    /// e.g. `users:read`, `users:write`
+    pub scopes: Vec<String>,
+    /// Requests authenticated with the key
+    pub usage_count: i64,
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+    pub rotated_at: Option<DateTimeWithTimeZone>,
+    /// Set when the key is revoked, it is refused from then on
+    pub revoked_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(timestamps::touch(self, insert))
+    }
+}
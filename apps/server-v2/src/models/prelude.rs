@@ -1,3 +1,4 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+pub use super::api_keys::Entity as ApiKeys;
 pub use super::users::Entity as Users;
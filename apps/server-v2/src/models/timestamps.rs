@@ -0,0 +1,28 @@
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, EntityTrait, IdenStatic, Iterable};
+
+pub const CREATED_AT: &str = "created_at";
+pub const UPDATED_AT: &str = "updated_at";
+
+/// Fills `created_at` on insert and `updated_at` on every save, for any entity
+/// that has those columns. Call it from `ActiveModelBehavior::before_save`:
+///
+/// ```ignore
+/// async fn before_save<C: ConnectionTrait>(self, _: &C, insert: bool) -> Result<Self, DbErr> {
+///     Ok(timestamps::touch(self, insert))
+/// }
+/// ```
+///
+/// The database keeps the same columns up to date with defaults and a trigger,
+/// so rows written outside the application are covered too.
+pub fn touch<A: ActiveModelTrait>(mut model: A, insert: bool) -> A {
+    let now = Utc::now().fixed_offset();
+    for column in <A::Entity as EntityTrait>::Column::iter() {
+        match column.as_str() {
+            CREATED_AT if insert && model.is_not_set(column) => model.set(column, now.into()),
+            UPDATED_AT => model.set(column, now.into()),
+            _ => {}
+        }
+    }
+    model
+}
@@ -1,10 +1,14 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+use schemars::JsonSchema;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+use super::timestamps;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize, JsonSchema)]
 #[sea_orm(table_name = "users")]
+#[schemars(rename = "User")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
@@ -13,11 +17,112 @@ pub struct Model {
     pub name: String,
     pub age: Option<i32>,
     pub is_active: Option<bool>,
-    pub created_at: Option<DateTimeWithTimeZone>,
-    pub updated_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+    pub updated_at: DateTimeWithTimeZone,
+    /// Set when the user is soft deleted, cleared on restore
+    pub deleted_at: Option<DateTimeWithTimeZone>,
+    /// Bumped on every update, backs the `ETag` / `If-Match` checks
+    pub version: i32,
+    /// argon2id PHC string. Never serialized, so it stays out of responses
+    /// and of the Redis cache; read it from the database when needed.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub password_hash: Option<String>,
+    /// What the user may do with tokens issued to them
+    pub role: Role,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        Ok(timestamps::touch(self, insert))
+    }
+}
+
+/// See [`Permission`](crate::core::permissions::Permission) for what each
+/// role allows. Stored as text; the variants are mapped by hand because
+/// `DeriveActiveEnum` can't name one after `self`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Operator,
+    Viewer,
+    /// Only their own record
+    #[serde(rename = "self")]
+    SelfOnly,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Operator => "operator",
+            Role::Viewer => "viewer",
+            Role::SelfOnly => "self",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "admin" => Ok(Role::Admin),
+            "operator" => Ok(Role::Operator),
+            "viewer" => Ok(Role::Viewer),
+            "self" => Ok(Role::SelfOnly),
+            other => Err(format!("unknown role \"{other}\"")),
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<Role> for Value {
+    fn from(role: Role) -> Self {
+        role.as_str().into()
+    }
+}
+
+impl sea_orm::TryGetable for Role {
+    fn try_get_by<I: sea_orm::ColIdx>(
+        res: &QueryResult,
+        index: I,
+    ) -> Result<Self, sea_orm::TryGetError> {
+        String::try_get_by(res, index)?
+            .parse()
+            .map_err(|e: String| sea_orm::TryGetError::DbErr(DbErr::Type(e)))
+    }
+}
+
+impl sea_orm::sea_query::ValueType for Role {
+    fn try_from(value: Value) -> Result<Self, sea_orm::sea_query::ValueTypeErr> {
+        <String as sea_orm::sea_query::ValueType>::try_from(value)?
+            .parse()
+            .map_err(|_| sea_orm::sea_query::ValueTypeErr)
+    }
+
+    fn type_name() -> String {
+        "Role".to_string()
+    }
+
+    fn array_type() -> sea_orm::sea_query::ArrayType {
+        sea_orm::sea_query::ArrayType::String
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::Text
+    }
+}
@@ -1,2 +1,4 @@
 pub mod api;
+pub mod api_keys;
+pub mod auth;
 pub mod users;
@@ -1,19 +1,163 @@
 use actix_web::http::StatusCode;
-use serde::Serialize;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize)]
+use crate::core::csv::{CSV_CONTENT_TYPE, NDJSON_CONTENT_TYPE};
+use crate::core::errors::AppError;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct ErrorResponse {
     pub message: String,
     pub status_code: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The fields the error is about, e.g. the column of a violated unique constraint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
 }
 
 impl ErrorResponse {
     pub fn get_status_code(&self) -> StatusCode {
-        match self.status_code {
-            400 => StatusCode::BAD_REQUEST,
-            404 => StatusCode::NOT_FOUND,
-            500 => StatusCode::INTERNAL_SERVER_ERROR,
-            _ => StatusCode::INTERNAL_SERVER_ERROR, // Default to 500 for any other status code
+        // Default to 500 for anything that isn't a valid error status code
+        match StatusCode::from_u16(self.status_code) {
+            Ok(status) if status.is_client_error() || status.is_server_error() => status,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
+
+/// Envelope returned by list endpoints.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(rename = "Paginated{T}")]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page: u64,
+    pub limit: u64,
+    pub pages: u64,
+    /// Filled in by the handler, which knows the request URL
+    #[serde(default)]
+    pub links: PageLinks,
+}
+
+impl<T> Paginated<T> {
+    pub fn new(items: Vec<T>, total: u64, page: u64, limit: u64) -> Self {
+        Paginated {
+            items,
+            total,
+            page,
+            limit,
+            pages: total.div_ceil(limit.max(1)),
+            links: PageLinks::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageLinks {
+    pub first: Option<String>,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    pub last: Option<String>,
+}
+
+/// Envelope returned by list endpoints in keyset mode. There is no total:
+/// counting would cost what keyset pagination is meant to save.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(rename = "CursorPage{T}")]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub limit: u64,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    #[serde(default)]
+    pub links: PageLinks,
+}
+
+/// Opaque position in a listing. Clients only pass back what they received,
+/// so the encoding can change (e.g. to `(created_at, id)`) without breaking them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("id:{}", self.id))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, AppError> {
+        let invalid = || AppError::BadRequest(format!("Invalid cursor: {cursor}"));
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let id = decoded
+            .strip_prefix("id:")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(invalid)?;
+
+        Ok(Cursor { id })
+    }
+}
+
+/// Where a keyset page starts: right after or right before a known row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorDirection {
+    After(Option<Cursor>),
+    Before(Cursor),
+}
+
+/// What a write may overwrite, from the request's `If-Match` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Precondition {
+    /// `If-Match: *`, or no header where it isn't required
+    Any,
+    /// Versions named by the client's entity tags
+    Versions(Vec<i32>),
+}
+
+impl Precondition {
+    pub fn allows(&self, version: i32) -> bool {
+        match self {
+            Precondition::Any => true,
+            Precondition::Versions(versions) => versions.contains(&version),
+        }
+    }
+}
+
+/// Line oriented formats users can be exported to and imported from.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DataFormat {
+    Csv,
+    Ndjson,
+}
+
+impl DataFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DataFormat::Csv => CSV_CONTENT_TYPE,
+            DataFormat::Ndjson => NDJSON_CONTENT_TYPE,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            DataFormat::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        [DataFormat::Csv, DataFormat::Ndjson]
+            .into_iter()
+            .find(|format| format.content_type() == content_type)
+    }
+}
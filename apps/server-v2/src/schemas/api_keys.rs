@@ -0,0 +1,89 @@
+use std::fmt;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::errors::AppError;
+use crate::core::validation::{self, Validate, Validator};
+use crate::models::api_keys::Model as ApiKeyModel;
+
+pub const NAME_LENGTH: std::ops::RangeInclusive<usize> = 1..=100;
+
+/// What an API key may do.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::UsersRead => "users:read",
+            Scope::UsersWrite => "users:write",
+        }
+    }
+
+    pub fn granted_to(&self, api_key: &ApiKeyModel) -> bool {
+        api_key.scopes.iter().any(|scope| scope == self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "users:read" => Ok(Scope::UsersRead),
+            "users:write" => Ok(Scope::UsersWrite),
+            other => Err(format!("unknown scope \"{other}\"")),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ApiKeyCreate {
+    /// What the key is for, e.g. the service using it
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl Validate for ApiKeyCreate {
+    fn validate(&self) -> Result<(), AppError> {
+        Validator::new()
+            .field(
+                "name",
+                self.name.as_str(),
+                &[&validation::length(NAME_LENGTH)],
+            )
+            .field(
+                "scopes",
+                self.scopes.as_slice(),
+                &[&|scopes: &[Scope]| {
+                    scopes
+                        .is_empty()
+                        .then(|| "must grant at least one scope".to_string())
+                }],
+            )
+            .finish()
+    }
+}
+
+/// A key with its plaintext, only ever returned by create and rotate.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ApiKeyCreated {
+    #[serde(flatten)]
+    pub api_key: ApiKeyModel,
+    /// Send it as `Authorization: ApiKey <key>` or `X-API-Key: <key>`. It
+    /// can't be shown again.
+    pub key: String,
+}
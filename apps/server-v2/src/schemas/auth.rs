@@ -0,0 +1,89 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::core::errors::AppError;
+use crate::core::password::PASSWORD_POLICY;
+use crate::core::validation::{self, Validate, Validator};
+use crate::models::users::Role;
+
+pub const SUBJECT_LENGTH: std::ops::RangeInclusive<usize> = 1..=255;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct TokenRequest {
+    /// Becomes the `sub` claim of the token
+    pub subject: String,
+}
+
+impl Validate for TokenRequest {
+    fn validate(&self) -> Result<(), AppError> {
+        Validator::new()
+            .field(
+                "subject",
+                self.subject.as_str(),
+                &[&validation::length(SUBJECT_LENGTH)],
+            )
+            .finish()
+    }
+}
+
+/// RFC 6749, section 5.1
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct TokenResponse {
+    pub access_token: String,
+    /// Always `Bearer`
+    pub token_type: String,
+    /// Seconds until the token expires
+    pub expires_in: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+/// A password set by an admin, no current password needed.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct PasswordSet {
+    pub password: String,
+}
+
+impl Validate for PasswordSet {
+    fn validate(&self) -> Result<(), AppError> {
+        Validator::new()
+            .field("password", self.password.as_str(), &[&password_policy])
+            .finish()
+    }
+}
+
+/// A role given by an admin.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct RoleSet {
+    pub role: Role,
+}
+
+/// A user changing their own password.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+impl Validate for PasswordChange {
+    fn validate(&self) -> Result<(), AppError> {
+        Validator::new()
+            .field(
+                "new_password",
+                self.new_password.as_str(),
+                &[&password_policy, &|new: &str| {
+                    (new == self.current_password)
+                        .then(|| "must differ from the current password".to_string())
+                }],
+            )
+            .finish()
+    }
+}
+
+fn password_policy(password: &str) -> Option<String> {
+    PASSWORD_POLICY.check(password)
+}
@@ -1,16 +1,514 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::core::csv::{self, CsvRecord};
+use crate::core::errors::AppError;
+use crate::core::patch::PatchOperation;
+use crate::core::validation::{self, Validate, Validator};
+use crate::models::users::Model as UserModel;
+use crate::schemas::api::{DataFormat, ErrorResponse};
+
+pub const NAME_LENGTH: std::ops::RangeInclusive<usize> = 1..=100;
+pub const AGE_RANGE: std::ops::RangeInclusive<i32> = 0..=150;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct UserCreate {
     pub email: String,
     pub name: String,
     pub age: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Full replacement of the editable fields, as sent with PUT. Every field is
+/// required; the nullable ones must be present, even if only as `null`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct UserUpdate {
-    pub email: Option<String>,
-    pub name: Option<String>,
+    pub email: String,
+    pub name: String,
+    #[serde(deserialize_with = "Option::deserialize")]
+    #[schemars(required)]
     pub age: Option<i32>,
+    #[serde(deserialize_with = "Option::deserialize")]
+    #[schemars(required)]
+    pub is_active: Option<bool>,
+}
+
+impl UserUpdate {
+    /// Members a patch may touch
+    pub const FIELDS: [&str; 4] = ["email", "name", "age", "is_active"];
+    /// Members that read as `null` once a patch removes them
+    pub const NULLABLE: [&str; 2] = ["age", "is_active"];
+
+    /// Reads back a patched document. Unknown members and missing required
+    /// ones are rejected, so a patch can't touch `id` or drop `name`.
+    pub fn from_patched(mut document: Value) -> Result<Self, AppError> {
+        let Value::Object(members) = &mut document else {
+            return Err(AppError::Validation(
+                "A patched user must be a JSON object".to_string(),
+            ));
+        };
+        if let Some(unknown) = members
+            .keys()
+            .find(|key| !Self::FIELDS.contains(&key.as_str()))
+        {
+            return Err(AppError::Validation(format!(
+                "Field `{unknown}` cannot be patched"
+            )));
+        }
+        for field in Self::NULLABLE {
+            members.entry(field).or_insert(Value::Null);
+        }
+
+        serde_json::from_value(document)
+            .map_err(|e| AppError::Validation(format!("Invalid patched user: {e}")))
+    }
+}
+
+impl From<&UserModel> for UserUpdate {
+    fn from(user: &UserModel) -> Self {
+        UserUpdate {
+            email: user.email.clone(),
+            name: user.name.clone(),
+            age: user.age,
+            is_active: user.is_active,
+        }
+    }
+}
+
+/// Body of a PATCH request, by content type.
+#[derive(Debug, Clone)]
+pub enum UserPatch {
+    /// RFC 7396 `application/merge-patch+json`
+    Merge(Value),
+    /// RFC 6902 `application/json-patch+json`
+    Json(Vec<PatchOperation>),
+}
+
+impl Validate for UserCreate {
+    fn validate(&self) -> Result<(), AppError> {
+        Validator::new()
+            .field("email", self.email.as_str(), &[&validation::email])
+            .field(
+                "name",
+                self.name.as_str(),
+                &[&validation::length(NAME_LENGTH), &validation::name_charset],
+            )
+            .optional("age", self.age.as_ref(), &[&validation::range(AGE_RANGE)])
+            .finish()
+    }
+}
+
+impl Validate for UserUpdate {
+    fn validate(&self) -> Result<(), AppError> {
+        Validator::new()
+            .field("email", self.email.as_str(), &[&validation::email])
+            .field(
+                "name",
+                self.name.as_str(),
+                &[&validation::length(NAME_LENGTH), &validation::name_charset],
+            )
+            .optional("age", self.age.as_ref(), &[&validation::range(AGE_RANGE)])
+            .finish()
+    }
+}
+
+/// Columns clients may sort on. Anything else is rejected instead of being
+/// passed through to SQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserSortField {
+    Name,
+    Email,
+    Age,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl UserSortField {
+    pub const ALL: [UserSortField; 5] = [
+        UserSortField::Name,
+        UserSortField::Email,
+        UserSortField::Age,
+        UserSortField::CreatedAt,
+        UserSortField::UpdatedAt,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserSortField::Name => "name",
+            UserSortField::Email => "email",
+            UserSortField::Age => "age",
+            UserSortField::CreatedAt => "created_at",
+            UserSortField::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+/// One `sort=` entry: `name` sorts ascending, `-name` descending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserSort {
+    pub field: UserSortField,
+    pub descending: bool,
+}
+
+impl UserSort {
+    /// Parses a comma separated list such as `-age,name`.
+    pub fn parse_list(value: &str) -> Result<Vec<UserSort>, AppError> {
+        let mut sort: Vec<UserSort> = Vec::new();
+        for entry in value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let entry: UserSort = entry.parse()?;
+            if sort.iter().any(|existing| existing.field == entry.field) {
+                return Err(AppError::BadRequest(format!(
+                    "Sort field `{}` is given more than once",
+                    entry.field.as_str()
+                )));
+            }
+            sort.push(entry);
+        }
+        Ok(sort)
+    }
+}
+
+impl FromStr for UserSort {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, descending) = match value.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (value.strip_prefix('+').unwrap_or(value), false),
+        };
+        let field = UserSortField::ALL
+            .into_iter()
+            .find(|field| field.as_str() == name)
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = UserSortField::ALL.iter().map(|f| f.as_str()).collect();
+                AppError::BadRequest(format!(
+                    "Cannot sort by `{name}`, expected one of: {}",
+                    allowed.join(", ")
+                ))
+            })?;
+
+        Ok(UserSort { field, descending })
+    }
+}
+
+impl fmt::Display for UserSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = if self.descending { "-" } else { "" };
+        write!(f, "{direction}{}", self.field.as_str())
+    }
+}
+
+/// Everything that narrows down or orders a user listing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserFilters {
+    pub search: Option<String>,
     pub is_active: Option<bool>,
+    pub age_min: Option<i32>,
+    pub age_max: Option<i32>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+    pub sort: Vec<UserSort>,
+}
+
+impl UserFilters {
+    pub fn check(&self) -> Result<(), AppError> {
+        let inverted = |name: &str| {
+            Err(AppError::BadRequest(format!(
+                "`{name}` range is empty: its lower bound is above its upper bound"
+            )))
+        };
+        if let (Some(min), Some(max)) = (self.age_min, self.age_max)
+            && min > max
+        {
+            return inverted("age");
+        }
+        if let (Some(after), Some(before)) = (self.created_after, self.created_before)
+            && after > before
+        {
+            return inverted("created");
+        }
+        if let (Some(after), Some(before)) = (self.updated_after, self.updated_before)
+            && after > before
+        {
+            return inverted("updated");
+        }
+        Ok(())
+    }
+
+    /// Canonical form of the filters for cache keys: every field in a fixed
+    /// order, so equal listings share an entry and different ones never do.
+    /// The free-text search goes last, where it cannot be mistaken for a field.
+    pub fn cache_key(&self) -> String {
+        fn part<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map_or("none".to_string(), T::to_string)
+        }
+        fn date(value: &Option<DateTime<Utc>>) -> String {
+            value.map_or("none".to_string(), |value| {
+                value.timestamp_micros().to_string()
+            })
+        }
+        let sort: Vec<String> = self.sort.iter().map(UserSort::to_string).collect();
+
+        format!(
+            "active:{}:age:{}-{}:created:{}-{}:updated:{}-{}:sort:{}:search:{}",
+            part(&self.is_active),
+            part(&self.age_min),
+            part(&self.age_max),
+            date(&self.created_after),
+            date(&self.created_before),
+            date(&self.updated_after),
+            date(&self.updated_before),
+            if sort.is_empty() {
+                "none".to_string()
+            } else {
+                sort.join(",")
+            },
+            part(&self.search),
+        )
+    }
+}
+
+/// How `POST /users/bulk` handles a failing operation.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// All operations run in one transaction, the first failure rolls back
+    /// everything and becomes the response
+    #[default]
+    Transactional,
+    /// Every operation is applied on its own and reports its own result
+    BestEffort,
+}
+
+/// One entry of a bulk request. `version` plays the role of `If-Match`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BulkOperation {
+    Create {
+        user: UserCreate,
+    },
+    Update {
+        id: i32,
+        version: Option<i32>,
+        user: UserUpdate,
+    },
+    Delete {
+        id: i32,
+        version: Option<i32>,
+    },
+}
+
+impl BulkOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BulkOperation::Create { .. } => "create",
+            BulkOperation::Update { .. } => "update",
+            BulkOperation::Delete { .. } => "delete",
+        }
+    }
+
+    /// The user targeted, unknown until a create succeeds
+    pub fn id(&self) -> Option<i32> {
+        match self {
+            BulkOperation::Create { .. } => None,
+            BulkOperation::Update { id, .. } | BulkOperation::Delete { id, .. } => Some(*id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BulkRequest {
+    #[serde(default)]
+    pub mode: BulkMode,
+    pub operations: Vec<BulkOperation>,
+}
+
+/// Outcome of one operation, at the same index as in the request.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BulkResult {
+    pub index: usize,
+    pub op: String,
+    /// Status the operation would have had as a single request
+    pub status: u16,
+    pub id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<UserModel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BulkResponse {
+    pub mode: BulkMode,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkResult>,
+}
+
+/// Columns of a CSV export, in order.
+pub const EXPORT_COLUMNS: [&str; 8] = [
+    "id",
+    "email",
+    "name",
+    "age",
+    "is_active",
+    "created_at",
+    "updated_at",
+    "version",
+];
+
+/// One CSV export record. Missing values are empty cells.
+pub fn export_record(user: &UserModel) -> Vec<String> {
+    fn cell<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map_or_else(String::new, T::to_string)
+    }
+    vec![
+        user.id.to_string(),
+        user.email.clone(),
+        user.name.clone(),
+        cell(&user.age),
+        cell(&user.is_active),
+        user.created_at.to_rfc3339(),
+        user.updated_at.to_rfc3339(),
+        user.version.to_string(),
+    ]
+}
+
+/// A problem with one line of an import. `field` is unset when the whole
+/// line is unreadable.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct ImportError {
+    pub line: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ImportError {
+    pub fn line(line: usize, message: impl Into<String>) -> Self {
+        ImportError {
+            line,
+            field: None,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Users read from the upload
+    pub total: usize,
+    /// Users that passed validation
+    pub valid: usize,
+    pub imported: usize,
+    pub errors: Vec<ImportError>,
+}
+
+/// A user read from an import, with the line it came from.
+#[derive(Debug, Clone)]
+pub struct ImportRow {
+    pub line: usize,
+    pub user: UserCreate,
+}
+
+/// Reads the users of an upload. Lines that cannot be read are reported and
+/// skipped, so one pass gives the complete error report.
+pub fn read_import(format: DataFormat, body: &str) -> (Vec<ImportRow>, Vec<ImportError>) {
+    match format {
+        DataFormat::Csv => read_csv(body),
+        DataFormat::Ndjson => read_ndjson(body),
+    }
+}
+
+fn read_ndjson(body: &str) -> (Vec<ImportRow>, Vec<ImportError>) {
+    let mut rows = vec![];
+    let mut errors = vec![];
+    for (index, content) in body.lines().enumerate() {
+        if content.trim().is_empty() {
+            continue;
+        }
+        // Unknown members are ignored, so an export can be imported as is
+        match serde_json::from_str::<UserCreate>(content) {
+            Ok(user) => rows.push(ImportRow {
+                line: index + 1,
+                user,
+            }),
+            Err(e) => errors.push(ImportError::line(index + 1, e.to_string())),
+        }
+    }
+    (rows, errors)
+}
+
+/// The header names the columns; `email` and `name` are required, `age` is
+/// optional and any other column (such as those of an export) is ignored.
+fn read_csv(body: &str) -> (Vec<ImportRow>, Vec<ImportError>) {
+    let records = match csv::parse(body) {
+        Ok(records) => records,
+        Err(e) => return (vec![], vec![ImportError::line(e.line, e.message)]),
+    };
+    let Some((header, records)) = records.split_first() else {
+        return (vec![], vec![ImportError::line(1, "missing header")]);
+    };
+
+    let column = |name: &str| {
+        header
+            .fields
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case(name))
+    };
+    let (Some(email), Some(name)) = (column("email"), column("name")) else {
+        let message = "header must name the `email` and `name` columns";
+        return (vec![], vec![ImportError::line(header.line, message)]);
+    };
+    let age = column("age");
+
+    let mut rows = vec![];
+    let mut errors = vec![];
+    for CsvRecord { line, fields } in records {
+        if fields.len() != header.fields.len() {
+            errors.push(ImportError::line(
+                *line,
+                format!(
+                    "expected {} fields, found {}",
+                    header.fields.len(),
+                    fields.len()
+                ),
+            ));
+            continue;
+        }
+        let age = match age.map(|age| fields[age].trim()) {
+            None | Some("") => None,
+            Some(value) => match value.parse() {
+                Ok(age) => Some(age),
+                Err(_) => {
+                    errors.push(ImportError {
+                        line: *line,
+                        field: Some("age".to_string()),
+                        message: "must be an integer".to_string(),
+                    });
+                    continue;
+                }
+            },
+        };
+        rows.push(ImportRow {
+            line: *line,
+            user: UserCreate {
+                email: fields[email].clone(),
+                name: fields[name].clone(),
+                age,
+            },
+        });
+    }
+    (rows, errors)
 }
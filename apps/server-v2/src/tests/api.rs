@@ -1 +1,4 @@
+pub mod middlewares;
 pub mod routes;
+pub mod test_docs;
+pub mod test_main;
@@ -0,0 +1,4 @@
+pub mod test_rate_limit;
+pub mod test_request_id;
+pub mod test_shipper;
+pub mod test_trace;
@@ -0,0 +1,271 @@
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::middleware::from_fn;
+use actix_web::{self, App, HttpMessage, ResponseError, test, web};
+
+use crate::api::guards::{API_KEY_HEADER, Caller, VerifiedApiKey};
+use crate::api::main::handler;
+use crate::api::middlewares::rate_limit::{
+    RATELIMIT_LIMIT, RATELIMIT_POLICY, RATELIMIT_REMAINING, RATELIMIT_RESET, RateLimitKey,
+    RateLimiter, rate_limit,
+};
+use crate::core::rate_limit::RateLimit;
+use crate::crud::api_keys::ApiKeyService;
+use crate::schemas::api::ErrorResponse;
+use crate::schemas::api_keys::{ApiKeyCreate, Scope};
+use crate::tests::utils::api::{TestAPIParameters, bearer};
+use crate::tests::utils::random::random_string;
+
+/// Twelve hex digits, the shape of a key prefix.
+fn hex_prefix() -> String {
+    format!("{:012x}", rand::random::<u64>() >> 16)
+}
+
+/// A limiter of its own, so counts left in Redis by other runs don't matter.
+fn limiter(prefix: &str, limit: u32) -> RateLimiter {
+    let limit = RateLimit {
+        limit,
+        window: Duration::from_secs(60),
+    };
+    RateLimiter::new(&format!("test-{}", random_string(12)), false)
+        .group("auth", &format!("{prefix}/auth"), limit, RateLimitKey::Ip)
+        .group(
+            "users",
+            &format!("{prefix}/users"),
+            limit,
+            RateLimitKey::Caller,
+        )
+}
+
+#[actix_web::test]
+async fn test_limit_per_caller() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .app_data(web::Data::new(limiter(prefix, 2)))
+            .service(handler(prefix))
+            .wrap(from_fn(rate_limit)),
+    )
+    .await;
+    let uri = format!("{prefix}/users/");
+
+    for remaining in ["1", "0"] {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(bearer("alice"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let headers = resp.headers();
+        assert_eq!(headers.get(RATELIMIT_LIMIT).unwrap(), "2");
+        assert_eq!(headers.get(RATELIMIT_REMAINING).unwrap(), remaining);
+        assert_eq!(headers.get(RATELIMIT_POLICY).unwrap(), "2;w=60");
+        assert!(headers.get(RATELIMIT_RESET).is_some());
+        assert!(headers.get(RETRY_AFTER).is_none());
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer("alice"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=120).contains(&retry_after), "{retry_after}");
+    assert_eq!(resp.headers().get(RATELIMIT_REMAINING).unwrap(), "0");
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.error.as_deref(), Some("too_many_requests"));
+
+    // Other callers have limits of their own
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer("bob"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Keys that don't verify count against the address
+    let key = format!("umk_0123456789ab_{}", random_string(43));
+    for expected in [StatusCode::UNAUTHORIZED, StatusCode::UNAUTHORIZED] {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((API_KEY_HEADER, key.clone()))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), expected);
+    }
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((API_KEY_HEADER, key))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // Routes outside the groups are not limited
+    for _ in 0..3 {
+        let req = test::TestRequest::get()
+            .uri(&format!("{prefix}/health/live"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp.headers().get(RATELIMIT_LIMIT).is_none());
+    }
+}
+
+#[actix_web::test]
+async fn test_made_up_keys_share_the_address_limit() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .app_data(web::Data::new(limiter(prefix, 3)))
+            .service(handler(prefix))
+            .wrap(from_fn(rate_limit)),
+    )
+    .await;
+    let uri = format!("{prefix}/users/");
+    let request = |key: String| {
+        test::TestRequest::get()
+            .uri(&uri)
+            .peer_addr("10.0.0.3:4000".parse().unwrap())
+            .insert_header((API_KEY_HEADER, key))
+            .to_request()
+    };
+
+    // A fresh well-formed key per request doesn't get a fresh limit
+    let mut statuses = vec![];
+    for _ in 0..5 {
+        let key = format!("umk_{}_{}", hex_prefix(), random_string(43));
+        statuses.push(test::call_service(&app, request(key)).await.status());
+    }
+    assert_eq!(statuses[..3], [StatusCode::UNAUTHORIZED; 3]);
+    assert_eq!(statuses[3..], [StatusCode::TOO_MANY_REQUESTS; 2]);
+
+    // A real key from the same address is counted on its own
+    let (_, key) = ApiKeyService {}
+        .create_api_key(
+            ApiKeyCreate {
+                name: random_string(12),
+                scopes: vec![Scope::UsersRead],
+            },
+            &api_params.db.connection,
+        )
+        .await
+        .unwrap();
+    let resp = test::call_service(&app, request(key.expose())).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(RATELIMIT_REMAINING).unwrap(), "2");
+}
+
+#[actix_web::test]
+async fn test_key_is_verified_once() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .app_data(web::Data::new(limiter(prefix, 3)))
+            .service(handler(prefix))
+            .wrap(from_fn(rate_limit)),
+    )
+    .await;
+    let service = ApiKeyService {};
+    let (created, key) = service
+        .create_api_key(
+            ApiKeyCreate {
+                name: random_string(12),
+                scopes: vec![Scope::UsersRead],
+            },
+            &api_params.db.connection,
+        )
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{prefix}/users/"))
+        .insert_header((API_KEY_HEADER, key.expose()))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let listed = service
+        .get_api_keys(&api_params.db.connection)
+        .await
+        .unwrap();
+    let stored = listed
+        .iter()
+        .find(|api_key| api_key.id == created.id)
+        .unwrap();
+    assert_eq!(stored.usage_count, 1);
+
+    // The limiter's verdict is taken as is, even against a valid key
+    let req = test::TestRequest::default()
+        .app_data(api_params.app_data.clone())
+        .insert_header((API_KEY_HEADER, key.expose()))
+        .to_http_request();
+    req.extensions_mut().insert(VerifiedApiKey(None));
+    let error = Caller::identify(&req).await.unwrap_err();
+    assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_limit_per_address() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .app_data(web::Data::new(limiter(prefix, 1)))
+            .service(handler(prefix))
+            .wrap(from_fn(rate_limit)),
+    )
+    .await;
+    let uri = format!("{prefix}/auth/login");
+    let login = |address: &str, token_for: &str| {
+        test::TestRequest::post()
+            .uri(&uri)
+            .peer_addr(address.parse().unwrap())
+            // Credentials don't matter for the auth group
+            .insert_header(bearer(token_for))
+            .set_json(serde_json::json!({"email": "nobody@example.com", "password": "x"}))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, login("10.0.0.1:4000", "alice")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, login("10.0.0.1:4001", "bob")).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let resp = test::call_service(&app, login("10.0.0.2:4000", "alice")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_no_limiter_no_limits() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(prefix))
+            .wrap(from_fn(rate_limit)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{prefix}/users/"))
+        .insert_header(bearer("tests"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(RATELIMIT_LIMIT).is_none());
+}
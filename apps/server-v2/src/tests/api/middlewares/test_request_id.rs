@@ -0,0 +1,139 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorServiceUnavailable;
+use actix_web::http::StatusCode;
+use actix_web::middleware::{Next, from_fn};
+use actix_web::{self, App, Error, HttpResponse, test, web};
+
+use crate::api::middlewares::logs::dispatch_logs;
+use crate::api::middlewares::request_id::{REQUEST_ID_HEADER, RequestId, request_id};
+
+async fn current_id() -> HttpResponse {
+    HttpResponse::Ok().body(
+        RequestId::current()
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+    )
+}
+
+async fn fail(_: ServiceRequest, _: Next<BoxBody>) -> Result<ServiceResponse, Error> {
+    Err(ErrorServiceUnavailable("down"))
+}
+
+#[actix_web::test]
+async fn test_generated_when_missing() {
+    let app = test::init_service(
+        App::new()
+            .route("/", web::get().to(current_id))
+            .wrap(from_fn(request_id)),
+    )
+    .await;
+
+    let mut ids = vec![];
+    for _ in 0..2 {
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        let id = resp.headers().get(REQUEST_ID_HEADER).unwrap().clone();
+        let body = test::read_body(resp).await;
+        // The handler runs in the scope of the id it answers with
+        assert_eq!(body, id.as_bytes());
+        assert!(uuid::Uuid::parse_str(id.to_str().unwrap()).is_ok());
+        ids.push(id);
+    }
+    assert_ne!(ids[0], ids[1]);
+}
+
+#[actix_web::test]
+async fn test_client_id_is_kept() {
+    let app = test::init_service(
+        App::new()
+            .route("/", web::get().to(current_id))
+            .wrap(from_fn(request_id)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((REQUEST_ID_HEADER, "client-42:retry.1"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "client-42:retry.1"
+    );
+    assert_eq!(test::read_body(resp).await, "client-42:retry.1");
+}
+
+#[actix_web::test]
+async fn test_invalid_client_id_is_replaced() {
+    let app = test::init_service(
+        App::new()
+            .route("/", web::get().to(current_id))
+            .wrap(from_fn(request_id)),
+    )
+    .await;
+
+    for invalid in ["", "has spaces", "quote\"d", &"a".repeat(129)] {
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, invalid))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let id = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert!(
+            uuid::Uuid::parse_str(id.to_str().unwrap()).is_ok(),
+            "{invalid}"
+        );
+    }
+}
+
+#[actix_web::test]
+async fn test_no_id_outside_requests() {
+    assert_eq!(RequestId::current(), None);
+
+    let id = RequestId::generate();
+    let seen = id.clone().scope(async { RequestId::current() }).await;
+    assert_eq!(seen, Some(id));
+    assert_eq!(RequestId::current(), None);
+}
+
+#[actix_web::test]
+async fn test_inside_dispatch_logs() {
+    let app = test::init_service(
+        App::new()
+            .route("/", web::get().to(current_id))
+            .wrap(from_fn(dispatch_logs))
+            .wrap(from_fn(request_id)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((REQUEST_ID_HEADER, "logged-1"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "logged-1");
+    assert_eq!(test::read_body(resp).await, "logged-1");
+}
+
+#[actix_web::test]
+async fn test_echoed_on_errors() {
+    let app = test::init_service(
+        App::new()
+            .route("/", web::get().to(current_id))
+            .wrap(from_fn(fail))
+            .wrap(from_fn(request_id)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((REQUEST_ID_HEADER, "failed-1"))
+        .to_request();
+    // Still an error for the outer middlewares, answered as the server would
+    let Err(error) = test::try_call_service(&app, req).await else {
+        panic!("the failing middleware answered");
+    };
+    let resp = error.error_response();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "failed-1");
+}
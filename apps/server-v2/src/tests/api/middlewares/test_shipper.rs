@@ -0,0 +1,73 @@
+use chrono::Utc;
+use std::time::Duration;
+
+use crate::api::middlewares::shipper::{EnqueueError, LogEntry, LogShipper, ShipperSnapshot};
+use crate::api::middlewares::utils::{Params, ReqParams, ResParams};
+
+// Nothing listens on port 1, so every flush fails right away
+const UNREACHABLE_CONF: &str = "http::addr=127.0.0.1:1;protocol_version=1;retry_timeout=0;";
+
+fn log_entry() -> LogEntry {
+    LogEntry {
+        params: Params {
+            req_params: ReqParams {
+                request_id: "3f2c8a9e-5d41-4b7e-9c0a-6e1f2d3b4a5c".to_string(),
+                method: "GET".to_string(),
+                headers: vec![],
+                http_version: "HTTP/1.1".to_string(),
+                path: "/api/v2/users/".to_string(),
+                scheme: "http".to_string(),
+                path_params: String::new(),
+                query_string: String::new(),
+                server: "localhost".to_string(),
+                client: "127.0.0.1".to_string(),
+            },
+            res_params: ResParams {
+                status_code: "200".to_string(),
+                headers: vec![],
+                process_time: 0.001,
+                created_at: Utc::now().to_rfc3339(),
+            },
+        },
+        timestamp: Utc::now(),
+    }
+}
+
+#[test]
+fn test_shutdown_flushes_pending_rows() {
+    let shipper = LogShipper::new(
+        UNREACHABLE_CONF.to_string(),
+        100,
+        50,
+        Duration::from_secs(60),
+    );
+    for _ in 0..3 {
+        shipper.enqueue(log_entry()).expect("queue has room");
+    }
+
+    // Neither the batch size nor the interval was reached, only shutdown flushes
+    shipper.shutdown();
+    assert_eq!(
+        shipper.stats(),
+        ShipperSnapshot {
+            shipped: 0,
+            dropped: 0,
+            failed: 3,
+        }
+    );
+}
+
+#[test]
+fn test_enqueue_after_shutdown_is_dropped() {
+    let shipper = LogShipper::new(
+        UNREACHABLE_CONF.to_string(),
+        100,
+        50,
+        Duration::from_secs(60),
+    );
+    shipper.shutdown();
+
+    let result = shipper.enqueue(log_entry());
+    assert!(matches!(result, Err(EnqueueError::Closed)));
+    assert_eq!(shipper.stats().dropped, 1);
+}
@@ -0,0 +1,211 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorServiceUnavailable;
+use actix_web::http::StatusCode;
+use actix_web::middleware::{Next, from_fn};
+use actix_web::{self, App, Error, HttpResponse, test, web};
+use opentelemetry::trace::{SpanId, SpanKind, Status, TraceId};
+use opentelemetry::{StringValue, Value};
+
+use crate::api::main::handler;
+use crate::api::middlewares::trace::{TRACEPARENT, trace_requests};
+use crate::core::telemetry::subscriber;
+use crate::schemas::users::UserCreate;
+use crate::tests::utils::api::{TestAPIParameters, authorize};
+use crate::tests::utils::random::{random_email, random_string};
+use crate::tests::utils::telemetry::{attribute, find, finished_spans, span_id, tracer_provider};
+
+const TRACEPARENT_VALUE: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+/// The trace id, span id and sampled flag of a `traceparent`.
+fn parse(traceparent: &str) -> (TraceId, SpanId, bool) {
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    assert_eq!(parts.len(), 4, "{traceparent}");
+    (
+        TraceId::from_hex(parts[1]).unwrap(),
+        SpanId::from_hex(parts[2]).unwrap(),
+        parts[3] == "01",
+    )
+}
+
+fn text(value: &str) -> Value {
+    Value::String(StringValue::from(value.to_string()))
+}
+
+#[actix_web::test]
+async fn test_request_spans() {
+    let _subscriber = tracing::subscriber::set_default(subscriber(tracer_provider()));
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(prefix))
+            .wrap(from_fn(authorize))
+            .wrap(from_fn(trace_requests)),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{prefix}/users/"))
+        .insert_header((TRACEPARENT, TRACEPARENT_VALUE))
+        .set_json(UserCreate {
+            email: random_email(),
+            name: random_string(16),
+            age: None,
+        })
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let traceparent = resp.headers().get(TRACEPARENT).unwrap().to_str().unwrap();
+    let returned = parse(traceparent);
+    drop(resp);
+
+    let (trace_id, caller_span_id, _) = parse(TRACEPARENT_VALUE);
+    let spans = finished_spans(trace_id);
+
+    let server = find(&spans, "POST /api/v2/users/");
+    assert_eq!(server.span_kind, SpanKind::Server);
+    assert_eq!(server.parent_span_id, caller_span_id);
+    assert!(server.parent_span_is_remote);
+    assert_eq!(returned, (trace_id, span_id(server), true));
+    assert_eq!(
+        attribute(server, "http.route"),
+        Some(&text("/api/v2/users/"))
+    );
+    assert_eq!(
+        attribute(server, "http.response.status_code"),
+        Some(&Value::I64(201))
+    );
+    assert_eq!(server.status, Status::Unset);
+
+    let service = find(&spans, "UserService::create_user");
+    assert_eq!(service.parent_span_id, span_id(server));
+
+    let insert = find(&spans, "INSERT");
+    assert_eq!(insert.span_kind, SpanKind::Client);
+    assert_eq!(insert.parent_span_id, span_id(service));
+    assert_eq!(
+        attribute(insert, "db.system.name"),
+        Some(&text("postgresql"))
+    );
+    let Some(Value::String(sql)) = attribute(insert, "db.query.text") else {
+        panic!("no statement on {insert:?}");
+    };
+    assert!(sql.as_str().starts_with(r#"INSERT INTO "users""#), "{sql}");
+
+    // Creating a user invalidates the cached lists
+    let redis = find(&spans, "redis SCAN");
+    assert_eq!(redis.span_kind, SpanKind::Client);
+    assert_eq!(redis.parent_span_id, span_id(service));
+    assert_eq!(attribute(redis, "db.system.name"), Some(&text("redis")));
+}
+
+#[actix_web::test]
+async fn test_new_and_unsampled_traces() {
+    let _subscriber = tracing::subscriber::set_default(subscriber(tracer_provider()));
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(prefix))
+            .wrap(from_fn(authorize))
+            .wrap(from_fn(trace_requests)),
+    )
+    .await;
+    let uri = format!("{prefix}/users/id/0");
+
+    // An invalid traceparent is ignored, starting a trace of our own
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((TRACEPARENT, "not-a-traceparent"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let (trace_id, returned_span_id, sampled) =
+        parse(resp.headers().get(TRACEPARENT).unwrap().to_str().unwrap());
+    assert!(sampled);
+    drop(resp);
+    let spans = finished_spans(trace_id);
+    let server = find(&spans, "GET /api/v2/users/id/{id}");
+    assert_eq!(server.parent_span_id, SpanId::INVALID);
+    assert_eq!(span_id(server), returned_span_id);
+    // Client errors don't fail the server span
+    assert_eq!(server.status, Status::Unset);
+
+    // Nothing is recorded for a trace the caller doesn't sample, but it is propagated
+    let unsampled = "00-1af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00";
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((TRACEPARENT, unsampled))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let (trace_id, _, sampled) = parse(resp.headers().get(TRACEPARENT).unwrap().to_str().unwrap());
+    assert_eq!(trace_id, parse(unsampled).0);
+    assert!(!sampled);
+    drop(resp);
+    assert!(finished_spans(trace_id).is_empty());
+}
+
+async fn fail(_: ServiceRequest, _: Next<BoxBody>) -> Result<ServiceResponse, Error> {
+    Err(ErrorServiceUnavailable("down"))
+}
+
+#[actix_web::test]
+async fn test_failed_middleware() {
+    let _subscriber = tracing::subscriber::set_default(subscriber(tracer_provider()));
+    let app = test::init_service(
+        App::new()
+            .route("/", web::get().to(HttpResponse::Ok))
+            .wrap(from_fn(fail))
+            .wrap(from_fn(trace_requests)),
+    )
+    .await;
+
+    let traceparent = "00-2af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header((TRACEPARENT, traceparent))
+        .to_request();
+    let Err(error) = test::try_call_service(&app, req).await else {
+        panic!("the failing middleware answered");
+    };
+    let resp = error.error_response();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let (trace_id, returned_span_id, _) =
+        parse(resp.headers().get(TRACEPARENT).unwrap().to_str().unwrap());
+    assert_eq!(trace_id, parse(traceparent).0);
+    drop(resp);
+
+    let spans = finished_spans(trace_id);
+    let server = find(&spans, "GET /");
+    assert_eq!(span_id(server), returned_span_id);
+    assert_eq!(
+        attribute(server, "http.response.status_code"),
+        Some(&Value::I64(503))
+    );
+    assert!(matches!(server.status, Status::Error { .. }));
+}
+
+#[actix_web::test]
+async fn test_no_subscriber() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(prefix))
+            .wrap(from_fn(authorize))
+            .wrap(from_fn(trace_requests)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{prefix}/users/id/0"))
+        .insert_header((TRACEPARENT, TRACEPARENT_VALUE))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(resp.headers().get(TRACEPARENT).is_none());
+}
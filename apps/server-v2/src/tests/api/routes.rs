@@ -1 +1,4 @@
+pub mod test_api_keys;
+pub mod test_auth;
+pub mod test_roles;
 pub mod test_users;
\ No newline at end of file
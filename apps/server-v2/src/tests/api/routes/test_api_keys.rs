@@ -0,0 +1,232 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{self, App, test};
+use serde_json::json;
+
+use crate::api::guards::{ADMIN_KEY_HEADER, API_KEY_HEADER};
+use crate::api::main::handler;
+use crate::core::config::SETTINGS;
+use crate::models::api_keys::Model as ApiKeyModel;
+use crate::schemas::api::ErrorResponse;
+use crate::tests::utils::api::TestAPIParameters;
+use crate::tests::utils::random::{random_email, random_string};
+
+fn admin_key() -> String {
+    SETTINGS
+        .admin_api_key
+        .as_ref()
+        .expect("ADMIN_API_KEY should be set for tests")
+        .expose()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn test_manage_api_keys() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let uri = format!("{}/api-keys/", api_params.prefix);
+    let name = format!("reporting {}", random_string(8));
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(json!({"name": name, "scopes": ["users:read"]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .set_json(json!({"name": name, "scopes": []}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .set_json(json!({"name": name, "scopes": ["users:delete"]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .set_json(json!({"name": name, "scopes": ["users:read"]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "no-store");
+    let created: serde_json::Value = test::read_body_json(resp).await;
+    let id = created["id"].as_i64().unwrap();
+    let key = created["key"].as_str().unwrap().to_string();
+    assert_eq!(created["name"], name);
+    assert_eq!(created["scopes"], json!(["users:read"]));
+    assert!(created["key_hash"].is_null());
+    assert!(created["key_salt"].is_null());
+
+    // Listed without the secret
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let listed: Vec<serde_json::Value> = test::read_body_json(resp).await;
+    let entry = listed.iter().find(|api_key| api_key["id"] == id).unwrap();
+    assert!(entry["key"].is_null());
+
+    // Rotating hands out a new key and retires the old one
+    let req = test::TestRequest::post()
+        .uri(&format!("{uri}{id}/rotate"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let rotated: serde_json::Value = test::read_body_json(resp).await;
+    let new_key = rotated["key"].as_str().unwrap().to_string();
+    assert_ne!(new_key, key);
+
+    let users_uri = format!("{}/users/", api_params.prefix);
+    let req = test::TestRequest::get()
+        .uri(&users_uri)
+        .insert_header((API_KEY_HEADER, key))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let req = test::TestRequest::get()
+        .uri(&users_uri)
+        .insert_header((API_KEY_HEADER, new_key.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{uri}{id}"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&users_uri)
+        .insert_header((API_KEY_HEADER, new_key))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{uri}{id}/rotate"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{uri}{}", i32::MAX))
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_scopes_are_enforced() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let keys_uri = format!("{}/api-keys/", api_params.prefix);
+    let users_uri = format!("{}/users/", api_params.prefix);
+
+    let read_only_name = random_string(10);
+    let mut keys = Vec::new();
+    for (name, scopes) in [
+        (read_only_name.clone(), json!(["users:read"])),
+        (random_string(10), json!(["users:read", "users:write"])),
+    ] {
+        let req = test::TestRequest::post()
+            .uri(&keys_uri)
+            .insert_header((ADMIN_KEY_HEADER, admin_key()))
+            .set_json(json!({"name": name, "scopes": scopes}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let created: serde_json::Value = test::read_body_json(resp).await;
+        keys.push(created["key"].as_str().unwrap().to_string());
+    }
+    let (read_only, read_write) = (&keys[0], &keys[1]);
+    let new_user = || json!({"name": random_string(10), "email": random_email(), "age": 30});
+
+    // Both headers are accepted, the scheme is case-insensitive
+    for header in [
+        (API_KEY_HEADER, read_only.clone()),
+        (AUTHORIZATION.as_str(), format!("ApiKey {read_only}")),
+        (AUTHORIZATION.as_str(), format!("apikey {read_only}")),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&users_uri)
+            .insert_header(header)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::post()
+        .uri(&users_uri)
+        .insert_header((API_KEY_HEADER, read_only.clone()))
+        .set_json(new_user())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert!(body.message.contains("users:write"), "{}", body.message);
+
+    let req = test::TestRequest::post()
+        .uri(&users_uri)
+        .insert_header((AUTHORIZATION, format!("ApiKey {read_write}")))
+        .set_json(new_user())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // A key doesn't stand in for a token
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/auth/password", api_params.prefix))
+        .insert_header((API_KEY_HEADER, read_write.clone()))
+        .set_json(json!({"current_password": "x", "new_password": "y"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri(&users_uri)
+        .insert_header((API_KEY_HEADER, "umk_000000000000_unknown"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.message, "Invalid API key");
+
+    // Every authenticated use is recorded, refused ones included
+    let req = test::TestRequest::get()
+        .uri(&keys_uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .to_request();
+    let listed: Vec<ApiKeyModel> = test::call_and_read_body_json(&app, req).await;
+    let read_only = listed
+        .iter()
+        .find(|api_key| api_key.name == read_only_name)
+        .unwrap();
+    assert_eq!(read_only.usage_count, 4);
+    assert!(read_only.last_used_at.is_some());
+}
@@ -0,0 +1,314 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::{AUTHORIZATION, ETAG, IF_NONE_MATCH, WWW_AUTHENTICATE};
+use actix_web::{self, App, test};
+use serde_json::json;
+
+use crate::api::guards::ADMIN_KEY_HEADER;
+use crate::api::main::handler;
+use crate::core::config::SETTINGS;
+use crate::core::jwt::JWT;
+use crate::schemas::api::ErrorResponse;
+use crate::schemas::auth::TokenResponse;
+use crate::tests::utils::api::{TestAPIParameters, bearer};
+use crate::tests::utils::users::create_random_user;
+
+#[actix_web::test]
+async fn test_users_require_a_token() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let uri = format!("{}/users/", api_params.prefix);
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        resp.headers().get(WWW_AUTHENTICATE).unwrap(),
+        "Bearer realm=\"api\""
+    );
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.error.as_deref(), Some("unauthorized"));
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((AUTHORIZATION, "Bearer not-a-token"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let challenge = resp
+        .headers()
+        .get(WWW_AUTHENTICATE)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(challenge.contains("error=\"invalid_token\""), "{challenge}");
+
+    let mut claims = JWT.claims("expired");
+    claims.exp = claims.iat - 3600;
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header((
+            AUTHORIZATION,
+            format!("Bearer {}", JWT.encode(&claims).unwrap()),
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.message, "Token has expired");
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(bearer("tests"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Service endpoints stay public
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/health/live", api_params.prefix))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_issue_token() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let uri = format!("{}/auth/token", api_params.prefix);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(json!({"subject": "alice"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let admin_key = SETTINGS
+        .admin_api_key
+        .as_ref()
+        .expect("ADMIN_API_KEY should be set for tests");
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key.expose()))
+        .set_json(json!({"subject": " "}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key.expose()))
+        .set_json(json!({"subject": "alice"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("Cache-Control").unwrap(), "no-store");
+    let token: TokenResponse = test::read_body_json(resp).await;
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.expires_in, SETTINGS.jwt_expiry.as_secs());
+    assert_eq!(JWT.decode(&token.access_token).unwrap().sub, "alice");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/", api_params.prefix))
+        .insert_header((AUTHORIZATION, format!("Bearer {}", token.access_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_password_change_refreshes_the_user() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let admin_key = SETTINGS
+        .admin_api_key
+        .as_ref()
+        .expect("ADMIN_API_KEY should be set for tests");
+    let user_uri = format!("{}/users/id/{}", api_params.prefix, user.id);
+
+    // Cached by this read
+    let req = test::TestRequest::get()
+        .uri(&user_uri)
+        .insert_header(bearer("tests"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let etag = resp.headers().get(ETAG).unwrap().clone();
+    let before: serde_json::Value = test::read_body_json(resp).await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{user_uri}/password"))
+        .insert_header(bearer("tests"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key.expose()))
+        .set_json(json!({"password": "correct horse battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // The row's `updated_at` moved, so the old tag no longer matches
+    let req = test::TestRequest::get()
+        .uri(&user_uri)
+        .insert_header(bearer("tests"))
+        .insert_header((IF_NONE_MATCH, etag.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_ne!(resp.headers().get(ETAG).unwrap(), &etag);
+    let after: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(after["version"], before["version"].as_i64().unwrap() + 1);
+    assert_ne!(after["updated_at"], before["updated_at"]);
+}
+
+#[actix_web::test]
+async fn test_login_and_change_password() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let admin_key = SETTINGS
+        .admin_api_key
+        .as_ref()
+        .expect("ADMIN_API_KEY should be set for tests");
+    let password_uri = format!("{}/users/id/{}/password", api_params.prefix, user.id);
+
+    let req = test::TestRequest::put()
+        .uri(&password_uri)
+        .insert_header(bearer("tests"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key.expose()))
+        .set_json(json!({"password": "short"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.fields[0].field, "password");
+
+    // Setting a password is for admins
+    let req = test::TestRequest::put()
+        .uri(&password_uri)
+        .insert_header(bearer("tests"))
+        .set_json(json!({"password": "correct horse battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::put()
+        .uri(&password_uri)
+        .insert_header(bearer("tests"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key.expose()))
+        .set_json(json!({"password": "correct horse battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let login_uri = format!("{}/auth/login", api_params.prefix);
+    let req = test::TestRequest::post()
+        .uri(&login_uri)
+        .set_json(json!({"email": user.email, "password": "wrong horse battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.message, "Invalid email or password");
+
+    let req = test::TestRequest::post()
+        .uri(&login_uri)
+        .set_json(json!({"email": user.email, "password": "correct horse battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let token: TokenResponse = test::read_body_json(resp).await;
+    let claims = JWT.decode(&token.access_token).unwrap();
+    assert_eq!(claims.user_id(), Some(user.id));
+    let authorization = (AUTHORIZATION, format!("Bearer {}", token.access_token));
+
+    // The user is served without its hash
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .insert_header(authorization.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["id"], user.id);
+    assert!(body.get("password_hash").is_none());
+
+    let password_uri = format!("{}/auth/password", api_params.prefix);
+    let req = test::TestRequest::post()
+        .uri(&password_uri)
+        .insert_header(authorization.clone())
+        .set_json(json!({
+            "current_password": "wrong horse battery",
+            "new_password": "staple horse battery",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&password_uri)
+        .insert_header(authorization.clone())
+        .set_json(json!({
+            "current_password": "correct horse battery",
+            "new_password": "correct horse battery",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::post()
+        .uri(&password_uri)
+        .insert_header(authorization)
+        .set_json(json!({
+            "current_password": "correct horse battery",
+            "new_password": "staple horse battery",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // Tokens that don't name a user can't change a password
+    let req = test::TestRequest::post()
+        .uri(&password_uri)
+        .insert_header(bearer("tests"))
+        .set_json(json!({
+            "current_password": "staple horse battery",
+            "new_password": "another horse battery",
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::post()
+        .uri(&login_uri)
+        .set_json(json!({"email": user.email, "password": "staple horse battery"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,269 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::IF_MATCH;
+use actix_web::{self, App, test};
+use serde_json::json;
+
+use crate::api::guards::ADMIN_KEY_HEADER;
+use crate::api::main::handler;
+use crate::core::config::SETTINGS;
+use crate::core::database::DatabaseService;
+use crate::crud::UserService;
+use crate::models::users::{Model as UserModel, Role};
+use crate::schemas::api::ErrorResponse;
+use crate::tests::utils::api::{TestAPIParameters, bearer};
+use crate::tests::utils::random::{random_email, random_string};
+use crate::tests::utils::users::create_random_user;
+
+async fn user_with_role(db: &DatabaseService, role: Role) -> UserModel {
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    UserService {}
+        .set_role(user.id as u16, role, &db.connection)
+        .await
+        .unwrap()
+}
+
+fn admin_key() -> String {
+    SETTINGS
+        .admin_api_key
+        .as_ref()
+        .expect("ADMIN_API_KEY should be set for tests")
+        .expose()
+        .to_string()
+}
+
+#[actix_web::test]
+async fn test_new_users_have_the_self_role() {
+    let api_params = TestAPIParameters::new().await;
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    assert_eq!(user.role, Role::SelfOnly);
+}
+
+/// Every role against every kind of route, on its own record and another's.
+#[actix_web::test]
+async fn test_role_matrix() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let users = format!("{}/users", api_params.prefix);
+    let db = &api_params.db;
+
+    use StatusCode as S;
+    // admin, operator, viewer, self
+    let roles = [Role::Admin, Role::Operator, Role::Viewer, Role::SelfOnly];
+    let cases: [(&str, [StatusCode; 4]); 9] = [
+        ("list", [S::OK, S::OK, S::OK, S::FORBIDDEN]),
+        ("read other", [S::OK, S::OK, S::OK, S::FORBIDDEN]),
+        ("read own", [S::OK, S::OK, S::OK, S::OK]),
+        ("read own by email", [S::OK, S::OK, S::OK, S::OK]),
+        (
+            "create",
+            [S::CREATED, S::CREATED, S::FORBIDDEN, S::FORBIDDEN],
+        ),
+        ("update other", [S::OK, S::OK, S::FORBIDDEN, S::FORBIDDEN]),
+        ("update own", [S::OK, S::OK, S::FORBIDDEN, S::OK]),
+        (
+            "set role",
+            [S::OK, S::FORBIDDEN, S::FORBIDDEN, S::FORBIDDEN],
+        ),
+        // Last, as it takes the other user away
+        (
+            "delete other",
+            [S::NO_CONTENT, S::FORBIDDEN, S::FORBIDDEN, S::FORBIDDEN],
+        ),
+    ];
+
+    for (index, role) in roles.into_iter().enumerate() {
+        let caller = user_with_role(db, role).await;
+        let other = user_with_role(db, Role::SelfOnly).await;
+        let auth = bearer(&caller.id.to_string());
+
+        for (case, expected) in &cases {
+            let update = json!({
+                "email": random_email(),
+                "name": random_string(10),
+                "age": 40,
+                "is_active": true,
+            });
+            let req = match *case {
+                "list" => test::TestRequest::get().uri(&format!("{users}/")),
+                "read other" => test::TestRequest::get().uri(&format!("{users}/id/{}", other.id)),
+                "read own" => test::TestRequest::get().uri(&format!("{users}/id/{}", caller.id)),
+                "read own by email" => {
+                    test::TestRequest::get().uri(&format!("{users}/email/{}", caller.email))
+                }
+                "create" => test::TestRequest::post()
+                    .uri(&format!("{users}/"))
+                    .set_json(
+                        json!({"name": random_string(10), "email": random_email(), "age": 30}),
+                    ),
+                "update other" => test::TestRequest::put()
+                    .uri(&format!("{users}/id/{}", other.id))
+                    .insert_header((IF_MATCH, "*"))
+                    .set_json(update),
+                "update own" => test::TestRequest::put()
+                    .uri(&format!("{users}/id/{}", caller.id))
+                    .insert_header((IF_MATCH, "*"))
+                    .set_json(update),
+                "delete other" => test::TestRequest::delete()
+                    .uri(&format!("{users}/id/{}", other.id))
+                    .insert_header((IF_MATCH, "*")),
+                "set role" => test::TestRequest::put()
+                    .uri(&format!("{users}/id/{}/role", other.id))
+                    .insert_header((ADMIN_KEY_HEADER, admin_key()))
+                    .set_json(json!({"role": "viewer"})),
+                _ => unreachable!(),
+            };
+            let req = req.insert_header(auth.clone()).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), expected[index], "{role} {case}");
+            if resp.status() == S::FORBIDDEN {
+                let body: ErrorResponse = test::read_body_json(resp).await;
+                assert!(
+                    body.message.starts_with(&format!("Role \"{role}\" may")),
+                    "{role} {case}: {}",
+                    body.message
+                );
+            }
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_denials_explain_themselves() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let users = format!("{}/users", api_params.prefix);
+    let db = &api_params.db;
+    let member = user_with_role(db, Role::SelfOnly).await;
+    let other = user_with_role(db, Role::SelfOnly).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("{users}/id/{}", other.id))
+        .insert_header(bearer(&member.id.to_string()))
+        .insert_header((IF_MATCH, "*"))
+        .set_json(json!({"age": 41}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.error.as_deref(), Some("forbidden"));
+    assert_eq!(body.message, "Role \"self\" may only update its own record");
+
+    // Operators may bulk create and update, but not delete
+    let operator = user_with_role(db, Role::Operator).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("{users}/bulk"))
+        .insert_header(bearer(&operator.id.to_string()))
+        .set_json(json!({"operations": [{"op": "delete", "id": other.id}]}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.message, "Role \"operator\" may not delete users");
+}
+
+#[actix_web::test]
+async fn test_role_changes_apply_at_once() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+    let users = format!("{}/users", api_params.prefix);
+    let db = &api_params.db;
+    let viewer = user_with_role(db, Role::Viewer).await;
+    let auth = bearer(&viewer.id.to_string());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{users}/"))
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // The same token, after the role is lowered
+    let req = test::TestRequest::put()
+        .uri(&format!("{users}/id/{}/role", viewer.id))
+        .insert_header(bearer("tests"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .set_json(json!({"role": "self"}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let updated: UserModel = test::read_body_json(resp).await;
+    assert_eq!(updated.role, Role::SelfOnly);
+    assert_eq!(updated.version, viewer.version + 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{users}/"))
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{users}/id/{}/role", viewer.id))
+        .insert_header(bearer("tests"))
+        .insert_header((ADMIN_KEY_HEADER, admin_key()))
+        .set_json(json!({"role": "root"}))
+        .to_request();
+    assert!(
+        test::call_service(&app, req)
+            .await
+            .status()
+            .is_client_error()
+    );
+
+    // Deactivated and deleted users lose access
+    let req = test::TestRequest::patch()
+        .uri(&format!("{users}/id/{}", viewer.id))
+        .insert_header(auth.clone())
+        .insert_header((IF_MATCH, "*"))
+        .set_json(json!({"is_active": false}))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get()
+        .uri(&format!("{users}/id/{}", viewer.id))
+        .insert_header(auth.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.message, "User is deactivated");
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{users}/id/{}", viewer.id))
+        .insert_header(bearer("tests"))
+        .insert_header((IF_MATCH, "*"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    let req = test::TestRequest::get()
+        .uri(&format!("{users}/id/{}", viewer.id))
+        .insert_header(auth)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(body.message, "The user this token was issued to is gone");
+}
@@ -1,13 +1,19 @@
 use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
 use actix_web::{self, App, test};
+use serde_json::json;
 
+use crate::api::guards::ADMIN_KEY_HEADER;
 use crate::api::main::handler;
+use crate::core::config::SETTINGS;
+use crate::core::csv;
 use crate::models::users::Model as UserModel;
-use crate::schemas::users::{UserCreate, UserUpdate};
+use crate::schemas::api::{CursorPage, ErrorResponse, Paginated};
+use crate::schemas::users::{BulkResponse, EXPORT_COLUMNS, ImportReport, UserCreate, UserUpdate};
 
-use crate::tests::utils::api::TestAPIParameters;
+use crate::tests::utils::api::{TestAPIParameters, authorize, if_match};
+use crate::tests::utils::random::{random_email, random_int, random_string};
 use crate::tests::utils::users::create_random_user;
-use crate::tests::utils::utils::{random_email, random_int, random_string};
 
 #[actix_web::test]
 async fn test_create_user() {
@@ -15,7 +21,8 @@ async fn test_create_user() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
@@ -46,7 +53,8 @@ async fn test_read_user() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
@@ -74,7 +82,8 @@ async fn test_read_user_not_found() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
@@ -93,7 +102,8 @@ async fn test_get_users() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
@@ -108,8 +118,51 @@ async fn test_get_users() {
 
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::OK);
-    let users: Vec<UserModel> = test::read_body_json(resp).await;
-    assert!(users.len() >= 5);
+    let users: Paginated<UserModel> = test::read_body_json(resp).await;
+    assert!(users.items.len() >= 5);
+    assert!(users.total >= 5);
+    assert_eq!(users.page, 1);
+    assert_eq!(users.links.prev, None);
+}
+
+#[actix_web::test]
+async fn test_get_users_links() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    for _ in 0..5 {
+        let _ = create_random_user(true, Some(&api_params.db.connection)).await;
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/?limit=2&page=2", api_params.prefix))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let link = resp
+        .headers()
+        .get("link")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(link.contains("rel=\"prev\""));
+    assert!(link.contains("rel=\"next\""));
+
+    let users: Paginated<UserModel> = test::read_body_json(resp).await;
+    assert_eq!(users.items.len(), 2);
+    assert_eq!((users.page, users.limit), (2, 2));
+    assert_eq!(users.pages, users.total.div_ceil(2));
+    let next = users.links.next.expect("page 2 should have a next page");
+    assert!(next.contains("limit=2") && next.contains("page=3"));
+    assert!(users.links.prev.unwrap().contains("page=1"));
 }
 
 #[actix_web::test]
@@ -118,7 +171,8 @@ async fn test_update_user() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
@@ -128,14 +182,15 @@ async fn test_update_user() {
         .unwrap();
 
     let update = UserUpdate {
-        name: Some("updated name".to_string()),
+        name: "updated name".to_string(),
         age: Some(30),
-        email: None,
-        is_active: None,
+        email: user.email.clone(),
+        is_active: Some(false),
     };
 
     let req = test::TestRequest::put()
         .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .insert_header(if_match(user.version))
         .set_json(update.clone())
         .to_request();
 
@@ -143,31 +198,60 @@ async fn test_update_user() {
     assert_eq!(resp.status(), StatusCode::OK);
     let updated: UserModel = test::read_body_json(resp).await;
     assert_eq!(updated.id, user.id);
-    assert_eq!(updated.name, update.name.unwrap());
+    assert_eq!(updated.name, update.name);
     assert_eq!(updated.age, update.age);
-    // Email unchanged
+    assert_eq!(updated.is_active, Some(false));
     assert_eq!(updated.email, user.email);
 }
 
+#[actix_web::test]
+async fn test_update_user_requires_every_field() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+
+    // `age` and `is_active` are nullable but PUT replaces the whole user
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .insert_header(if_match(user.version))
+        .set_json(serde_json::json!({"email": user.email, "name": "updated name"}))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
 #[actix_web::test]
 async fn test_update_user_not_found() {
     let api_params = TestAPIParameters::new().await;
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
     let update = UserUpdate {
-        name: Some("updated name".to_string()),
+        name: "updated name".to_string(),
         age: Some(30),
-        email: None,
+        email: random_email(),
         is_active: None,
     };
 
     let req = test::TestRequest::put()
         .uri(&format!("{}/users/id/999999", api_params.prefix))
+        .insert_header(if_match(1))
         .set_json(update)
         .to_request();
 
@@ -181,7 +265,8 @@ async fn test_delete_user() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
@@ -192,6 +277,7 @@ async fn test_delete_user() {
 
     let req = test::TestRequest::delete()
         .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .insert_header(if_match(user.version))
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::NO_CONTENT);
@@ -210,13 +296,821 @@ async fn test_delete_user_not_found() {
     let app = test::init_service(
         App::new()
             .app_data(api_params.app_data.clone())
-            .service(handler(api_params.prefix.as_str())),
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
     )
     .await;
 
     let req = test::TestRequest::delete()
         .uri(&format!("{}/users/id/999999", api_params.prefix))
+        .insert_header(if_match(1))
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
+
+#[actix_web::test]
+async fn test_create_user_invalid_body() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/users/", api_params.prefix))
+        .insert_header(("content-type", "application/json"))
+        .set_payload("{\"email\": 42}")
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let error: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(error.status_code, 400);
+    assert_eq!(error.error.as_deref(), Some("bad_request"));
+}
+
+#[actix_web::test]
+async fn test_create_user_duplicate_email() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let user_create = UserCreate {
+        email: user.email,
+        name: random_string(32),
+        age: None,
+    };
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/users/", api_params.prefix))
+        .set_json(user_create)
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let error: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(error.error.as_deref(), Some("conflict"));
+    assert_eq!(error.fields.len(), 1);
+    assert_eq!(error.fields[0].field, "email");
+}
+
+#[actix_web::test]
+async fn test_create_user_invalid_fields() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user_create = UserCreate {
+        email: "not-an-email".to_string(),
+        name: "<script>".to_string(),
+        age: Some(-1),
+    };
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/users/", api_params.prefix))
+        .set_json(user_create)
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(error.error.as_deref(), Some("validation_error"));
+    let fields: Vec<&str> = error.fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(fields, ["email", "name", "age"]);
+}
+
+#[actix_web::test]
+async fn test_get_users_cursor() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    for _ in 0..3 {
+        let _ = create_random_user(true, Some(&api_params.db.connection)).await;
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/?after=&limit=2", api_params.prefix))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().contains_key("link"));
+    let first: CursorPage<UserModel> = test::read_body_json(resp).await;
+    assert_eq!(first.items.len(), 2);
+    assert!(first.prev_cursor.is_none());
+    let next = first
+        .links
+        .next
+        .expect("first page should link to the next one");
+
+    let req = test::TestRequest::get()
+        .uri(next.trim_start_matches("http://localhost:8080"))
+        .to_request();
+    let second: CursorPage<UserModel> = test::call_and_read_body_json(&app, req).await;
+    assert!(second.items[0].id > first.items[1].id);
+    assert!(second.prev_cursor.is_some());
+}
+
+#[actix_web::test]
+async fn test_get_users_bad_pagination() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let oversized = format!("limit={}", SETTINGS.max_page_size + 1);
+    for query in [
+        "page=0",
+        "limit=0",
+        "after=&limit=0",
+        oversized.as_str(),
+        "limit=18446744073709551615",
+        "page=18446744073709551615&limit=18",
+        "page=4611686018427387904&limit=1000",
+        "after=garbage",
+        "after=&before=",
+        "after=&page=2",
+        "after=&sort=name",
+        "sort=password",
+        "age_min=50&age_max=10",
+        "created_after=yesterday",
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("{}/users/?{query}", api_params.prefix))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    // Past the last page, but within range
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "{}/users/?page=1000000000&limit=1000",
+            api_params.prefix
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let users: Paginated<UserModel> = test::read_body_json(resp).await;
+    assert!(users.items.is_empty());
+    assert_eq!(users.page, 1_000_000_000);
+}
+
+#[actix_web::test]
+async fn test_get_users_sort_and_filters() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    for _ in 0..3 {
+        let _ = create_random_user(true, Some(&api_params.db.connection)).await;
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "{}/users/?sort=-age,name&is_active=true&age_min=18&age_max=65&updated_before=2999-01-01T00:00:00Z",
+            api_params.prefix
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let users: Paginated<UserModel> = test::read_body_json(resp).await;
+    assert!(
+        users
+            .items
+            .windows(2)
+            .all(|pair| pair[0].age >= pair[1].age)
+    );
+    assert!(users.items.iter().all(|user| user.is_active == Some(true)));
+}
+
+#[actix_web::test]
+async fn test_restore_user() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .insert_header(if_match(user.version))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "{}/users/id/{}/restore",
+            api_params.prefix, user.id
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .to_request();
+    let resp: UserModel = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp.id, user.id);
+    assert_eq!(resp.deleted_at, None);
+}
+
+#[actix_web::test]
+async fn test_purge_user_requires_admin() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let uri = format!("{}/users/id/{}/purge", api_params.prefix, user.id);
+
+    let req = test::TestRequest::delete().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, "wrong key"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let admin_key = SETTINGS
+        .admin_api_key
+        .as_ref()
+        .expect("ADMIN_API_KEY should be set for tests");
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header((ADMIN_KEY_HEADER, admin_key.expose()))
+        .insert_header(if_match(user.version))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // Gone for good: nothing left to restore
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "{}/users/id/{}/restore",
+            api_params.prefix, user.id
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_patch_user() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let uri = format!("{}/users/id/{}", api_params.prefix, user.id);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(if_match(user.version))
+        .insert_header(("content-type", "application/merge-patch+json"))
+        .set_payload(r#"{"age": null}"#)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let patched: UserModel = test::read_body_json(resp).await;
+    assert_eq!(patched.age, None);
+    assert_eq!(patched.name, user.name);
+
+    // A failing `test` op means the user changed under the client
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(if_match(patched.version))
+        .insert_header(("content-type", "application/json-patch+json"))
+        .set_payload(
+            r#"[{"op": "test", "path": "/name", "value": "someone else"},
+                {"op": "replace", "path": "/name", "value": "Patched"}]"#,
+        )
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(if_match(patched.version))
+        .insert_header(("content-type", "text/plain"))
+        .set_payload("name=Patched")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(if_match(patched.version))
+        .insert_header(("content-type", "application/json-patch+json"))
+        .set_payload(r#"[{"op": "remove", "path": "/email"}]"#)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+async fn test_update_user_preconditions() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let uri = format!("{}/users/id/{}", api_params.prefix, user.id);
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    let etag = resp.headers().get("etag").unwrap().clone();
+    assert_eq!(etag.to_str().unwrap(), format!("\"v{}\"", user.version));
+
+    let update = UserUpdate {
+        name: "first writer".to_string(),
+        ..UserUpdate::from(&user)
+    };
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .set_json(update.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("if-match", etag.clone()))
+        .set_json(update)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_ne!(resp.headers().get("etag"), Some(&etag));
+
+    // The second writer still holds the old ETag
+    let update = UserUpdate {
+        name: "second writer".to_string(),
+        ..UserUpdate::from(&user)
+    };
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("if-match", etag.clone()))
+        .set_json(update)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("if-match", "*"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+}
+
+#[actix_web::test]
+async fn test_read_user_not_modified() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let uri = format!("{}/users/id/{}", api_params.prefix, user.id);
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let etag = resp.headers().get("etag").unwrap().clone();
+    let modified = resp.headers().get("last-modified").unwrap().clone();
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-none-match", etag.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get("etag"), Some(&etag));
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-modified-since", modified.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    // A non-matching If-None-Match wins over a matching If-Modified-Since
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/email/{}", api_params.prefix, user.email))
+        .insert_header(("if-none-match", "\"v0\""))
+        .insert_header(("if-modified-since", modified))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let update = UserUpdate {
+        name: "changed".to_string(),
+        ..UserUpdate::from(&user)
+    };
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(if_match(user.version))
+        .set_json(update)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("if-none-match", etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_get_users_not_modified() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    // Narrowed to the new user so concurrent tests don't change the listing
+    for query in [
+        format!("search={}", user.email),
+        format!("search={}&after=", user.email),
+    ] {
+        let uri = format!("{}/users/?{query}", api_params.prefix);
+
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let etag = resp.headers().get("etag").unwrap().clone();
+        assert!(!etag.to_str().unwrap().starts_with("W/"));
+        assert!(resp.headers().contains_key("last-modified"));
+
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("if-none-match", etag.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("{uri}&limit=5"))
+            .insert_header(("if-none-match", etag))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
+
+#[actix_web::test]
+async fn test_bulk_users() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let user = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let uri = format!("{}/users/bulk", api_params.prefix);
+    let operations = json!([
+        {"op": "create", "user": {"email": random_email(), "name": "bulk one", "age": 30}},
+        {"op": "update", "id": user.id, "version": user.version, "user": {
+            "email": user.email, "name": "bulk updated", "age": null, "is_active": false
+        }},
+        {"op": "create", "user": {"email": "not an email", "name": "bulk two"}},
+        {"op": "delete", "id": 999999, "version": 1},
+    ]);
+
+    // All or nothing: the invalid create fails the whole batch
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(json!({"operations": operations}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(error.fields[0].field, "operations[2].email");
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(json!({"mode": "best_effort", "operations": operations}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let response: BulkResponse = test::read_body_json(resp).await;
+    assert_eq!((response.succeeded, response.failed), (2, 2));
+    let statuses: Vec<u16> = response
+        .results
+        .iter()
+        .map(|result| result.status)
+        .collect();
+    assert_eq!(statuses, [201, 200, 422, 404]);
+    assert_eq!(
+        response.results[1].user.as_ref().unwrap().name,
+        "bulk updated"
+    );
+    assert_eq!(response.results[3].id, Some(999999));
+
+    // The batch invalidated the cached user
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/id/{}", api_params.prefix, user.id))
+        .to_request();
+    let resp: UserModel = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp.name, "bulk updated");
+}
+
+#[actix_web::test]
+async fn test_bulk_users_limits() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+    let uri = format!("{}/users/bulk", api_params.prefix);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(json!({"operations": []}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let operation = json!({"op": "delete", "id": 1, "version": 1});
+    let operations = vec![operation; SETTINGS.bulk_max_operations + 1];
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(json!({"operations": operations}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let name = "x".repeat(SETTINGS.bulk_max_body_size);
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .set_json(
+            json!({"operations": [{"op": "create", "user": {"email": "a@b.co", "name": name}}]}),
+        )
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[actix_web::test]
+async fn test_export_users() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    // Every user of the test shares a tag to filter the export on
+    let tag = random_string(12);
+    let mut emails = vec![];
+    for index in 0..3 {
+        let user = UserCreate {
+            email: format!("{tag}{index}@example.com"),
+            name: format!("Export {index}"),
+            age: (index > 0).then_some(20 + index),
+        };
+        let req = test::TestRequest::post()
+            .uri(&format!("{}/users/", api_params.prefix))
+            .set_json(&user)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::CREATED
+        );
+        emails.push(user.email);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "{}/users/export?format=csv&search={tag}&age_min=21",
+            api_params.prefix
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/csv");
+    let body = test::read_body(resp).await;
+    let records = csv::parse(std::str::from_utf8(&body).unwrap()).unwrap();
+    assert_eq!(records[0].fields, EXPORT_COLUMNS);
+    let exported: Vec<&str> = records[1..]
+        .iter()
+        .map(|record| record.fields[1].as_str())
+        .collect();
+    assert_eq!(exported, [emails[1].as_str(), emails[2].as_str()]);
+    assert_eq!(records[1].fields[2], "Export 1");
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "{}/users/export?format=ndjson&search={tag}",
+            api_params.prefix
+        ))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let users: Vec<UserModel> = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(users.len(), 3);
+    assert!(users.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    for query in ["format=xml", "format=csv&sort=name", "format=csv&page=2"] {
+        let req = test::TestRequest::get()
+            .uri(&format!("{}/users/export?{query}", api_params.prefix))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[actix_web::test]
+async fn test_import_users() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str()))
+            .wrap(from_fn(authorize)),
+    )
+    .await;
+
+    let existing = create_random_user(true, Some(&api_params.db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let (first, second) = (random_email(), random_email());
+    let uri = format!("{}/users/import", api_params.prefix);
+
+    let invalid = format!(
+        "email,name,age\n{first},Ann,30\n{first},Ann again,\nnot-an-email,Bob,\n{},Taken,\n{second},Old,200\n",
+        existing.email
+    );
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("content-type", "text/csv"))
+        .set_payload(invalid)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let report: ImportReport = test::read_body_json(resp).await;
+    assert_eq!((report.total, report.valid, report.imported), (5, 1, 0));
+    let lines: Vec<(usize, Option<&str>)> = report
+        .errors
+        .iter()
+        .map(|error| (error.line, error.field.as_deref()))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (3, Some("email")),
+            (4, Some("email")),
+            (5, Some("email")),
+            (6, Some("age"))
+        ]
+    );
+
+    let valid = format!(
+        "{{\"email\":\"{first}\",\"name\":\"Ann\",\"age\":30}}\n{{\"email\":\"{second}\",\"name\":\"Bea\"}}\n"
+    );
+    let req = test::TestRequest::post()
+        .uri(&format!("{uri}?format=ndjson&dry_run=true"))
+        .set_payload(valid.clone())
+        .to_request();
+    let report: ImportReport = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        (report.dry_run, report.valid, report.imported),
+        (true, 2, 0)
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/email/{first}", api_params.prefix))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("content-type", "application/x-ndjson"))
+        .set_payload(valid)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: ImportReport = test::read_body_json(resp).await;
+    assert_eq!(report.imported, 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/users/email/{first}", api_params.prefix))
+        .to_request();
+    let user: UserModel = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((user.name.as_str(), user.age), ("Ann", Some(30)));
+
+    let req = test::TestRequest::post()
+        .uri(&uri)
+        .insert_header(("content-type", "text/plain"))
+        .set_payload("email,name\n")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
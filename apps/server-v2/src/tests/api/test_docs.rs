@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use actix_web::http::StatusCode;
+use actix_web::{self, App, HttpRequest, HttpResponse, test, web};
+use serde_json::{Value, json};
+
+use crate::api::main::{handler, openapi};
+use crate::tests::utils::api::TestAPIParameters;
+
+/// Every `$ref` in `value`, to check none of them dangles.
+fn refs<'a>(value: &'a Value, found: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(reference)) = map.get("$ref") {
+                found.push(reference);
+            }
+            map.values().for_each(|value| refs(value, found));
+        }
+        Value::Array(items) => items.iter().for_each(|value| refs(value, found)),
+        _ => {}
+    }
+}
+
+/// Full pattern of every resource registered in the app, by name. The route
+/// macros name resources after their handler, which the document uses as the
+/// `operationId`. `ResourceMap` can't be iterated, so the names are read from
+/// its `Debug` output and each one is resolved through the map itself.
+async fn registered_routes(req: HttpRequest) -> HttpResponse {
+    let rmap = req.resource_map();
+    let debug = format!("{rmap:?}");
+    let routes: BTreeMap<&str, String> = debug
+        .match_indices(": ResourceMap {")
+        .filter_map(|(index, _)| debug[..index].strip_suffix('"')?.rsplit_once('"'))
+        .map(|(_, name)| name)
+        .filter(|name| *name != "registered_routes")
+        .map(|name| {
+            let url = rmap
+                .url_for(&req, name, std::iter::repeat("0"))
+                .unwrap_or_else(|e| panic!("{name}: {e}"));
+            let pattern = rmap.match_pattern(url.path()).unwrap();
+            (name, pattern)
+        })
+        .collect();
+    HttpResponse::Ok().json(routes)
+}
+
+#[actix_web::test]
+async fn test_every_route_is_documented() {
+    let api_params = TestAPIParameters::new().await;
+    let prefix = api_params.prefix.as_str();
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(prefix))
+            .service(
+                web::resource("/routes")
+                    .name("registered_routes")
+                    .get(registered_routes),
+            ),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/routes").to_request();
+    let routes: BTreeMap<String, String> = test::call_and_read_body_json(&app, req).await;
+    assert!(routes.contains_key("get_users"), "{routes:?}");
+
+    let document = openapi(prefix);
+    let mut documented = BTreeMap::new();
+    for (path, item) in document["paths"].as_object().unwrap() {
+        for operation in item.as_object().unwrap().values() {
+            let id = operation["operationId"].as_str().unwrap().to_string();
+            assert!(
+                documented
+                    .insert(id.clone(), format!("{prefix}{path}"))
+                    .is_none(),
+                "{id} is documented twice"
+            );
+        }
+    }
+    assert_eq!(routes, documented);
+}
+
+#[actix_web::test]
+async fn test_openapi_document() {
+    let document = openapi("/api/v2");
+
+    assert_eq!(document["openapi"], "3.1.0");
+    assert_eq!(document["servers"][0]["url"], "/api/v2");
+
+    let paths = document["paths"].as_object().unwrap();
+    // Users need a bearer token or a scoped API key, issuing either needs the
+    // admin key instead
+    assert_eq!(
+        paths["/users/"]["get"]["security"],
+        json!([{"bearerAuth": []}, {"apiKeyAuth": ["users:read"]}])
+    );
+    assert_eq!(
+        paths["/users/"]["post"]["security"],
+        json!([{"bearerAuth": []}, {"apiKeyAuth": ["users:write"]}])
+    );
+    assert_eq!(
+        paths["/users/id/{id}"]["put"]["responses"]["403"]["description"],
+        "Needs role admin, operator, self (own record), or an API key with the `users:write` scope"
+    );
+    assert!(paths["/users/"]["get"]["responses"]["401"].is_object());
+    assert!(paths["/auth/token"]["post"]["security"].is_null());
+    assert!(paths["/api-keys/"]["post"]["security"].is_null());
+    assert_eq!(
+        document["components"]["securitySchemes"]["apiKeyAuth"]["name"],
+        "X-API-Key"
+    );
+    assert_eq!(
+        document["components"]["securitySchemes"]["bearerAuth"]["scheme"],
+        "bearer"
+    );
+
+    let schemas = document["components"]["schemas"].as_object().unwrap();
+    for name in [
+        "User",
+        "UserCreate",
+        "UserUpdate",
+        "ErrorResponse",
+        "PaginatedUser",
+    ] {
+        assert!(schemas.contains_key(name), "{name}");
+    }
+    let user = &schemas["User"];
+    assert!(user["properties"]["version"].is_object());
+    assert!(user["properties"]["password_hash"].is_null());
+    let api_key = &schemas["ApiKey"];
+    assert!(api_key["properties"]["scopes"].is_object());
+    assert!(api_key["properties"]["key_hash"].is_null());
+    assert!(api_key["properties"]["key_salt"].is_null());
+    // PUT needs the nullable fields too, if only as null
+    assert_eq!(
+        schemas["UserUpdate"]["required"],
+        serde_json::json!(["email", "name", "age", "is_active"])
+    );
+
+    let mut found = vec![];
+    refs(&document, &mut found);
+    for reference in found {
+        let name = reference
+            .strip_prefix("#/components/schemas/")
+            .unwrap_or_else(|| panic!("unexpected $ref {reference}"));
+        assert!(schemas.contains_key(name), "dangling $ref {reference}");
+    }
+
+    // Query parameters come from the handlers' query structs
+    let parameters = document["paths"]["/users/"]["get"]["parameters"]
+        .as_array()
+        .unwrap();
+    let names: Vec<&str> = parameters
+        .iter()
+        .filter_map(|parameter| parameter["name"].as_str())
+        .collect();
+    assert!(names.contains(&"age_min") && names.contains(&"after"));
+}
+
+#[actix_web::test]
+async fn test_docs_are_served() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/openapi.json", api_params.prefix))
+        .to_request();
+    let document: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(document["openapi"], "3.1.0");
+
+    for (page, marker) in [("docs", "swagger-ui"), ("redoc", "<redoc")] {
+        let req = test::TestRequest::get()
+            .uri(&format!("{}/{page}", api_params.prefix))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains(marker) && body.contains("openapi.json"));
+        assert!(!body.contains("latest"), "{page} loads an unpinned bundle");
+    }
+
+    // The root advertises the pages where they are
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/", api_params.prefix))
+        .to_request();
+    let root: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(root["docs"], format!("{}/docs", api_params.prefix));
+    assert_eq!(root["redoc"], format!("{}/redoc", api_params.prefix));
+}
@@ -0,0 +1,54 @@
+use actix_web::http::StatusCode;
+use actix_web::{self, App, test};
+
+use crate::api::main::handler;
+use crate::core::health::{HealthReport, HealthStatus};
+use crate::tests::utils::api::TestAPIParameters;
+
+#[actix_web::test]
+async fn test_health_check() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .app_data(api_params.health_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/health", api_params.prefix))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    let status = resp.status();
+    let report: HealthReport = test::read_body_json(resp).await;
+
+    // The test database is always reachable; QuestDB may not be
+    assert_eq!(report.database.status, HealthStatus::Healthy);
+    assert!(report.database.error.is_none());
+    assert_eq!(status, report.get_status_code());
+    if report.status == HealthStatus::Healthy {
+        assert_eq!(status, StatusCode::OK);
+    } else {
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
+
+#[actix_web::test]
+async fn test_liveness_check() {
+    let api_params = TestAPIParameters::new().await;
+    let app = test::init_service(
+        App::new()
+            .app_data(api_params.app_data.clone())
+            .service(handler(api_params.prefix.as_str())),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/health/live", api_params.prefix))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
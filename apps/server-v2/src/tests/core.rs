@@ -0,0 +1,13 @@
+pub mod test_api_keys;
+pub mod test_cache;
+pub mod test_config;
+pub mod test_csv;
+pub mod test_errors;
+pub mod test_health;
+pub mod test_jwt;
+pub mod test_password;
+pub mod test_patch;
+pub mod test_permissions;
+pub mod test_rate_limit;
+pub mod test_telemetry;
+pub mod test_validation;
@@ -0,0 +1,44 @@
+use crate::core::api_keys::{ApiKey, KEY_MARKER};
+
+#[test]
+fn test_generate_and_parse() {
+    let key = ApiKey::generate();
+    let exposed = key.expose();
+    assert!(exposed.starts_with(&format!("{KEY_MARKER}_{}_", key.prefix)));
+    assert_eq!(key.prefix.len(), 12);
+    assert_eq!(ApiKey::parse(&exposed), Some(key.clone()));
+    assert_ne!(ApiKey::generate().expose(), exposed);
+
+    for malformed in [
+        "",
+        "umk",
+        "umk_0123456789ab",
+        "umk_0123456789ab_",
+        "xyz_0123456789ab_secret",
+        "umk_0123456789_secret",
+        "umk_0123456789zz_secret",
+    ] {
+        assert!(ApiKey::parse(malformed).is_none(), "{malformed}");
+    }
+}
+
+#[test]
+fn test_hash_and_verify() {
+    let key = ApiKey::generate();
+    let salt = ApiKey::new_salt();
+    let hash = key.hash(&salt);
+    assert!(key.verify(&salt, &hash));
+    assert!(!key.verify(&ApiKey::new_salt(), &hash));
+    assert!(!ApiKey::generate().verify(&salt, &hash));
+    assert!(!key.verify(&salt, "not base64!"));
+    // Same key, other salt: other hash
+    assert_ne!(key.hash(&ApiKey::new_salt()), hash);
+}
+
+#[test]
+fn test_debug_hides_the_secret() {
+    let key = ApiKey::generate();
+    let debug = format!("{key:?}");
+    assert!(debug.contains(&key.prefix));
+    assert!(!debug.contains(&key.expose()));
+}
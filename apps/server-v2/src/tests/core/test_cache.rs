@@ -0,0 +1,48 @@
+use crate::core::cache::REDIS_SERVICE;
+use crate::tests::utils::random::random_string;
+
+#[tokio::test]
+async fn test_set_get_delete() {
+    let key = format!("test:{}", random_string(16));
+    REDIS_SERVICE
+        .set(key.as_str(), "value")
+        .await
+        .expect("set should succeed");
+
+    assert_eq!(
+        REDIS_SERVICE.get(key.as_str()).await,
+        Some("value".to_string())
+    );
+
+    REDIS_SERVICE
+        .delete(key.as_str())
+        .await
+        .expect("delete should succeed");
+    assert_eq!(REDIS_SERVICE.get(key.as_str()).await, None);
+}
+
+#[tokio::test]
+async fn test_delete_pattern() {
+    let prefix = format!("test:{}", random_string(16));
+    for i in 0..3 {
+        REDIS_SERVICE
+            .set(format!("{prefix}:{i}").as_str(), "value")
+            .await
+            .expect("set should succeed");
+    }
+
+    let deleted = REDIS_SERVICE
+        .delete_pattern(format!("{prefix}:*").as_str())
+        .await;
+    assert_eq!(deleted, 3);
+    assert_eq!(
+        REDIS_SERVICE.get(format!("{prefix}:0").as_str()).await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_ping() {
+    let pong = REDIS_SERVICE.ping().await.expect("ping should succeed");
+    assert_eq!(pong, "PONG");
+}
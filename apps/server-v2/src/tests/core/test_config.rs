@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::core::config::{ConfigSources, ConfigValue, Profile, Secret, Settings};
+use crate::core::jwt::JwtAlgorithm;
+
+fn sources(env: &[(&str, &str)], file: &[(&str, &str)]) -> ConfigSources {
+    let to_map = |entries: &[(&str, &str)]| -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    ConfigSources {
+        env: to_map(env),
+        file: to_map(file),
+    }
+}
+
+#[test]
+fn test_defaults() {
+    let settings = Settings::from_sources(&sources(&[], &[])).expect("defaults are valid");
+    assert_eq!(settings.profile, Profile::Development);
+    assert_eq!(settings.postgres_port, 5432);
+    assert_eq!(settings.redis_port, 6379);
+    assert_eq!(settings.database_connect_timeout, Duration::from_secs(8));
+    assert!(settings.postgres_password.is_none());
+    // Outside production a missing key is generated
+    assert!(!settings.secret_key.expose().is_empty());
+}
+
+#[test]
+fn test_env_overrides_file() {
+    let settings = Settings::from_sources(&sources(
+        &[("POSTGRES_PORT", "5434")],
+        &[("postgres.port", "5433"), ("redis.port", "6380")],
+    ))
+    .expect("configuration is valid");
+    assert_eq!(settings.postgres_port, 5434);
+    assert_eq!(settings.redis_port, 6380);
+}
+
+#[test]
+fn test_reports_every_problem() {
+    let result = Settings::from_sources(&sources(
+        &[
+            ("POSTGRES_PORT", "not a port"),
+            ("REDIS_PORT", "0"),
+            ("DATABASE_URL", "mysql://localhost/db"),
+        ],
+        &[("questdb.flush_interval", "soon")],
+    ));
+    let report = result.expect_err("configuration is invalid");
+
+    assert_eq!(report.errors.len(), 4);
+    let message = report.to_string();
+    assert!(message.contains("POSTGRES_PORT"));
+    assert!(message.contains("REDIS_PORT"));
+    assert!(message.contains("DATABASE_URL"));
+    assert!(message.contains("questdb.flush_interval"));
+}
+
+#[test]
+fn test_production_requires_secrets() {
+    let result = Settings::from_sources(&sources(&[("APP_ENV", "production")], &[]));
+    let report = result.expect_err("secrets are missing");
+
+    assert_eq!(report.errors.len(), 2);
+    assert!(report.errors.iter().any(|e| e.starts_with("SECRET_KEY")));
+    assert!(
+        report
+            .errors
+            .iter()
+            .any(|e| e.starts_with("POSTGRES_PASSWORD"))
+    );
+
+    let settings = Settings::from_sources(&sources(
+        &[
+            ("APP_ENV", "production"),
+            ("SECRET_KEY", "secret-key"),
+            ("POSTGRES_PASSWORD", "password"),
+        ],
+        &[],
+    ))
+    .expect("configuration is valid");
+    assert_eq!(settings.profile, Profile::Production);
+    assert_eq!(settings.secret_key.expose(), "secret-key");
+}
+
+#[test]
+fn test_duration_values() {
+    assert_eq!(
+        Duration::parse_value("500ms"),
+        Ok(Duration::from_millis(500))
+    );
+    assert_eq!(Duration::parse_value("8s"), Ok(Duration::from_secs(8)));
+    assert_eq!(Duration::parse_value("8"), Ok(Duration::from_secs(8)));
+    assert_eq!(Duration::parse_value("10m"), Ok(Duration::from_secs(600)));
+    assert!(Duration::parse_value("8 days").is_err());
+    assert!(Duration::parse_value("ms").is_err());
+    assert!(Duration::parse_value("18446744073709551615h").is_err());
+}
+
+#[test]
+fn test_secret_is_redacted() {
+    let secret = Secret::new("password");
+    assert_eq!(format!("{secret:?}"), "Secret(********)");
+    assert_eq!(format!("{secret}"), "********");
+    assert_eq!(secret.expose(), "password");
+}
+
+#[test]
+fn test_jwt_settings() {
+    let settings = Settings::from_sources(&sources(
+        &[("JWT_ALGORITHM", "es256k"), ("JWT_AUDIENCE", "users")],
+        &[("auth.jwt_expiry", "15m")],
+    ))
+    .expect("a generated key is a secp256k1 key");
+    assert_eq!(settings.jwt_algorithm, JwtAlgorithm::Es256k);
+    assert_eq!(settings.jwt_expiry, Duration::from_secs(900));
+    assert_eq!(settings.jwt_audience.as_deref(), Some("users"));
+    assert!(settings.jwt_issuer.is_none());
+
+    let result = Settings::from_sources(&sources(
+        &[("JWT_ALGORITHM", "ES256K"), ("SECRET_KEY", "not-hex")],
+        &[],
+    ));
+    let report = result.expect_err("ES256K needs a hex key").to_string();
+    assert!(report.contains("JWT_ALGORITHM"), "{report}");
+
+    let result = Settings::from_sources(&sources(&[("JWT_ALGORITHM", "RS256")], &[]));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_password_settings() {
+    let settings = Settings::from_sources(&sources(
+        &[("PASSWORD_REQUIRE_DIGIT", "true")],
+        &[
+            ("auth.password_min_length", "16"),
+            ("auth.argon2_time_cost", "3"),
+        ],
+    ))
+    .expect("configuration is valid");
+    assert_eq!(settings.password_min_length, 16);
+    assert!(settings.password_require_digit);
+    assert_eq!(settings.argon2_time_cost, 3);
+
+    let result = Settings::from_sources(&sources(
+        &[
+            ("PASSWORD_MIN_LENGTH", "200"),
+            ("PASSWORD_MAX_LENGTH", "100"),
+            ("ARGON2_MEMORY_COST", "1"),
+        ],
+        &[],
+    ));
+    let report = result.expect_err("invalid password settings").to_string();
+    assert!(report.contains("PASSWORD_MIN_LENGTH"), "{report}");
+    assert!(report.contains("ARGON2_MEMORY_COST"), "{report}");
+}
+
+#[test]
+fn test_rate_limit_settings() {
+    let settings = Settings::from_sources(&sources(
+        &[("RATE_LIMIT_USERS", "100/10s")],
+        &[("rate_limit.enabled", "false")],
+    ))
+    .expect("configuration is valid");
+    assert!(!settings.rate_limit_enabled);
+    assert_eq!(settings.rate_limit_users.limit, 100);
+    assert_eq!(settings.rate_limit_users.window, Duration::from_secs(10));
+    assert_eq!(settings.rate_limit_auth.limit, 10);
+
+    let result = Settings::from_sources(&sources(&[("RATE_LIMIT_AUTH", "10")], &[]));
+    let report = result.expect_err("invalid rate limit").to_string();
+    assert!(report.contains("RATE_LIMIT_AUTH"), "{report}");
+}
+
+#[test]
+fn test_tracing_settings() {
+    let settings = Settings::from_sources(&sources(&[], &[])).expect("configuration is valid");
+    assert_eq!(settings.tracing_otlp_endpoint, None);
+    assert_eq!(settings.tracing_service_name, "user-management-api");
+
+    let settings = Settings::from_sources(&sources(
+        &[("OTEL_EXPORTER_OTLP_ENDPOINT", "http://collector:4318")],
+        &[("tracing.service_name", "users")],
+    ))
+    .expect("configuration is valid");
+    assert_eq!(
+        settings.tracing_otlp_endpoint.unwrap().as_str(),
+        "http://collector:4318/"
+    );
+    assert_eq!(settings.tracing_service_name, "users");
+
+    let result = Settings::from_sources(&sources(
+        &[("OTEL_EXPORTER_OTLP_ENDPOINT", "grpc://collector:4317")],
+        &[],
+    ));
+    let report = result.expect_err("invalid endpoint").to_string();
+    assert!(report.contains("OTEL_EXPORTER_OTLP_ENDPOINT"), "{report}");
+}
+
+#[test]
+fn test_rejects_zero_where_needed() {
+    // 0 parses, a minimum of 0 connections is fine
+    let settings = Settings::from_sources(&sources(&[("DATABASE_MIN_CONNECTIONS", "0")], &[]))
+        .expect("configuration is valid");
+    assert_eq!(settings.database_min_connections, 0);
+
+    let result = Settings::from_sources(&sources(
+        &[
+            ("MAX_PAGE_SIZE", "0"),
+            ("BULK_MAX_OPERATIONS", "0"),
+            ("BULK_MAX_BODY_SIZE", "0"),
+            ("QUESTDB_BATCH_SIZE", "0"),
+            ("QUESTDB_QUEUE_CAPACITY", "0"),
+            ("DATABASE_MAX_CONNECTIONS", "0"),
+            ("DATABASE_MIN_CONNECTIONS", "0"),
+        ],
+        &[],
+    ));
+    let report = result.expect_err("zero sizes are invalid");
+    assert_eq!(report.errors.len(), 6, "{report}");
+
+    let result = Settings::from_sources(&sources(&[("BULK_MAX_OPERATIONS", "-1")], &[]));
+    let report = result.expect_err("negative sizes are invalid").to_string();
+    assert!(report.contains("non-negative integer"), "{report}");
+}
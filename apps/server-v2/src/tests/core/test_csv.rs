@@ -0,0 +1,85 @@
+use crate::core::csv::{CsvRecord, parse, write_record};
+use crate::schemas::api::DataFormat;
+use crate::schemas::users::read_import;
+
+#[test]
+fn test_write_record() {
+    assert_eq!(write_record(&["a", "b", ""]), "a,b,\r\n");
+    assert_eq!(
+        write_record(&["O'Hara, Jr.", "say \"hi\"", "two\nlines"]),
+        "\"O'Hara, Jr.\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n"
+    );
+}
+
+#[test]
+fn test_parse_round_trip() {
+    let fields = ["O'Hara, Jr.", "say \"hi\"", "two\r\nlines", ""];
+    let input = write_record(&["first"]) + &write_record(&fields);
+    let records = parse(&input).unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].fields, fields);
+}
+
+#[test]
+fn test_parse_line_numbers() {
+    // Blank lines are skipped, quoted line breaks don't start a record
+    let input = "email,name\n\na@b.co,\"multi\nline\"\nc@d.co,last";
+    let records = parse(input).unwrap();
+
+    assert_eq!(
+        records,
+        [
+            CsvRecord {
+                line: 1,
+                fields: vec!["email".to_string(), "name".to_string()],
+            },
+            CsvRecord {
+                line: 3,
+                fields: vec!["a@b.co".to_string(), "multi\nline".to_string()],
+            },
+            CsvRecord {
+                line: 5,
+                fields: vec!["c@d.co".to_string(), "last".to_string()],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_stray_quotes() {
+    // Quotes inside an unquoted field are kept as text
+    let records = parse("a,b\nc,d\"e\n").unwrap();
+    assert_eq!(records[1].fields, ["c", "d\"e"]);
+
+    // An unterminated quote swallows the rest of the input into one field,
+    // which then fails the field count check of an import
+    let records = parse("a,b\nc,\"unterminated\nd,e\n").unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].line, 2);
+    assert_eq!(records[1].fields, ["c", "unterminated\nd,e\n"]);
+}
+
+#[test]
+fn test_read_import() {
+    let csv = "id,Email,name,age\n1,a@b.co,Ann,30\n2,c@d.co,Bob,old\n3,e@f.co\n4,g@h.co,Gus,\n";
+    let (rows, errors) = read_import(DataFormat::Csv, csv);
+
+    assert_eq!(rows.iter().map(|row| row.line).collect::<Vec<_>>(), [2, 5]);
+    assert_eq!(rows[0].user.age, Some(30));
+    assert_eq!(rows[1].user.age, None);
+    assert_eq!(
+        errors.iter().map(|error| error.line).collect::<Vec<_>>(),
+        [3, 4]
+    );
+    assert_eq!(errors[0].field.as_deref(), Some("age"));
+
+    let (rows, errors) = read_import(DataFormat::Csv, "email,age\na@b.co,1\n");
+    assert!(rows.is_empty());
+    assert_eq!(errors[0].line, 1);
+
+    let ndjson = "{\"email\":\"a@b.co\",\"name\":\"Ann\",\"id\":4}\n\n{\"email\":\"c@d.co\"}\n";
+    let (rows, errors) = read_import(DataFormat::Ndjson, ndjson);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(errors[0].line, 3);
+}
@@ -0,0 +1,118 @@
+use actix_web::ResponseError;
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::http::header::WWW_AUTHENTICATE;
+use sea_orm::DbErr;
+
+use crate::core::errors::AppError;
+use crate::schemas::api::{ErrorResponse, FieldError};
+
+#[test]
+fn test_status_codes() {
+    let cases = [
+        (AppError::BadRequest(String::new()), StatusCode::BAD_REQUEST),
+        (AppError::NotFound(String::new()), StatusCode::NOT_FOUND),
+        (AppError::Conflict(String::new()), StatusCode::CONFLICT),
+        (
+            AppError::Duplicate(FieldError {
+                field: String::new(),
+                message: String::new(),
+            }),
+            StatusCode::CONFLICT,
+        ),
+        (
+            AppError::Validation(String::new()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            AppError::Unauthorized(String::new()),
+            StatusCode::UNAUTHORIZED,
+        ),
+        (
+            AppError::InvalidToken(String::new()),
+            StatusCode::UNAUTHORIZED,
+        ),
+        (AppError::Forbidden(String::new()), StatusCode::FORBIDDEN),
+        (
+            AppError::PayloadTooLarge(String::new()),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ),
+        (
+            AppError::TooManyRequests(String::new()),
+            StatusCode::TOO_MANY_REQUESTS,
+        ),
+        (
+            AppError::Unavailable(String::new()),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ),
+        (
+            AppError::Internal(String::new()),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    ];
+
+    for (error, status) in cases {
+        assert_eq!(error.status_code(), status, "{}", error.kind());
+        assert_eq!(error.to_response().get_status_code(), status);
+    }
+}
+
+#[test]
+fn test_error_at_location() {
+    let error = AppError::NotFound("User with ID 7 not found".to_string()).at("operations[2]");
+    assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(error.message(), "operations[2]: User with ID 7 not found");
+
+    let error = AppError::InvalidFields(vec![FieldError {
+        field: "email".to_string(),
+        message: "must be a valid email address".to_string(),
+    }])
+    .at("operations[0]");
+    assert_eq!(error.fields()[0].field, "operations[0].email");
+}
+
+#[test]
+fn test_from_db_err() {
+    let error = AppError::from(DbErr::RecordNotFound("User not found".to_string()));
+    assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+
+    let error = AppError::from(DbErr::Custom("boom".to_string()));
+    assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn test_from_redis_error() {
+    let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+    let error = AppError::from(redis::RedisError::from(io_error));
+    assert_eq!(error.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_web::test]
+async fn test_error_response_body() {
+    let response = AppError::Conflict("Email already taken".to_string()).error_response();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let body = to_bytes(response.into_body()).await.unwrap();
+    let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.status_code, 409);
+    assert_eq!(body.message, "Email already taken");
+    assert_eq!(body.error.as_deref(), Some("conflict"));
+}
+
+#[test]
+fn test_unauthorized_challenge() {
+    let response = AppError::Unauthorized("Missing bearer token".to_string()).error_response();
+    assert_eq!(
+        response.headers().get(WWW_AUTHENTICATE).unwrap(),
+        "Bearer realm=\"api\""
+    );
+
+    let response = AppError::InvalidToken("Token has expired".to_string()).error_response();
+    assert_eq!(
+        response.headers().get(WWW_AUTHENTICATE).unwrap(),
+        "Bearer realm=\"api\", error=\"invalid_token\", error_description=\"Token has expired\""
+    );
+
+    let response = AppError::Forbidden("Invalid admin key".to_string()).error_response();
+    assert!(!response.headers().contains_key(WWW_AUTHENTICATE));
+}
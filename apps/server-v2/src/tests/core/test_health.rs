@@ -0,0 +1,48 @@
+use actix_web::http::StatusCode;
+
+use crate::core::health::{DependencyHealth, HealthReport, HealthStatus};
+
+fn dependency(status: HealthStatus, critical: bool) -> DependencyHealth {
+    DependencyHealth {
+        status,
+        critical,
+        latency_ms: 1.0,
+        error: match status {
+            HealthStatus::Healthy => None,
+            _ => Some("connection refused".to_string()),
+        },
+    }
+}
+
+#[test]
+fn test_all_healthy() {
+    let report = HealthReport::new(
+        dependency(HealthStatus::Healthy, true),
+        dependency(HealthStatus::Healthy, false),
+        dependency(HealthStatus::Healthy, false),
+    );
+    assert_eq!(report.status, HealthStatus::Healthy);
+    assert_eq!(report.get_status_code(), StatusCode::OK);
+}
+
+#[test]
+fn test_optional_dependency_down_is_degraded() {
+    let report = HealthReport::new(
+        dependency(HealthStatus::Healthy, true),
+        dependency(HealthStatus::Unhealthy, false),
+        dependency(HealthStatus::Healthy, false),
+    );
+    assert_eq!(report.status, HealthStatus::Degraded);
+    assert_eq!(report.get_status_code(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[test]
+fn test_critical_dependency_down_is_unhealthy() {
+    let report = HealthReport::new(
+        dependency(HealthStatus::Unhealthy, true),
+        dependency(HealthStatus::Unhealthy, false),
+        dependency(HealthStatus::Healthy, false),
+    );
+    assert_eq!(report.status, HealthStatus::Unhealthy);
+    assert_eq!(report.get_status_code(), StatusCode::SERVICE_UNAVAILABLE);
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+use crate::core::config::Secret;
+use crate::core::errors::AppError;
+use crate::core::jwt::{Audience, Jwt, JwtAlgorithm};
+
+const SECP256K1_KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+fn jwt(algorithm: JwtAlgorithm, key: &str) -> Jwt {
+    Jwt::new(
+        algorithm,
+        &Secret::new(key),
+        Duration::from_secs(60),
+        Some("issuer".to_string()),
+        Some("audience".to_string()),
+        Duration::from_secs(5),
+    )
+    .unwrap()
+}
+
+fn rejection(jwt: &Jwt, token: &str) -> String {
+    match jwt.decode(token) {
+        Err(AppError::InvalidToken(message)) => message,
+        other => panic!("expected an invalid token, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    for (algorithm, key) in [
+        (JwtAlgorithm::Hs256, "secret-key"),
+        (JwtAlgorithm::Es256k, SECP256K1_KEY),
+    ] {
+        let jwt = jwt(algorithm, key);
+        let claims = jwt.claims("alice");
+        let token = jwt.encode(&claims).unwrap();
+
+        let header = token.split('.').next().unwrap();
+        let header: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).unwrap()).unwrap();
+        assert_eq!(header["alg"], algorithm.as_str());
+
+        assert_eq!(jwt.decode(&token).unwrap(), claims);
+        assert_eq!(claims.exp - claims.iat, 60);
+        assert_eq!(claims.aud, Some(Audience::One("audience".to_string())));
+    }
+}
+
+#[test]
+fn test_rejects_expired() {
+    let jwt = jwt(JwtAlgorithm::Hs256, "secret-key");
+    let mut claims = jwt.claims("alice");
+    claims.exp = claims.iat - 10;
+    let token = jwt.encode(&claims).unwrap();
+    assert_eq!(rejection(&jwt, &token), "Token has expired");
+
+    // Within the leeway
+    claims.exp = claims.iat - 2;
+    assert!(jwt.decode(&jwt.encode(&claims).unwrap()).is_ok());
+
+    claims.exp = claims.iat + 60;
+    claims.nbf = Some(claims.iat + 30);
+    let token = jwt.encode(&claims).unwrap();
+    assert_eq!(rejection(&jwt, &token), "Token is not valid yet");
+}
+
+#[test]
+fn test_rejects_wrong_issuer_and_audience() {
+    let jwt = jwt(JwtAlgorithm::Hs256, "secret-key");
+    let mut claims = jwt.claims("alice");
+    claims.iss = Some("someone-else".to_string());
+    assert_eq!(
+        rejection(&jwt, &jwt.encode(&claims).unwrap()),
+        "Unexpected issuer"
+    );
+
+    let mut claims = jwt.claims("alice");
+    claims.aud = None;
+    assert_eq!(
+        rejection(&jwt, &jwt.encode(&claims).unwrap()),
+        "Token is not meant for this audience"
+    );
+
+    claims.aud = Some(Audience::Many(vec![
+        "other".to_string(),
+        "audience".to_string(),
+    ]));
+    assert!(jwt.decode(&jwt.encode(&claims).unwrap()).is_ok());
+}
+
+#[test]
+fn test_rejects_other_keys_and_algorithms() {
+    let hs256 = jwt(JwtAlgorithm::Hs256, "secret-key");
+    let es256k = jwt(JwtAlgorithm::Es256k, SECP256K1_KEY);
+    let claims = hs256.claims("alice");
+
+    let token = jwt(JwtAlgorithm::Hs256, "another-key")
+        .encode(&claims)
+        .unwrap();
+    assert_eq!(rejection(&hs256, &token), "Invalid signature");
+
+    let token = hs256.encode(&claims).unwrap();
+    assert_eq!(rejection(&es256k, &token), "Unexpected signing algorithm");
+
+    // An unsigned token must not pass for a signed one
+    let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"none"}"#);
+    let payload = token.split('.').nth(1).unwrap();
+    let unsigned = format!("{header}.{payload}.");
+    assert_eq!(rejection(&hs256, &unsigned), "Unexpected signing algorithm");
+}
+
+#[test]
+fn test_rejects_tampered_and_malformed_tokens() {
+    for (algorithm, key) in [
+        (JwtAlgorithm::Hs256, "secret-key"),
+        (JwtAlgorithm::Es256k, SECP256K1_KEY),
+    ] {
+        let jwt = jwt(algorithm, key);
+        let token = jwt.encode(&jwt.claims("alice")).unwrap();
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let forged = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&jwt.claims("mallory")).unwrap());
+        parts[1] = &forged;
+        assert_eq!(rejection(&jwt, &parts.join(".")), "Invalid signature");
+
+        assert_eq!(rejection(&jwt, "not-a-token"), "Malformed token");
+        assert_eq!(
+            rejection(&jwt, &format!("{token}.extra")),
+            "Malformed token"
+        );
+    }
+}
+
+#[test]
+fn test_es256k_needs_a_secp256k1_key() {
+    let result = Jwt::new(
+        JwtAlgorithm::Es256k,
+        &Secret::new("secret-key"),
+        Duration::from_secs(60),
+        None,
+        None,
+        Duration::ZERO,
+    );
+    assert!(result.is_err());
+    assert_eq!("es256k".parse::<JwtAlgorithm>(), Ok(JwtAlgorithm::Es256k));
+    assert!("none".parse::<JwtAlgorithm>().is_err());
+}
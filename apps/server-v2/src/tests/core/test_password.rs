@@ -0,0 +1,76 @@
+use crate::core::password::{PasswordPolicy, Passwords};
+
+fn policy() -> PasswordPolicy {
+    PasswordPolicy {
+        min_length: 12,
+        max_length: 64,
+        require_mixed_case: false,
+        require_digit: false,
+        require_symbol: false,
+    }
+}
+
+#[test]
+fn test_policy_length() {
+    let policy = policy();
+    assert!(policy.check("correct horse battery").is_none());
+    assert!(policy.check("too short").is_some());
+    assert!(policy.check(&"a".repeat(65)).is_some());
+    assert!(policy.check(&" ".repeat(12)).is_some());
+    // Characters, not bytes
+    assert!(policy.check("ééééééééééé").is_some());
+    assert!(policy.check("éééééééééééé").is_none());
+}
+
+#[test]
+fn test_policy_character_classes() {
+    let policy = PasswordPolicy {
+        require_mixed_case: true,
+        require_digit: true,
+        require_symbol: true,
+        ..policy()
+    };
+    assert_eq!(
+        policy.check("correct horse battery").as_deref(),
+        Some("must mix lowercase and uppercase letters")
+    );
+    assert_eq!(
+        policy.check("Correct horse battery").as_deref(),
+        Some("must contain a digit")
+    );
+    assert_eq!(
+        policy.check("Correcthorsebattery9").as_deref(),
+        Some("must contain a symbol")
+    );
+    assert!(policy.check("Correct horse battery 9").is_none());
+}
+
+#[test]
+fn test_hash_and_verify() {
+    let passwords = Passwords::new(1024, 1, 1).unwrap();
+    let hash = passwords.hash("correct horse battery").unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"), "{hash}");
+    assert!(passwords.verify("correct horse battery", &hash));
+    assert!(!passwords.verify("wrong horse battery", &hash));
+    assert!(!passwords.verify("correct horse battery", "not a hash"));
+
+    // Salted: the same password never hashes the same
+    assert_ne!(hash, passwords.hash("correct horse battery").unwrap());
+}
+
+#[test]
+fn test_needs_rehash() {
+    let cheap = Passwords::new(1024, 1, 1).unwrap();
+    let costly = Passwords::new(2048, 2, 1).unwrap();
+    let hash = cheap.hash("correct horse battery").unwrap();
+    assert!(!cheap.needs_rehash(&hash));
+    assert!(costly.needs_rehash(&hash));
+    // Older hashes stay valid under new parameters
+    assert!(costly.verify("correct horse battery", &hash));
+}
+
+#[test]
+fn test_invalid_parameters() {
+    assert!(Passwords::new(1, 1, 1).is_err());
+    assert!(Passwords::new(1024, 0, 1).is_err());
+}
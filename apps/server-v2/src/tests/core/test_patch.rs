@@ -0,0 +1,92 @@
+use serde_json::json;
+
+use crate::core::errors::AppError;
+use crate::core::patch::{PatchErrorKind, PatchOperation, json_patch, merge_patch};
+
+fn operations(value: serde_json::Value) -> Vec<PatchOperation> {
+    serde_json::from_value(value).expect("valid operations")
+}
+
+#[test]
+fn test_merge_patch() {
+    // Example from RFC 7396, section 3
+    let mut target = json!({
+        "title": "Goodbye!",
+        "author": {"givenName": "John", "familyName": "Doe"},
+        "tags": ["example", "sample"],
+        "content": "This will be unchanged"
+    });
+    let patch = json!({
+        "title": "Hello!",
+        "phoneNumber": "+01-123-456-7890",
+        "author": {"familyName": null},
+        "tags": ["example"]
+    });
+    merge_patch(&mut target, &patch);
+
+    assert_eq!(
+        target,
+        json!({
+            "title": "Hello!",
+            "author": {"givenName": "John"},
+            "tags": ["example"],
+            "content": "This will be unchanged",
+            "phoneNumber": "+01-123-456-7890"
+        })
+    );
+}
+
+#[test]
+fn test_json_patch() {
+    let mut document = json!({"name": "John", "age": 30, "tags": ["a", "b"]});
+    let operations = operations(json!([
+        {"op": "test", "path": "/name", "value": "John"},
+        {"op": "replace", "path": "/name", "value": "Jane"},
+        {"op": "remove", "path": "/age"},
+        {"op": "add", "path": "/tags/-", "value": "c"},
+        {"op": "move", "from": "/tags/0", "path": "/first"},
+        {"op": "copy", "from": "/name", "path": "/nickname"}
+    ]));
+    json_patch(&mut document, &operations).expect("patch should apply");
+
+    assert_eq!(
+        document,
+        json!({"name": "Jane", "tags": ["b", "c"], "first": "a", "nickname": "Jane"})
+    );
+}
+
+#[test]
+fn test_json_patch_is_atomic() {
+    let original = json!({"name": "John", "age": 30});
+    let mut document = original.clone();
+    let operations = operations(json!([
+        {"op": "replace", "path": "/name", "value": "Jane"},
+        {"op": "test", "path": "/age", "value": 31}
+    ]));
+
+    let error = json_patch(&mut document, &operations).unwrap_err();
+    assert!(matches!(error.kind, PatchErrorKind::TestFailed));
+    assert_eq!(document, original);
+    // A failed test means the client's view is stale
+    assert!(matches!(AppError::from(error), AppError::Conflict(_)));
+}
+
+#[test]
+fn test_json_patch_invalid_paths() {
+    let mut document = json!({"tags": ["a"]});
+    for operation in operations(json!([
+        {"op": "remove", "path": "/missing"},
+        {"op": "add", "path": "/tags/5", "value": "b"},
+        {"op": "move", "from": "/tags", "path": "/tags/0"}
+    ])) {
+        let error = json_patch(&mut document, std::slice::from_ref(&operation)).unwrap_err();
+        assert!(
+            matches!(AppError::from(error), AppError::Validation(_)),
+            "{operation:?}"
+        );
+    }
+    // Pointers must be empty or start with `/`
+    assert!(
+        serde_json::from_value::<PatchOperation>(json!({"op": "remove", "path": "tags"})).is_err()
+    );
+}
@@ -0,0 +1,90 @@
+use crate::core::permissions::Permission;
+use crate::models::users::Role;
+use crate::schemas::api_keys::Scope;
+
+const PERMISSIONS: [Permission; 6] = [
+    Permission::ListUsers,
+    Permission::ReadUser,
+    Permission::CreateUsers,
+    Permission::UpdateUser,
+    Permission::DeleteUsers,
+    Permission::ManageAccess,
+];
+
+/// Expected grants, one row per permission: admin, operator, viewer, self
+/// on another user's record, self on their own record.
+const MATRIX: [[bool; 5]; 6] = [
+    [true, true, true, false, false],
+    [true, true, true, false, true],
+    [true, true, false, false, false],
+    [true, true, false, false, true],
+    [true, false, false, false, false],
+    [true, false, false, false, false],
+];
+
+#[test]
+fn test_role_matrix() {
+    for (permission, expected) in PERMISSIONS.iter().zip(MATRIX) {
+        let granted = [
+            permission.granted_to(Role::Admin, false),
+            permission.granted_to(Role::Operator, false),
+            permission.granted_to(Role::Viewer, false),
+            permission.granted_to(Role::SelfOnly, false),
+            permission.granted_to(Role::SelfOnly, true),
+        ];
+        assert_eq!(granted, expected, "{permission:?}");
+        // Owning the record never takes a permission away
+        for role in [Role::Admin, Role::Operator, Role::Viewer] {
+            assert_eq!(
+                permission.granted_to(role, true),
+                permission.granted_to(role, false),
+                "{permission:?} {role}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_denial_reasons() {
+    assert_eq!(Permission::ReadUser.denial(Role::Viewer, false), None);
+    assert_eq!(
+        Permission::DeleteUsers
+            .denial(Role::Operator, false)
+            .unwrap(),
+        "Role \"operator\" may not delete users"
+    );
+    assert_eq!(
+        Permission::UpdateUser
+            .denial(Role::SelfOnly, false)
+            .unwrap(),
+        "Role \"self\" may only update its own record"
+    );
+    assert_eq!(
+        Permission::ListUsers.denial(Role::SelfOnly, true).unwrap(),
+        "Role \"self\" may not list users"
+    );
+}
+
+#[test]
+fn test_scopes_and_roles() {
+    assert_eq!(Permission::ListUsers.scope(), Scope::UsersRead);
+    assert_eq!(Permission::ReadUser.scope(), Scope::UsersRead);
+    assert_eq!(Permission::DeleteUsers.scope(), Scope::UsersWrite);
+    assert_eq!(
+        Permission::UpdateUser.roles(),
+        "admin, operator, self (own record)"
+    );
+    assert_eq!(Permission::ManageAccess.roles(), "admin");
+}
+
+#[test]
+fn test_role_round_trip() {
+    for role in [Role::Admin, Role::Operator, Role::Viewer, Role::SelfOnly] {
+        assert_eq!(role.as_str().parse::<Role>(), Ok(role));
+        assert_eq!(
+            serde_json::to_value(role).unwrap(),
+            serde_json::json!(role.as_str())
+        );
+    }
+    assert!("root".parse::<Role>().is_err());
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use crate::core::rate_limit::RateLimit;
+
+fn per_minute(limit: u32) -> RateLimit {
+    RateLimit {
+        limit,
+        window: Duration::from_secs(60),
+    }
+}
+
+#[test]
+fn test_parse_rate_limit() {
+    assert_eq!("600/1m".parse::<RateLimit>(), Ok(per_minute(600)));
+    assert_eq!(
+        " 5 / 10s ".parse::<RateLimit>(),
+        Ok(RateLimit {
+            limit: 5,
+            window: Duration::from_secs(10),
+        })
+    );
+    assert_eq!(per_minute(600).to_string(), "600/60s");
+    for invalid in ["600", "0/1m", "600/500ms", "many/1m", "600/soon"] {
+        assert!(invalid.parse::<RateLimit>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_decide_within_limit() {
+    let decision = per_minute(10).decide(Duration::from_secs(15), 4, 0);
+    assert!(decision.allowed);
+    assert_eq!(decision.limit, 10);
+    assert_eq!(decision.remaining, 6);
+    assert_eq!(decision.reset, 45);
+    assert_eq!(decision.retry_after, 0);
+
+    // The last request the limit allows
+    let decision = per_minute(10).decide(Duration::from_secs(15), 10, 0);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+}
+
+#[test]
+fn test_decide_over_limit() {
+    let decision = per_minute(10).decide(Duration::from_secs(15), 11, 0);
+    assert!(!decision.allowed);
+    assert_eq!(decision.remaining, 0);
+    // The window must end, then enough of the next one pass for the 11
+    // counted requests to fade to 9: 60 * (1 - 9 / 11) ~ 11s
+    assert_eq!(decision.retry_after, 45 + 11);
+}
+
+#[test]
+fn test_previous_window_fades() {
+    let limit = per_minute(10);
+    // Three quarters of the previous window still overlap: 8 * 0.75 + 4 = 10
+    let decision = limit.decide(Duration::from_secs(15), 4, 8);
+    assert!(decision.allowed);
+    assert_eq!(decision.remaining, 0);
+
+    // 8 * 0.75 + 5 = 11; room for one more once 8 * overlap <= 4
+    let decision = limit.decide(Duration::from_secs(15), 5, 8);
+    assert!(!decision.allowed);
+    assert_eq!(decision.retry_after, 15);
+
+    // Late in the window the previous one hardly counts
+    let decision = limit.decide(Duration::from_secs(57), 5, 8);
+    assert!(decision.allowed);
+    assert_eq!(decision.reset, 3);
+}
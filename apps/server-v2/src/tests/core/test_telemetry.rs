@@ -0,0 +1,66 @@
+use opentelemetry::Value;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{SpanId, SpanKind, TraceContextExt, TraceId};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use std::collections::HashMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::core::telemetry::subscriber;
+use crate::tests::utils::telemetry::{attribute, find, finished_spans, span_id, tracer_provider};
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[test]
+fn test_spans_nest() {
+    let trace_id = tracing::subscriber::with_default(subscriber(tracer_provider()), || {
+        let outer = tracing::info_span!("outer", otel.kind = "server", answer = 42);
+        let _outer = outer.enter();
+        // Spans of other crates are skipped, their children kept
+        let foreign = tracing::info_span!(target: "other_crate", "foreign");
+        let _foreign = foreign.enter();
+        let inner = tracing::info_span!("inner", otel.name = "renamed");
+        let _inner = inner.enter();
+        tracing::info!(rows = 3, "fetched");
+        inner.context().span().span_context().trace_id()
+    });
+
+    let spans = finished_spans(trace_id);
+    assert_eq!(spans.len(), 2, "{spans:#?}");
+    let (outer, inner) = (find(&spans, "outer"), find(&spans, "renamed"));
+
+    assert_eq!(outer.span_kind, SpanKind::Server);
+    assert_eq!(outer.parent_span_id, SpanId::INVALID);
+    assert_eq!(attribute(outer, "answer"), Some(&Value::I64(42)));
+
+    assert_eq!(inner.span_kind, SpanKind::Internal);
+    assert_eq!(inner.parent_span_id, span_id(outer));
+    let events = &inner.events.events;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].name, "fetched");
+    assert!(
+        events[0]
+            .attributes
+            .iter()
+            .any(|attribute| attribute.key.as_str() == "rows" && attribute.value == Value::I64(3))
+    );
+    assert!(outer.start_time <= inner.start_time && inner.end_time <= outer.end_time);
+}
+
+#[test]
+fn test_remote_parent() {
+    let headers = HashMap::from([("traceparent".to_string(), TRACEPARENT.to_string())]);
+    let caller = TraceContextPropagator::new().extract(&headers);
+    let remote = caller.span().span_context().clone();
+    assert!(remote.is_valid() && remote.is_sampled());
+
+    tracing::subscriber::with_default(subscriber(tracer_provider()), || {
+        let span = tracing::info_span!("handled");
+        span.set_parent(caller).unwrap();
+    });
+
+    let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+    let spans = finished_spans(trace_id);
+    let handled = find(&spans, "handled");
+    assert_eq!(handled.parent_span_id, remote.span_id());
+    assert!(handled.parent_span_is_remote);
+}
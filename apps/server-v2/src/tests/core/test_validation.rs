@@ -0,0 +1,79 @@
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+
+use crate::core::errors::AppError;
+use crate::core::validation::{self, Validate};
+use crate::schemas::users::{UserCreate, UserUpdate};
+
+#[test]
+fn test_email() {
+    for valid in ["john@example.com", "john.doe+tag@mail.example.co", "a@b.io"] {
+        assert_eq!(validation::email(valid), None, "{valid}");
+    }
+    for invalid in [
+        "",
+        "john",
+        "john@",
+        "@example.com",
+        "john@example",
+        "john doe@example.com",
+        "john..doe@example.com",
+        "john@-example.com",
+        "john@example..com",
+    ] {
+        assert!(validation::email(invalid).is_some(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_name() {
+    let length = validation::length(1..=5);
+    assert_eq!(length("José"), None);
+    assert!(length("   ").is_some());
+    assert!(length("Johnathan").is_some());
+
+    assert_eq!(validation::name_charset("Anne-Marie O'Neil Jr."), None);
+    assert!(validation::name_charset("<script>").is_some());
+}
+
+#[test]
+fn test_age() {
+    let range = validation::range(0..=150);
+    assert_eq!(range(&42), None);
+    assert!(range(&-1).is_some());
+    assert!(range(&1000).is_some());
+}
+
+#[test]
+fn test_user_create_lists_every_invalid_field() {
+    let user = UserCreate {
+        email: "not-an-email".to_string(),
+        name: String::new(),
+        age: Some(-3),
+    };
+    let error = user.validate().expect_err("user should be invalid");
+    assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let AppError::InvalidFields(fields) = error else {
+        panic!("expected field errors, got {error:?}");
+    };
+    let names: Vec<&str> = fields.iter().map(|field| field.field.as_str()).collect();
+    assert_eq!(names, ["email", "name", "age"]);
+}
+
+#[test]
+fn test_user_update() {
+    let update = UserUpdate {
+        email: "john@example.com".to_string(),
+        name: "John".to_string(),
+        age: None,
+        is_active: Some(false),
+    };
+    assert!(update.validate().is_ok());
+
+    let update = UserUpdate {
+        age: Some(200),
+        ..update
+    };
+    assert_eq!(update.validate().unwrap_err().fields()[0].field, "age");
+}
@@ -1 +1,2 @@
+pub mod test_api_keys;
 pub mod test_users;
\ No newline at end of file
@@ -0,0 +1,141 @@
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+
+use crate::core::database::DatabaseService;
+use crate::crud::api_keys::ApiKeyService;
+use crate::schemas::api_keys::{ApiKeyCreate, Scope};
+use crate::tests::utils::random::random_string;
+
+async fn setup() -> (DatabaseService, ApiKeyService) {
+    let db = DatabaseService::init(None).await;
+    (db, ApiKeyService {})
+}
+
+fn request(scopes: Vec<Scope>) -> ApiKeyCreate {
+    ApiKeyCreate {
+        name: format!("  service {}  ", random_string(8)),
+        scopes,
+    }
+}
+
+#[tokio::test]
+async fn test_create_and_authenticate() {
+    let (db, service) = setup().await;
+    let (created, key) = service
+        .create_api_key(
+            request(vec![Scope::UsersWrite, Scope::UsersRead, Scope::UsersWrite]),
+            &db.connection,
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.prefix, key.prefix);
+    assert_eq!(created.name, created.name.trim());
+    assert_eq!(created.scopes, vec!["users:read", "users:write"]);
+    assert_eq!(created.usage_count, 0);
+    assert!(created.last_used_at.is_none());
+    // Only the hash is stored
+    assert!(!created.key_hash.contains(&key.expose()));
+
+    let found = service
+        .authenticate(&key.expose(), &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(found.id, created.id);
+    service
+        .authenticate(&key.expose(), &db.connection)
+        .await
+        .unwrap();
+    let listed = service.get_api_keys(&db.connection).await.unwrap();
+    let stored = listed
+        .iter()
+        .find(|api_key| api_key.id == created.id)
+        .unwrap();
+    assert_eq!(stored.usage_count, 2);
+    assert!(stored.last_used_at.is_some());
+    // Using a key doesn't count as changing it
+    assert_eq!(stored.updated_at, created.updated_at);
+}
+
+#[tokio::test]
+async fn test_authenticate_rejects_bad_keys() {
+    let (db, service) = setup().await;
+    let (_, key) = service
+        .create_api_key(request(vec![Scope::UsersRead]), &db.connection)
+        .await
+        .unwrap();
+    let wrong_secret = format!("umk_{}_{}", key.prefix, random_string(43));
+    for presented in ["", "garbage", wrong_secret.as_str()] {
+        let err = service
+            .authenticate(presented, &db.connection)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED, "{presented}");
+    }
+}
+
+#[tokio::test]
+async fn test_rotate_api_key() {
+    let (db, service) = setup().await;
+    let (created, old_key) = service
+        .create_api_key(request(vec![Scope::UsersRead]), &db.connection)
+        .await
+        .unwrap();
+    let (rotated, new_key) = service
+        .rotate_api_key(created.id, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(rotated.id, created.id);
+    assert_eq!(rotated.scopes, created.scopes);
+    assert_ne!(rotated.prefix, created.prefix);
+    assert!(rotated.rotated_at.is_some());
+
+    assert!(
+        service
+            .authenticate(&old_key.expose(), &db.connection)
+            .await
+            .is_err()
+    );
+    assert!(
+        service
+            .authenticate(&new_key.expose(), &db.connection)
+            .await
+            .is_ok()
+    );
+
+    let err = service
+        .rotate_api_key(i32::MAX, &db.connection)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_revoke_api_key() {
+    let (db, service) = setup().await;
+    let (created, key) = service
+        .create_api_key(request(vec![Scope::UsersRead]), &db.connection)
+        .await
+        .unwrap();
+    let revoked = service
+        .revoke_api_key(created.id, &db.connection)
+        .await
+        .unwrap();
+    assert!(revoked.revoked_at.is_some());
+    // Idempotent
+    let again = service
+        .revoke_api_key(created.id, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(again.revoked_at, revoked.revoked_at);
+
+    let err = service
+        .authenticate(&key.expose(), &db.connection)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::UNAUTHORIZED);
+    let err = service
+        .rotate_api_key(created.id, &db.connection)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::CONFLICT);
+}
@@ -1,9 +1,17 @@
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+use serde_json::json;
+
+use crate::core::cache::REDIS_SERVICE;
 use crate::core::database::DatabaseService;
 use crate::crud::UserService;
-use crate::models::users::Model as UserModel;
-use crate::schemas::users::{UserCreate, UserUpdate};
+use crate::models::users::{Model as UserModel, Role};
+use crate::schemas::api::{Cursor, CursorDirection, Precondition};
+use crate::schemas::users::{
+    BulkMode, BulkOperation, BulkRequest, UserCreate, UserFilters, UserPatch, UserSort, UserUpdate,
+};
+use crate::tests::utils::random::{random_email, random_int, random_string};
 use crate::tests::utils::users::create_random_user;
-use crate::tests::utils::utils::{random_email, random_int, random_string};
 
 // Helper to init DB + service
 async fn setup() -> (DatabaseService, UserService) {
@@ -22,7 +30,6 @@ async fn test_create_user() {
     let user = user_service
         .create_user(user_create.clone(), &db.connection)
         .await
-        .ok()
         .expect("failed to create user");
 
     assert_eq!(user.email, user_create.email);
@@ -41,7 +48,6 @@ async fn test_get_user() {
     let retrieved = user_service
         .get_user_by_id(user.id as u16, &db.connection)
         .await
-        .ok()
         .expect("user should exist");
 
     assert_eq!(retrieved.id, user.id);
@@ -60,8 +66,8 @@ async fn test_get_user_not_found() {
         .await;
     assert!(result.is_err());
     let err = result.err().unwrap();
-    assert_eq!(err.status_code, 404);
-    assert!(err.message.contains("not found"));
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    assert!(err.message().contains("not found"));
 }
 
 #[tokio::test]
@@ -74,7 +80,6 @@ async fn test_get_user_by_email() {
     let retrieved = user_service
         .get_user_by_email(user.email.as_str(), &db.connection)
         .await
-        .ok()
         .expect("user should exist");
     assert_eq!(retrieved.id, user.id);
     assert_eq!(retrieved.email, user.email);
@@ -89,8 +94,8 @@ async fn test_get_user_by_email_not_found() {
         .await;
     assert!(result.is_err());
     let err = result.err().unwrap();
-    assert_eq!(err.status_code, 404);
-    assert!(err.message.contains("not found"));
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    assert!(err.message().contains("not found"));
 }
 
 #[tokio::test]
@@ -108,22 +113,32 @@ async fn test_get_users_and_search() {
 
     // List users
     let users = user_service
-        .get_users(&db.connection, 1, 10, None)
+        .get_users(&db.connection, 1, 10, &UserFilters::default())
         .await
-        .ok()
         .expect("should list users");
-    assert!(users.len() >= 5);
+    assert!(users.items.len() >= 5);
+    assert!(users.total >= 5);
+    assert_eq!(users.pages, users.total.div_ceil(10));
 
     // Search using substring of first user's name
     let needle = created[0].name.chars().take(5).collect::<String>();
     let searched = user_service
-        .get_users(&db.connection, 1, 10, Some(needle.clone()))
+        .get_users(
+            &db.connection,
+            1,
+            10,
+            &UserFilters {
+                search: Some(needle.clone()),
+                ..Default::default()
+            },
+        )
         .await
-        .ok()
         .expect("search should work");
-    assert!(!searched.is_empty());
+    assert!(!searched.items.is_empty());
+    assert!(searched.total <= users.total);
     assert!(
         searched
+            .items
             .iter()
             .all(|u| u.name.contains(&needle) || u.email.contains(&needle))
     );
@@ -139,15 +154,14 @@ async fn test_update_user() {
     let new_name = format!("updated_{}", random_string(8));
     let new_email = random_email();
     let update = UserUpdate {
-        name: Some(new_name.clone()),
-        email: Some(new_email.clone()),
+        name: new_name.clone(),
+        email: new_email.clone(),
         age: Some(30),
         is_active: Some(true),
     };
     let updated = user_service
-        .update_user(user.id as u16, update, &db.connection)
+        .update_user(user.id as u16, update, &Precondition::Any, &db.connection)
         .await
-        .ok()
         .expect("update should succeed");
     assert_eq!(updated.id, user.id);
     assert_eq!(updated.name, new_name);
@@ -159,15 +173,556 @@ async fn test_update_user() {
 async fn test_update_user_not_found() {
     let (db, user_service) = setup().await;
     let update = UserUpdate {
-        name: Some(random_string(10)),
-        email: Some(random_email()),
+        name: random_string(10),
+        email: random_email(),
         age: Some(40),
         is_active: Some(true),
     };
     let result = user_service
-        .update_user(65535, update, &db.connection)
+        .update_user(65535, update, &Precondition::Any, &db.connection)
         .await;
     assert!(result.is_err());
     let err = result.err().unwrap();
-    assert_eq!(err.status_code, 404);
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_create_user_duplicate_email() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let user_create = UserCreate {
+        email: user.email.clone(),
+        name: random_string(16),
+        age: None,
+    };
+    let err = user_service
+        .create_user(user_create, &db.connection)
+        .await
+        .expect_err("duplicate email should be rejected");
+    assert_eq!(err.status_code(), StatusCode::CONFLICT);
+    assert_eq!(err.fields()[0].field, "email");
+}
+
+#[tokio::test]
+async fn test_update_user_duplicate_email() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let other = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let update = UserUpdate {
+        email: other.email,
+        ..UserUpdate::from(&user)
+    };
+    let err = user_service
+        .update_user(user.id as u16, update, &Precondition::Any, &db.connection)
+        .await
+        .expect_err("duplicate email should be rejected");
+    assert_eq!(err.status_code(), StatusCode::CONFLICT);
+    assert_eq!(err.fields()[0].field, "email");
+}
+
+#[tokio::test]
+async fn test_get_users_by_cursor() {
+    let (db, user_service) = setup().await;
+    // A shared tag in the names isolates this test's users from the rest of the table
+    let tag = random_string(24);
+    let mut created: Vec<UserModel> = Vec::new();
+    for i in 0..5 {
+        let user_create = UserCreate {
+            email: random_email(),
+            name: format!("{tag} {i}"),
+            age: None,
+        };
+        created.push(
+            user_service
+                .create_user(user_create, &db.connection)
+                .await
+                .unwrap(),
+        );
+    }
+
+    let filters = UserFilters {
+        search: Some(tag.clone()),
+        ..Default::default()
+    };
+    let mut seen = Vec::new();
+    let mut direction = CursorDirection::After(None);
+    loop {
+        let page = user_service
+            .get_users_by_cursor(&db.connection, direction, 2, &filters)
+            .await
+            .expect("cursor page should load");
+        assert!(page.items.len() <= 2);
+        seen.extend(page.items.iter().map(|user| user.id));
+        match page.next_cursor {
+            Some(cursor) => {
+                direction = CursorDirection::After(Some(Cursor::decode(&cursor).unwrap()))
+            }
+            None => break,
+        }
+    }
+    assert_eq!(seen, created.iter().map(|user| user.id).collect::<Vec<_>>());
+
+    // Walking back from the last user returns the two before it, in order
+    let page = user_service
+        .get_users_by_cursor(
+            &db.connection,
+            CursorDirection::Before(Cursor { id: created[4].id }),
+            2,
+            &filters,
+        )
+        .await
+        .unwrap();
+    let ids: Vec<i32> = page.items.iter().map(|user| user.id).collect();
+    assert_eq!(ids, [created[2].id, created[3].id]);
+    assert!(page.prev_cursor.is_some());
+}
+
+#[test]
+fn test_cursor_round_trip() {
+    let cursor = Cursor { id: 42 };
+    assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    assert!(Cursor::decode("not a cursor").is_err());
+}
+
+#[tokio::test]
+async fn test_get_users_sorted_and_filtered() {
+    let (db, user_service) = setup().await;
+    let tag = random_string(24);
+    for (i, age) in [20, 30, 40].into_iter().enumerate() {
+        let user_create = UserCreate {
+            email: random_email(),
+            name: format!("{tag} {i}"),
+            age: Some(age),
+        };
+        user_service
+            .create_user(user_create, &db.connection)
+            .await
+            .unwrap();
+    }
+
+    let filters = UserFilters {
+        search: Some(tag),
+        is_active: Some(true),
+        age_min: Some(25),
+        sort: UserSort::parse_list("-age").unwrap(),
+        ..Default::default()
+    };
+    let users = user_service
+        .get_users(&db.connection, 1, 10, &filters)
+        .await
+        .expect("filtered listing should work");
+    let ages: Vec<Option<i32>> = users.items.iter().map(|user| user.age).collect();
+    assert_eq!(ages, [Some(40), Some(30)]);
+    assert_eq!(users.total, 2);
+
+    // The cached entry of the unfiltered listing must not be served for this one
+    let other = UserFilters {
+        age_max: Some(25),
+        ..filters.clone()
+    };
+    assert_ne!(filters.cache_key(), other.cache_key());
+}
+
+#[test]
+fn test_parse_sort() {
+    let sort = UserSort::parse_list("-age, name").unwrap();
+    assert_eq!(
+        sort.iter().map(UserSort::to_string).collect::<Vec<_>>(),
+        ["-age", "name"]
+    );
+    assert!(UserSort::parse_list("password").is_err());
+    assert!(UserSort::parse_list("name,-name").is_err());
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_restore_user() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+
+    let deleted = user_service
+        .delete_user(user.id as u16, &Precondition::Any, &db.connection)
+        .await
+        .expect("delete should succeed");
+    assert!(deleted.deleted_at.is_some());
+
+    // Hidden from every default query
+    let err = user_service
+        .get_user_by_id(user.id as u16, &db.connection)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    assert!(
+        user_service
+            .get_user_by_email(&user.email, &db.connection)
+            .await
+            .is_err()
+    );
+    let filters = UserFilters {
+        search: Some(user.email.clone()),
+        ..Default::default()
+    };
+    let listed = user_service
+        .get_users(&db.connection, 1, 10, &filters)
+        .await
+        .unwrap();
+    assert_eq!(listed.total, 0);
+
+    let restored = user_service
+        .restore_user(user.id as u16, &db.connection)
+        .await
+        .expect("restore should succeed");
+    assert_eq!(restored.deleted_at, None);
+    let fetched = user_service
+        .get_user_by_id(user.id as u16, &db.connection)
+        .await
+        .expect("restored user should be visible");
+    assert_eq!(fetched.email, user.email);
+
+    // Only deleted users can be restored
+    let err = user_service
+        .restore_user(user.id as u16, &db.connection)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_purge_user() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+
+    user_service
+        .purge_user(user.id as u16, &Precondition::Any, &db.connection)
+        .await
+        .expect("purge should succeed");
+    let err = user_service
+        .restore_user(user.id as u16, &db.connection)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_patch_user() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    assert!(user.age.is_some());
+
+    // Absent members are kept, explicit nulls clear
+    let patch = UserPatch::Merge(json!({"name": "Patched", "age": null}));
+    let patched = user_service
+        .patch_user(user.id as u16, patch, &Precondition::Any, &db.connection)
+        .await
+        .expect("merge patch should apply");
+    assert_eq!(patched.name, "Patched");
+    assert_eq!(patched.age, None);
+    assert_eq!(patched.email, user.email);
+    assert_eq!(patched.is_active, user.is_active);
+
+    let patch = UserPatch::Json(
+        serde_json::from_value(json!([
+            {"op": "test", "path": "/name", "value": "Patched"},
+            {"op": "add", "path": "/age", "value": 41}
+        ]))
+        .unwrap(),
+    );
+    let patched = user_service
+        .patch_user(user.id as u16, patch, &Precondition::Any, &db.connection)
+        .await
+        .expect("json patch should apply");
+    assert_eq!(patched.age, Some(41));
+
+    // Required members can't be removed, and the result is validated
+    for patch in [
+        UserPatch::Merge(json!({"name": null})),
+        UserPatch::Merge(json!({"id": 1})),
+        UserPatch::Merge(json!({"age": -5})),
+    ] {
+        let err = user_service
+            .patch_user(
+                user.id as u16,
+                patch.clone(),
+                &Precondition::Any,
+                &db.connection,
+            )
+            .await
+            .expect_err("patch should be rejected");
+        assert_eq!(
+            err.status_code(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{patch:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_timestamps_are_maintained() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let created_at = user.created_at;
+    assert_eq!(user.updated_at, created_at);
+
+    let update = UserUpdate {
+        name: random_string(12),
+        ..UserUpdate::from(&user)
+    };
+    let updated = user_service
+        .update_user(user.id as u16, update, &Precondition::Any, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(updated.created_at, created_at);
+    assert!(updated.updated_at > created_at);
+}
+
+#[tokio::test]
+async fn test_update_user_stale_version() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+
+    let updated = user_service
+        .update_user(
+            user.id as u16,
+            UserUpdate::from(&user),
+            &Precondition::Versions(vec![user.version]),
+            &db.connection,
+        )
+        .await
+        .expect("matching version should be accepted");
+    assert_eq!(updated.version, user.version + 1);
+
+    let err = user_service
+        .update_user(
+            user.id as u16,
+            UserUpdate::from(&user),
+            &Precondition::Versions(vec![user.version]),
+            &db.connection,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+}
+
+#[tokio::test]
+async fn test_bulk_users_transactional_rolls_back() {
+    let (db, user_service) = setup().await;
+    let existing = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let new_email = random_email();
+
+    let operations = vec![
+        BulkOperation::Create {
+            user: UserCreate {
+                email: new_email.clone(),
+                name: random_string(16),
+                age: None,
+            },
+        },
+        BulkOperation::Delete {
+            id: existing.id,
+            version: Some(existing.version),
+        },
+        // Clashes with the user already there
+        BulkOperation::Create {
+            user: UserCreate {
+                email: existing.email.clone(),
+                name: random_string(16),
+                age: None,
+            },
+        },
+    ];
+    let error = user_service
+        .bulk_users(
+            BulkRequest {
+                mode: BulkMode::Transactional,
+                operations: operations.clone(),
+            },
+            &db.connection,
+        )
+        .await
+        .expect_err("the last create must fail");
+    assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    assert_eq!(error.fields()[0].field, "operations[2].email");
+
+    // Neither the create nor the delete before the failure were kept
+    let result = user_service
+        .get_user_by_email(&new_email, &db.connection)
+        .await;
+    assert!(result.is_err());
+    let user = user_service
+        .get_user_by_id(existing.id as u16, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(user.version, existing.version);
+
+    let response = user_service
+        .bulk_users(
+            BulkRequest {
+                mode: BulkMode::BestEffort,
+                operations,
+            },
+            &db.connection,
+        )
+        .await
+        .unwrap();
+    assert_eq!((response.succeeded, response.failed), (2, 1));
+    let statuses: Vec<u16> = response
+        .results
+        .iter()
+        .map(|result| result.status)
+        .collect();
+    assert_eq!(statuses, [201, 204, 409]);
+}
+
+#[tokio::test]
+async fn test_passwords() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    let id = user.id as u16;
+
+    // No password yet: nobody can log in
+    let result = user_service
+        .authenticate(
+            &user.email,
+            "correct horse battery".to_string(),
+            &db.connection,
+        )
+        .await;
+    assert_eq!(result.unwrap_err().status_code(), StatusCode::UNAUTHORIZED);
+
+    user_service
+        .set_password(id, "correct horse battery".to_string(), &db.connection)
+        .await
+        .unwrap();
+    let authenticated = user_service
+        .authenticate(
+            &user.email,
+            "correct horse battery".to_string(),
+            &db.connection,
+        )
+        .await
+        .unwrap();
+    assert_eq!(authenticated.id, user.id);
+
+    for (email, password) in [
+        (user.email.as_str(), "wrong horse battery"),
+        ("nobody@example.com", "correct horse battery"),
+    ] {
+        let result = user_service
+            .authenticate(email, password.to_string(), &db.connection)
+            .await;
+        assert_eq!(result.unwrap_err().status_code(), StatusCode::UNAUTHORIZED);
+    }
+
+    let result = user_service
+        .change_password(
+            user.id,
+            "wrong horse battery".to_string(),
+            "staple horse battery".to_string(),
+            &db.connection,
+        )
+        .await;
+    assert_eq!(result.unwrap_err().status_code(), StatusCode::FORBIDDEN);
+    user_service
+        .change_password(
+            user.id,
+            "correct horse battery".to_string(),
+            "staple horse battery".to_string(),
+            &db.connection,
+        )
+        .await
+        .unwrap();
+    assert!(
+        user_service
+            .authenticate(
+                &user.email,
+                "staple horse battery".to_string(),
+                &db.connection
+            )
+            .await
+            .is_ok()
+    );
+
+    // The hash stays out of serialized users and of the cache
+    let fetched = user_service
+        .get_user_by_id(id, &db.connection)
+        .await
+        .unwrap();
+    let serialized = serde_json::to_value(&fetched).unwrap();
+    assert!(serialized.get("password_hash").is_none());
+    let cached = REDIS_SERVICE.get(&format!("user:id:{}", user.id)).await;
+    if let Some(cached) = cached {
+        assert!(!cached.contains("argon2"), "{cached}");
+    }
+
+    let result = user_service
+        .set_password(0, "correct horse battery".to_string(), &db.connection)
+        .await;
+    assert_eq!(result.unwrap_err().status_code(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_set_role() {
+    let (db, user_service) = setup().await;
+    let user = create_random_user(true, Some(&db.connection))
+        .await
+        .as_model()
+        .unwrap();
+    assert_eq!(user.role, Role::SelfOnly);
+    let id = user.id as u16;
+
+    // Cache the user, the new role must not be served from it
+    user_service
+        .get_user_by_id(id, &db.connection)
+        .await
+        .unwrap();
+    let updated = user_service
+        .set_role(id, Role::Operator, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(updated.role, Role::Operator);
+    assert_eq!(updated.version, user.version + 1);
+    let fetched = user_service
+        .get_user_by_id(id, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(fetched.role, Role::Operator);
+
+    // Same role, nothing to write
+    let unchanged = user_service
+        .set_role(id, Role::Operator, &db.connection)
+        .await
+        .unwrap();
+    assert_eq!(unchanged.version, updated.version);
+
+    let result = user_service.set_role(0, Role::Admin, &db.connection).await;
+    assert_eq!(result.unwrap_err().status_code(), StatusCode::NOT_FOUND);
 }
@@ -1,3 +1,4 @@
 pub mod api;
+pub mod random;
+pub mod telemetry;
 pub mod users;
-pub mod utils;
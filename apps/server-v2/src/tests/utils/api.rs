@@ -1,19 +1,62 @@
-use crate::core::database::DatabaseService;
-use actix_web::{self, web};
+use crate::api::guards::API_KEY_HEADER;
+use crate::api::preconditions::version_etag;
+use crate::core::database::{DatabaseParams, DatabaseService};
+use crate::core::health::HealthService;
+use crate::core::jwt::JWT;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, HeaderName, HeaderValue, IfMatch};
+use actix_web::middleware::Next;
+use actix_web::{self, Error, web};
 
 pub struct TestAPIParameters {
     pub prefix: String,
     pub db: DatabaseService,
     pub app_data: web::Data<DatabaseService>,
+    pub health_data: web::Data<HealthService>,
 }
 
 impl TestAPIParameters {
     pub async fn new() -> TestAPIParameters {
         let db = DatabaseService::init(None).await;
+        let questdb = DatabaseService::init_lazy(Some(DatabaseParams::questdb())).await;
         TestAPIParameters {
             prefix: "/api/v2".to_string(),
             db: db.clone(),
+            health_data: web::Data::new(HealthService::new(
+                db.connection.clone(),
+                questdb.connection,
+            )),
             app_data: web::Data::new(db),
         }
     }
 }
+
+/// `If-Match` header naming the given version of a user
+pub fn if_match(version: i32) -> IfMatch {
+    IfMatch::Items(vec![version_etag(version).0])
+}
+
+/// Signed token for `subject`, as `POST /auth/token` would issue it
+pub fn token(subject: &str) -> String {
+    JWT.encode(&JWT.claims(subject)).unwrap()
+}
+
+/// `Authorization` header carrying a valid token for `subject`
+pub fn bearer(subject: &str) -> (HeaderName, String) {
+    (AUTHORIZATION, format!("Bearer {}", token(subject)))
+}
+
+/// Test middleware authenticating every request that doesn't carry its own
+/// `Authorization` or `X-API-Key` header, for tests that aren't about
+/// authentication.
+pub async fn authorize(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    if !req.headers().contains_key(AUTHORIZATION) && !req.headers().contains_key(API_KEY_HEADER) {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token("tests"))).unwrap();
+        req.headers_mut().insert(AUTHORIZATION, value);
+    }
+    next.call(req).await
+}
@@ -0,0 +1,54 @@
+use std::sync::LazyLock;
+
+use opentelemetry::global;
+use opentelemetry::trace::{SpanId, TraceId};
+use opentelemetry::{Key, Value};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+
+/// One provider for every test: statement spans come from the global one,
+/// which tests running at the same time can't each replace. Tests tell their
+/// spans apart by trace.
+static PROVIDER: LazyLock<(SdkTracerProvider, InMemorySpanExporter)> = LazyLock::new(|| {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    global::set_tracer_provider(provider.clone());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    (provider, exporter)
+});
+
+/// Exports each span in memory as soon as it ends.
+pub fn tracer_provider() -> &'static SdkTracerProvider {
+    &PROVIDER.0
+}
+
+/// The spans of one trace that have ended.
+pub fn finished_spans(trace_id: TraceId) -> Vec<SpanData> {
+    PROVIDER
+        .1
+        .get_finished_spans()
+        .unwrap()
+        .into_iter()
+        .filter(|span| span.span_context.trace_id() == trace_id)
+        .collect()
+}
+
+pub fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|span| span.name == name)
+        .unwrap_or_else(|| panic!("no {name} span in {spans:#?}"))
+}
+
+pub fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a Value> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key == Key::from(key.to_string()))
+        .map(|attribute| &attribute.value)
+}
+
+pub fn span_id(span: &SpanData) -> SpanId {
+    span.span_context.span_id()
+}
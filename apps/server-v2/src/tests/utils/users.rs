@@ -1,4 +1,4 @@
-use super::utils::{random_email, random_int, random_string};
+use super::random::{random_email, random_int, random_string};
 use crate::crud::UserService;
 use crate::models::users::Model as UserModel;
 use crate::schemas::users::UserCreate;
@@ -37,7 +37,6 @@ pub async fn create_random_user(
         let model = user_service
             .create_user(user_create, db)
             .await
-            .ok()
             .expect("Failed to create user in DB");
         RandomUser::Model(model)
     } else {